@@ -0,0 +1,303 @@
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::buddy_allocator::PlacementStrategy;
+use crate::memory_manager::{ManagerConfig, MemoryManager};
+use crate::workload::WorkloadOp;
+
+/// Arena usage after some number of workload operations
+#[derive(Debug, Clone, Copy)]
+pub struct FragmentationSample {
+    pub operation: usize,
+    pub used_bytes: usize,
+    pub utilization: f64,
+    pub fragmentation: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub strategy: PlacementStrategy,
+    pub operations: usize,
+    pub allocations: usize,
+    pub frees: usize,
+    pub failures: usize,
+    pub elapsed: Duration,       // Time spent inside the manager only
+    pub latencies_ns: Vec<u64>,  // Per operation, sorted ascending
+    pub samples: Vec<FragmentationSample>,
+}
+
+impl BenchResult {
+    pub fn throughput(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds == 0.0 { 0.0 } else { self.operations as f64 / seconds }
+    }
+
+    /// Latency at percentile `p` (0-100), nearest-rank
+    pub fn percentile(&self, p: f64) -> u64 {
+        if self.latencies_ns.is_empty() {
+            return 0;
+        }
+        let rank = ((p / 100.0) * self.latencies_ns.len() as f64).ceil() as usize;
+        self.latencies_ns[rank.clamp(1, self.latencies_ns.len()) - 1]
+    }
+
+    pub fn failure_rate(&self) -> f64 {
+        if self.allocations == 0 { 0.0 } else { self.failures as f64 / self.allocations as f64 }
+    }
+
+    pub fn peak_fragmentation(&self) -> f64 {
+        self.samples.iter().map(|sample| sample.fragmentation).fold(0.0, f64::max)
+    }
+}
+
+/// Runs a generated workload against a fresh, quiet manager, sampling usage every `sample_every` operations
+pub fn run_workload(ops: &[WorkloadOp], strategy: PlacementStrategy, sample_every: usize) -> BenchResult {
+    run_workload_with_config(ops, ManagerConfig { strategy, ..ManagerConfig::default() }, sample_every)
+}
+
+/// Like `run_workload`, with a manager built from `config`. Panics if `config` is invalid.
+pub fn run_workload_with_config(ops: &[WorkloadOp], config: ManagerConfig, sample_every: usize) -> BenchResult {
+    let strategy = config.strategy;
+    let mut memory_manager = MemoryManager::with_config(config).expect("invalid manager configuration");
+    memory_manager.set_verbose(false);
+
+    let mut ids: Vec<Option<usize>> = Vec::new(); // Block ID of each Alloc, None if it failed
+    let mut result = BenchResult {
+        strategy,
+        operations: ops.len(),
+        allocations: 0,
+        frees: 0,
+        failures: 0,
+        elapsed: Duration::ZERO,
+        latencies_ns: Vec::with_capacity(ops.len()),
+        samples: Vec::new(),
+    };
+
+    for (i, op) in ops.iter().enumerate() {
+        let started = Instant::now();
+        match *op {
+            WorkloadOp::Alloc { size } => {
+                let id = memory_manager.insert(size, Vec::new()).ok();
+                result.allocations += 1;
+                if id.is_none() {
+                    result.failures += 1;
+                }
+                ids.push(id);
+            }
+            WorkloadOp::Free { alloc_index } => {
+                if let Some(id) = ids.get(alloc_index).copied().flatten() {
+                    let _ = memory_manager.delete(id);
+                }
+                result.frees += 1;
+            }
+        }
+        let elapsed = started.elapsed();
+        result.elapsed += elapsed;
+        result.latencies_ns.push(elapsed.as_nanos() as u64);
+
+        if sample_every > 0 && ((i + 1) % sample_every == 0 || i + 1 == ops.len()) {
+            let stats = memory_manager.stats();
+            result.samples.push(FragmentationSample {
+                operation: i + 1,
+                used_bytes: stats.used_bytes,
+                utilization: stats.utilization(),
+                fragmentation: stats.fragmentation(),
+            });
+        }
+    }
+
+    result.latencies_ns.sort_unstable();
+    result
+}
+
+/// Summary per strategy, then fragmentation over time with one column per strategy
+pub fn format_table(results: &[BenchResult]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{:<10} {:>8} {:>12} {:>8} {:>8} {:>8} {:>8} {:>9} {:>9}",
+        "strategy", "ops", "ops/sec", "p50 ns", "p90 ns", "p99 ns", "max ns", "failures", "peak frag"
+    );
+    for result in results {
+        let _ = writeln!(
+            out,
+            "{:<10} {:>8} {:>12.0} {:>8} {:>8} {:>8} {:>8} {:>8.2}% {:>8.2}%",
+            result.strategy.name(),
+            result.operations,
+            result.throughput(),
+            result.percentile(50.0),
+            result.percentile(90.0),
+            result.percentile(99.0),
+            result.percentile(100.0),
+            result.failure_rate() * 100.0,
+            result.peak_fragmentation() * 100.0
+        );
+    }
+
+    let Some(first) = results.first() else {
+        return out;
+    };
+    let _ = writeln!(out, "\nFragmentation over time:");
+    let _ = write!(out, "{:>10}", "op");
+    for result in results {
+        let _ = write!(out, " {:>10}", result.strategy.name());
+    }
+    let _ = writeln!(out);
+    // At most 10 rows, always including the last sample
+    let step = first.samples.len().div_ceil(10).max(1);
+    for row in (0..first.samples.len()).rev().step_by(step).collect::<Vec<_>>().into_iter().rev() {
+        let _ = write!(out, "{:>10}", first.samples[row].operation);
+        for result in results {
+            match result.samples.get(row) {
+                Some(sample) => { let _ = write!(out, " {:>9.2}%", sample.fragmentation * 100.0); }
+                None => { let _ = write!(out, " {:>10}", "-"); }
+            }
+        }
+        let _ = writeln!(out);
+    }
+    out
+}
+
+pub fn summary_csv(results: &[BenchResult]) -> String {
+    let mut out = String::from("strategy,operations,allocations,frees,failures,ops_per_sec,p50_ns,p90_ns,p99_ns,max_ns,failure_rate,peak_fragmentation\n");
+    for result in results {
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{},{:.0},{},{},{},{},{:.6},{:.6}",
+            result.strategy.name(),
+            result.operations,
+            result.allocations,
+            result.frees,
+            result.failures,
+            result.throughput(),
+            result.percentile(50.0),
+            result.percentile(90.0),
+            result.percentile(99.0),
+            result.percentile(100.0),
+            result.failure_rate(),
+            result.peak_fragmentation()
+        );
+    }
+    out
+}
+
+pub fn timeline_csv(results: &[BenchResult]) -> String {
+    let mut out = String::from("strategy,operation,used_bytes,utilization,fragmentation\n");
+    for result in results {
+        for sample in &result.samples {
+            let _ = writeln!(
+                out,
+                "{},{},{},{:.6},{:.6}",
+                result.strategy.name(),
+                sample.operation,
+                sample.used_bytes,
+                sample.utilization,
+                sample.fragmentation
+            );
+        }
+    }
+    out
+}
+
+/// The options `mm-bench` and the `bench` subcommand accept, as printed by their `--help`
+pub const BENCH_USAGE: &str = "\
+Options:
+  --seed <n>               Workload seed (default 1)
+  --ops <n>                Number of operations (default 10000)
+  --sizes <dist>           uniform:<min>:<max> | power-law:<alpha>:<min>:<max> | bimodal:<small>:<large>:<fraction>
+  --lifetimes <dist>       uniform:<min>:<max> | exp:<mean>   (in operations)
+  --alloc-ratio <p>        Probability that an operation allocates (default 0.5)
+  --strategy <name>        first-fit | best-fit | worst-fit; repeat to compare several (default all)
+  --arena-size <bytes>     Arena size, a power of two; K and M suffixes allowed (default 64K)
+  --min-block <bytes>      Smallest block handed out, a power of two (default 1)
+  --sample-every <n>       Operations between fragmentation samples (default ops/100)
+  --csv <path>             Write the per-strategy summary as CSV
+  --timeline-csv <path>    Write the fragmentation samples as CSV";
+
+/// Command-line options shared by `mm-bench` and the main binary's `bench` subcommand
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    pub workload: crate::workload::WorkloadConfig,
+    pub strategies: Vec<PlacementStrategy>,
+    pub arena_size: usize,
+    pub min_block: usize,
+    pub sample_every: Option<usize>,
+    pub csv: Option<String>,
+    pub timeline_csv: Option<String>,
+}
+
+impl BenchOptions {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = BenchOptions {
+            workload: crate::workload::WorkloadConfig::default(),
+            strategies: Vec::new(),
+            arena_size: crate::buddy_allocator::DEFAULT_ARENA_SIZE,
+            min_block: 1,
+            sample_every: None,
+            csv: None,
+            timeline_csv: None,
+        };
+
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {}", flag));
+            let number = |value: &String| value.parse::<usize>().map_err(|_| format!("Invalid number '{}' for {}", value, flag));
+            match flag.as_str() {
+                "--seed" => {
+                    let value = value()?;
+                    options.workload.seed = value.parse().map_err(|_| format!("Invalid seed '{}'", value))?;
+                }
+                "--ops" => options.workload.operations = number(value()?)?,
+                "--sizes" => options.workload.sizes = value()?.parse()?,
+                "--lifetimes" => options.workload.lifetimes = value()?.parse()?,
+                "--alloc-ratio" => {
+                    let value = value()?;
+                    options.workload.alloc_ratio = value.parse().map_err(|_| format!("Invalid ratio '{}'", value))?;
+                }
+                "--strategy" => options.strategies.push(value()?.parse()?),
+                "--arena-size" => options.arena_size = crate::cli::parse_size(value()?).map_err(|e| format!("{} for {}", e, flag))?,
+                "--min-block" => options.min_block = crate::cli::parse_size(value()?).map_err(|e| format!("{} for {}", e, flag))?,
+                "--sample-every" => options.sample_every = Some(number(value()?)?),
+                "--csv" => options.csv = Some(value()?.clone()),
+                "--timeline-csv" => options.timeline_csv = Some(value()?.clone()),
+                _ => return Err(format!("Unknown option '{}'", flag)),
+            }
+        }
+
+        if options.strategies.is_empty() {
+            options.strategies = PlacementStrategy::ALL.to_vec();
+        }
+        options.config(options.strategies[0]).validate()?;
+        Ok(options)
+    }
+
+    /// The manager configuration for one strategy's run
+    pub fn config(&self, strategy: PlacementStrategy) -> ManagerConfig {
+        ManagerConfig { arena_size: self.arena_size, min_block: self.min_block, strategy, block_headers: false }
+    }
+}
+
+/// Generates the workload once, runs it with every requested strategy, writes any CSV
+/// files and returns the table for printing
+pub fn run_bench(options: &BenchOptions) -> Result<String, String> {
+    let ops = crate::workload::generate(&options.workload);
+    let sample_every = options.sample_every.unwrap_or((ops.len() / 100).max(1));
+    let results: Vec<BenchResult> = options.strategies
+        .iter()
+        .map(|&strategy| run_workload_with_config(&ops, options.config(strategy), sample_every))
+        .collect();
+
+    if let Some(path) = &options.csv {
+        std::fs::write(path, summary_csv(&results)).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    }
+    if let Some(path) = &options.timeline_csv {
+        std::fs::write(path, timeline_csv(&results)).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    }
+
+    let workload = &options.workload;
+    Ok(format!(
+        "Workload: seed {}, {} operations, sizes {:?}, lifetimes {:?}, alloc ratio {}\n\n{}",
+        workload.seed, workload.operations, workload.sizes, workload.lifetimes, workload.alloc_ratio,
+        format_table(&results)
+    ))
+}
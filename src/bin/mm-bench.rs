@@ -0,0 +1,27 @@
+use std::env;
+
+use memory_manager::bench::{run_bench, BenchOptions, BENCH_USAGE};
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("Usage: {} [options]\n{}", args[0], BENCH_USAGE);
+        return;
+    }
+
+    let options = match BenchOptions::parse(&args[1..]) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {}\nUsage: {} [options]\n{}", e, args[0], BENCH_USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    match run_bench(&options) {
+        Ok(table) => print!("{}", table),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
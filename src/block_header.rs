@@ -0,0 +1,78 @@
+/// Bytes at the start of every block of a manager with in-band headers
+pub const HEADER_SIZE: usize = 16;
+
+const HEADER_MAGIC: u16 = 0xB10C;
+
+/// The header a block carries inside the arena when `ManagerConfig::block_headers` is on,
+/// so the heap can be walked from its raw bytes like a boundary-tag allocator's.
+///
+/// Stored little-endian as a `u16` magic, the order and state bytes, a `u32` data length
+/// and a `u64` block ID. Free blocks have an ID and data length of 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockHeader {
+    pub order: u8, // The block is `1 << order` bytes, header included
+    pub allocated: bool,
+    pub id: usize,
+    pub data_len: usize, // Bytes of data after the header
+}
+
+impl BlockHeader {
+    pub fn free(order: u8) -> Self {
+        BlockHeader { order, allocated: false, id: 0, data_len: 0 }
+    }
+
+    pub fn size(&self) -> usize {
+        1 << self.order
+    }
+
+    pub fn encode(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0u8; HEADER_SIZE];
+        bytes[0..2].copy_from_slice(&HEADER_MAGIC.to_le_bytes());
+        bytes[2] = self.order;
+        bytes[3] = self.allocated as u8;
+        bytes[4..8].copy_from_slice(&(self.data_len as u32).to_le_bytes());
+        bytes[8..16].copy_from_slice(&(self.id as u64).to_le_bytes());
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<BlockHeader, String> {
+        let bytes: &[u8; HEADER_SIZE] = bytes.get(..HEADER_SIZE).and_then(|bytes| bytes.try_into().ok()).ok_or("header is cut off")?;
+        let magic = u16::from_le_bytes([bytes[0], bytes[1]]);
+        if magic != HEADER_MAGIC {
+            return Err(format!("bad header magic 0x{:04X}", magic));
+        }
+        let allocated = match bytes[3] {
+            0 => false,
+            1 => true,
+            state => return Err(format!("bad block state {}", state)),
+        };
+        let id = u64::from_le_bytes(bytes[8..16].try_into().expect("8 bytes"));
+        Ok(BlockHeader {
+            order: bytes[2],
+            allocated,
+            id: usize::try_from(id).map_err(|_| format!("block ID {} is too large", id))?,
+            data_len: u32::from_le_bytes(bytes[4..8].try_into().expect("4 bytes")) as usize,
+        })
+    }
+}
+
+/// Walks an arena of blocks with headers from address 0, returning the start and header of
+/// every block in address order. Fails at the first header that is missing, misplaced or
+/// does not fit its block.
+pub fn walk(arena: &[u8]) -> Result<Vec<(usize, BlockHeader)>, String> {
+    let mut blocks = Vec::new();
+    let mut start = 0;
+    while start < arena.len() {
+        let header = BlockHeader::decode(&arena[start..]).map_err(|e| format!("0x{:04X}: {}", start, e))?;
+        let size = 1usize.checked_shl(header.order.into()).unwrap_or(0);
+        if size < HEADER_SIZE || !start.is_multiple_of(size) || size > arena.len() - start {
+            return Err(format!("0x{:04X}: order {} does not fit the arena", start, header.order));
+        }
+        if header.data_len > size - HEADER_SIZE {
+            return Err(format!("0x{:04X}: {} bytes of data do not fit a {}-byte block", start, header.data_len, size));
+        }
+        blocks.push((start, header));
+        start += size;
+    }
+    Ok(blocks)
+}
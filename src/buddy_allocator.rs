@@ -1,40 +1,221 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How the allocator picks among the free blocks large enough for a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlacementStrategy {
+    #[default]
+    FirstFit, // Lowest-addressed free block that is large enough
+    BestFit,  // Smallest suitable block, lowest address on ties
+    WorstFit, // Largest suitable block, lowest address on ties
+}
+
+impl PlacementStrategy {
+    pub const ALL: [PlacementStrategy; 3] = [
+        PlacementStrategy::FirstFit,
+        PlacementStrategy::BestFit,
+        PlacementStrategy::WorstFit,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlacementStrategy::FirstFit => "first-fit",
+            PlacementStrategy::BestFit => "best-fit",
+            PlacementStrategy::WorstFit => "worst-fit",
+        }
+    }
+}
+
+impl std::fmt::Display for PlacementStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl std::str::FromStr for PlacementStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PlacementStrategy::ALL
+            .iter()
+            .copied()
+            .find(|strategy| strategy.name() == s)
+            .ok_or_else(|| format!("Unknown allocator strategy '{}' (expected first-fit, best-fit or worst-fit)", s))
+    }
+}
+
+/// Arena size used unless a `ManagerConfig` says otherwise
+pub const DEFAULT_ARENA_SIZE: usize = 65536;
+
+// A block of order k is 2^k bytes and starts at a multiple of 2^k
+fn order_of(size: usize) -> usize {
+    size.trailing_zeros() as usize
+}
+
+/// A buddy allocator: free blocks are powers of two, split in half until they fit a request
+/// and merged with their buddy when both halves are free again.
+/// See https://en.wikipedia.org/wiki/Buddy_memory_allocation
 pub struct BuddyAllocator {
-    free_blocks: Vec<(usize, usize)>, // (start, end)
+    free_lists: Vec<BTreeSet<usize>>, // Start addresses of the free blocks of each order
+    allocated: BTreeMap<usize, usize>, // Start -> order of every allocated block
+    strategy: PlacementStrategy,
+    arena_size: usize,
+    explanation: Option<Vec<String>>, // Collected while explain mode is on
+}
+
+impl Default for BuddyAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl BuddyAllocator {
     pub fn new() -> Self {
-        let free_blocks = vec![(0, 65536)];
-        BuddyAllocator { free_blocks }
-    }
-
-    // BS: see https://en.wikipedia.org/wiki/Buddy_memory_allocation
-    //
-    // You're currently cutting spaces off of a large free block to perform allocation.
-    // Instead, you should be cutting the free block in half until it fits the size.
-    //
-    // Find the first free block that is greater than or equal to the requested size.
-    // If the  block is equal to the size, allocate it and you're done.
-    // If the block is greater than the requested size, cut it in half until it meets
-    // the size requirement.
+        Self::with_strategy(PlacementStrategy::FirstFit)
+    }
+
+    pub fn with_strategy(strategy: PlacementStrategy) -> Self {
+        Self::with_arena(DEFAULT_ARENA_SIZE, strategy)
+    }
+
+    /// An allocator managing `[0, arena_size)`. An arena that is not a power of two starts
+    /// as several top-level blocks, largest first.
+    pub fn with_arena(arena_size: usize, strategy: PlacementStrategy) -> Self {
+        let max_order = if arena_size == 0 { 0 } else { order_of(arena_size.next_power_of_two()) };
+        let mut free_lists = vec![BTreeSet::new(); max_order + 1];
+        let mut start = 0;
+        for order in (0..=max_order).rev() {
+            if arena_size - start >= 1 << order {
+                free_lists[order].insert(start);
+                start += 1 << order;
+            }
+        }
+        BuddyAllocator { free_lists, allocated: BTreeMap::new(), strategy, arena_size, explanation: None }
+    }
+
+    /// An allocator in a given state, e.g. read back from a memory image: `free` and
+    /// `allocated` are `(start, end)` pairs that together must tile `[0, arena_size)` with
+    /// aligned power-of-two blocks, with no two free buddies left unmerged
+    pub fn from_blocks(arena_size: usize, strategy: PlacementStrategy, free: &[(usize, usize)], allocated: &[(usize, usize)]) -> Result<Self, String> {
+        let mut allocator = Self::with_arena(arena_size, strategy);
+        allocator.free_lists.iter_mut().for_each(BTreeSet::clear);
+
+        let mut blocks: Vec<(usize, usize, bool)> =
+            free.iter().map(|&(start, end)| (start, end, false)).chain(allocated.iter().map(|&(start, end)| (start, end, true))).collect();
+        blocks.sort_unstable();
+        let mut expected = 0;
+        for (start, end, is_allocated) in blocks {
+            let size = end.saturating_sub(start);
+            if !size.is_power_of_two() || !start.is_multiple_of(size) || end > arena_size {
+                return Err(format!("0x{:04X} - 0x{:04X} is not a buddy block of the {}-byte arena", start, end, arena_size));
+            }
+            if start != expected {
+                return Err(format!("Blocks overlap or leave a gap at 0x{:04X}", start.min(expected)));
+            }
+            expected = end;
+            if is_allocated {
+                allocator.allocated.insert(start, order_of(size));
+            } else {
+                allocator.free_lists[order_of(size)].insert(start);
+            }
+        }
+        if expected != arena_size {
+            return Err(format!("Blocks end at 0x{:04X}, short of the {}-byte arena", expected, arena_size));
+        }
+
+        for (order, starts) in allocator.free_lists.iter().enumerate() {
+            for &start in starts {
+                let buddy = start ^ (1 << order);
+                if start < buddy && starts.contains(&buddy) && order + 1 < allocator.free_lists.len() && buddy + (1 << order) <= arena_size {
+                    return Err(format!("Free buddies 0x{:04X} and 0x{:04X} of order {} were not merged", start, buddy, order));
+                }
+            }
+        }
+        Ok(allocator)
+    }
+
+    pub fn strategy(&self) -> PlacementStrategy {
+        self.strategy
+    }
+
+    pub fn arena_size(&self) -> usize {
+        self.arena_size
+    }
+
+    /// Turns explain mode on or off. While on, every allocation and deallocation records the
+    /// steps it took, ending with the buddy tree; see `take_explanation`.
+    pub fn set_explain(&mut self, explain: bool) {
+        self.explanation = if explain { Some(self.explanation.take().unwrap_or_default()) } else { None };
+    }
+
+    pub fn is_explaining(&self) -> bool {
+        self.explanation.is_some()
+    }
+
+    /// The lines recorded since the last call, oldest first
+    pub fn take_explanation(&mut self) -> Vec<String> {
+        self.explanation.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn explain(&mut self, line: impl FnOnce() -> String) {
+        if let Some(explanation) = self.explanation.as_mut() {
+            explanation.push(line());
+        }
+    }
+
+    fn explain_tree(&mut self) {
+        if self.explanation.is_some() {
+            let tree = self.render_tree();
+            self.explain(|| "  buddy tree:".to_string());
+            for line in tree.lines() {
+                self.explain(|| format!("    {}", line));
+            }
+        }
+    }
+
     pub fn allocate(&mut self, size: usize) -> Result<usize, String> {
         if size == 0 {
             return Err("Invalid size".to_string());
         }
 
         let aligned_size = size.next_power_of_two();
+        let order = order_of(aligned_size);
+        self.explain(|| format!("allocate {} bytes: order {} ({} bytes)", size, order, aligned_size));
 
-        for i in 0..self.free_blocks.len() {
-            let (start, end) = self.free_blocks[i];
-            if end - start >= aligned_size {
-                self.free_blocks.remove(i);
-                if end - start > aligned_size {
-                    self.free_blocks.push((start + aligned_size, end));
-                }
-                return Ok(start);
-            }
+        let Some((mut block_order, start)) = self.choose_block(order) else {
+            self.explain(|| format!("  no free block of order {} or higher: insufficient memory", order));
+            return Err("Insufficient memory".to_string());
+        };
+        let strategy = self.strategy;
+        self.explain(|| format!("  chose free block 0x{:04X} of order {} ({} bytes) by {}", start, block_order, 1usize << block_order, strategy));
+        self.free_lists[block_order].remove(&start);
+
+        // Keep the lower half and free the upper one until the block is the requested size
+        while block_order > order {
+            block_order -= 1;
+            let upper = start + (1 << block_order);
+            self.free_lists[block_order].insert(upper);
+            self.explain(|| format!("  split into 0x{:04X} and 0x{:04X} (order {}); 0x{:04X} stays free", start, upper, block_order, upper));
+        }
+
+        self.allocated.insert(start, order);
+        self.explain(|| format!("  allocated 0x{:04X} - 0x{:04X}", start, start + aligned_size));
+        self.explain_tree();
+        Ok(start)
+    }
+
+    // The free block the strategy picks for a block of `order`, as (order, start)
+    fn choose_block(&self, order: usize) -> Option<(usize, usize)> {
+        let mut candidates = self.free_lists
+            .iter()
+            .enumerate()
+            .skip(order)
+            .flat_map(|(order, starts)| starts.iter().map(move |&start| (order, start)));
+
+        match self.strategy {
+            PlacementStrategy::FirstFit => candidates.min_by_key(|&(_, start)| start),
+            PlacementStrategy::BestFit => candidates.next(),
+            PlacementStrategy::WorstFit => candidates.min_by_key(|&(order, start)| (std::cmp::Reverse(order), start)),
         }
-        Err("Insufficient memory".to_string())
     }
 
     pub fn deallocate(&mut self, start: usize, size: usize) -> Result<(), String> {
@@ -42,35 +223,163 @@ impl BuddyAllocator {
             return Err("Invalid size for deallocation".to_string());
         }
         let aligned_size = size.next_power_of_two();
-        let end = start + aligned_size;
-
-        if start >= end || end > 65536 {
+        let mut order = order_of(aligned_size);
+        if start.checked_add(aligned_size).is_none_or(|end| end > self.arena_size) || self.allocated.get(&start) != Some(&order) {
             return Err("Invalid deallocation range".to_string());
         }
+        self.allocated.remove(&start);
+        self.explain(|| format!("free 0x{:04X} - 0x{:04X}: order {} ({} bytes)", start, start + aligned_size, order, aligned_size));
 
-        self.free_blocks.push((start, end));
-        self.merge_free_blocks();
+        // Merge with the buddy for as long as the whole buddy is free
+        let mut start = start;
+        loop {
+            let buddy = start ^ (1 << order);
+            if order + 1 >= self.free_lists.len() || buddy + (1 << order) > self.arena_size {
+                self.explain(|| format!("  0x{:04X} of order {} has no buddy inside the arena: stop", start, order));
+                break;
+            }
+            if !self.free_lists[order].remove(&buddy) {
+                let reason = self.buddy_state(buddy, order);
+                self.explain(|| format!("  buddy 0x{:04X} of order {} is {}: stop", buddy, order, reason));
+                break;
+            }
+            let merged = start.min(buddy);
+            self.explain(|| format!("  buddy 0x{:04X} of order {} is free: merge into 0x{:04X} (order {})", buddy, order, merged, order + 1));
+            start = merged;
+            order += 1;
+        }
+
+        self.free_lists[order].insert(start);
+        self.explain(|| format!("  0x{:04X} of order {} is now free", start, order));
+        self.explain_tree();
         Ok(())
     }
 
-    fn merge_free_blocks(&mut self) {
-        self.free_blocks.sort_by_key(|&(start, _)| start);
-        let mut i = 0;
-        while i < self.free_blocks.len() - 1 {
-            let (start1, end1) = self.free_blocks[i];
-            let (start2, end2) = self.free_blocks[i + 1];
-            if end1 == start2 {
-                self.free_blocks[i] = (start1, end2);
-                self.free_blocks.remove(i + 1);
-            } else {
-                i += 1;
-            }
-        }
+    // Why a buddy that is not on the free list of its order cannot be merged. With eager
+    // merging it cannot be inside a larger free block, as that block would contain ours too.
+    fn buddy_state(&self, start: usize, order: usize) -> &'static str {
+        if self.allocated.get(&start) == Some(&order) { "allocated" } else { "split into smaller blocks" }
+    }
+
+    /// Free blocks as `(start, end)` pairs, in address order
+    pub fn free_blocks(&self) -> Vec<(usize, usize)> {
+        let mut blocks: Vec<(usize, usize)> = self.free_lists
+            .iter()
+            .enumerate()
+            .flat_map(|(order, starts)| starts.iter().map(move |&start| (start, start + (1 << order))))
+            .collect();
+        blocks.sort_unstable();
+        blocks
+    }
+
+    /// Allocated blocks as `(start, end)` pairs, in address order
+    pub fn allocated_blocks(&self) -> Vec<(usize, usize)> {
+        self.allocated.iter().map(|(&start, &order)| (start, start + (1 << order))).collect()
+    }
+
+    /// Start addresses of the free blocks of each order, indexed by order (a block of order
+    /// `k` is `1 << k` bytes)
+    pub fn free_lists(&self) -> &[BTreeSet<usize>] {
+        &self.free_lists
+    }
+
+    pub fn largest_free_block(&self) -> usize {
+        self.free_lists.iter().rposition(|starts| !starts.is_empty()).map(|order| 1 << order).unwrap_or(0)
     }
 
     pub fn dump_free_blocks(&self) {
-        for (start, end) in &self.free_blocks {
+        for (start, end) in self.free_blocks() {
             println!("Free Block: 0x{:04X} - 0x{:04X}", start, end);
         }
     }
-}
\ No newline at end of file
+
+    /// The buddy hierarchy as an indented tree: every block that has been split, down to
+    /// the free and allocated blocks
+    pub fn render_tree(&self) -> String {
+        let mut out = String::new();
+        for (start, end) in self.top_level_blocks() {
+            self.render_node(&mut out, start, order_of(end - start), "", "");
+        }
+        out
+    }
+
+    /// The blocks the arena is divided into before any split, as `(start, end)` pairs: one
+    /// for a power-of-two arena, largest first otherwise
+    pub fn top_level_blocks(&self) -> Vec<(usize, usize)> {
+        let mut blocks = Vec::new();
+        let mut start = 0;
+        for order in (0..self.free_lists.len()).rev() {
+            if self.arena_size - start >= 1 << order {
+                blocks.push((start, start + (1 << order)));
+                start += 1 << order;
+            }
+        }
+        blocks
+    }
+
+    // Whether a block of the buddy tree is free, allocated or split into two halves
+    fn state(&self, start: usize, order: usize) -> &'static str {
+        if self.free_lists[order].contains(&start) {
+            "free"
+        } else if self.allocated.get(&start) == Some(&order) {
+            "allocated"
+        } else {
+            "split"
+        }
+    }
+
+    fn render_node(&self, out: &mut String, start: usize, order: usize, first_prefix: &str, prefix: &str) {
+        let state = self.state(start, order);
+        out.push_str(&format!("{}0x{:04X} - 0x{:04X} order {} {}\n", first_prefix, start, start + (1 << order), order, state));
+        if state == "split" && order > 0 {
+            let upper = start + (1 << (order - 1));
+            self.render_node(out, start, order - 1, &format!("{}├── ", prefix), &format!("{}│   ", prefix));
+            self.render_node(out, upper, order - 1, &format!("{}└── ", prefix), &format!("{}    ", prefix));
+        }
+    }
+
+    /// The buddy tree as a Graphviz digraph: a node per block showing its address range, order
+    /// and state, with the two halves of every split block boxed together as buddies
+    pub fn to_dot(&self) -> String {
+        self.to_dot_labelled(|_| None)
+    }
+
+    /// Like `to_dot`, with an extra line on allocated blocks: `label` gets the block's start
+    pub fn to_dot_labelled(&self, label: impl Fn(usize) -> Option<String>) -> String {
+        let mut out = String::from("digraph buddy {\n    node [shape=box, style=filled, fontname=\"monospace\"];\n");
+        for (start, end) in self.top_level_blocks() {
+            self.dot_node(&mut out, start, order_of(end - start), &label);
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn dot_node(&self, out: &mut String, start: usize, order: usize, label: &impl Fn(usize) -> Option<String>) {
+        let state = self.state(start, order);
+        let mut text = format!("0x{:04X} - 0x{:04X}\\norder {}\\n{}", start, start + (1 << order), order, state);
+        if let Some(extra) = label(start).filter(|_| state == "allocated") {
+            text.push_str(&format!("\\n{}", extra.replace('"', "\\\"")));
+        }
+        let color = match state {
+            "free" => "palegreen",
+            "allocated" => "lightblue",
+            _ => "white",
+        };
+        out.push_str(&format!("    b{}_{} [label=\"{}\", fillcolor={}];\n", start, order, text, color));
+        if state == "split" && order > 0 {
+            let upper = start + (1 << (order - 1));
+            out.push_str(&format!(
+                "    subgraph cluster_{}_{} {{ label=\"buddies\"; style=dashed; b{}_{}; b{}_{}; }}\n",
+                start,
+                order - 1,
+                start,
+                order - 1,
+                upper,
+                order - 1
+            ));
+            out.push_str(&format!("    b{}_{} -> b{}_{};\n    b{}_{} -> b{}_{};\n", start, order, start, order - 1, start, order, upper, order - 1));
+            self.dot_node(out, start, order - 1, label);
+            self.dot_node(out, upper, order - 1, label);
+        }
+    }
+}
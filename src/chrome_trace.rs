@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::fs;
+use std::time::Instant;
+
+use crate::json::Json;
+use crate::memory_manager::MemoryBlock;
+
+const PID: usize = 1;
+
+// A block whose duration event is still open
+#[derive(Debug, Clone)]
+struct LiveBlock {
+    address: usize,
+    size: usize,
+    lane: usize,
+    since: f64, // Microseconds since the trace started
+}
+
+/// Records the lifetime of every block as a Chrome trace-event file, for chrome://tracing or
+/// Perfetto: a duration event per block from insert to delete, counter tracks for used bytes
+/// and the largest free block, and an instant event for every failed operation. Blocks that
+/// overlap in time go on separate lanes (threads, to the viewer).
+#[derive(Debug, Clone)]
+pub struct ChromeTrace {
+    started: Instant,
+    last: f64,
+    live: HashMap<usize, LiveBlock>, // Block ID -> open event
+    lanes: Vec<bool>,                // Whether each lane has a block on it
+    events: Vec<Json>,
+}
+
+impl Default for ChromeTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChromeTrace {
+    pub fn new() -> Self {
+        ChromeTrace { started: Instant::now(), last: 0.0, live: HashMap::new(), lanes: Vec::new(), events: Vec::new() }
+    }
+
+    /// Brings the trace up to date after one `MemoryManager` operation: closes the events of
+    /// blocks that were freed or moved, opens events for new ones and samples the counters
+    pub fn record(&mut self, operation: &str, blocks: &[MemoryBlock], largest_free: usize, error: Option<&str>) {
+        let now = self.started.elapsed().as_nanos() as f64 / 1000.0;
+        let current: HashMap<usize, (usize, usize)> = blocks
+            .iter()
+            .filter_map(|block| match block {
+                MemoryBlock::Allocated(allocated_block) => Some((allocated_block.id, (allocated_block.start, allocated_block.size()))),
+                MemoryBlock::Free(_) => None,
+            })
+            .collect();
+
+        let mut ended: Vec<usize> =
+            self.live.iter().filter(|(id, block)| current.get(id) != Some(&(block.address, block.size))).map(|(&id, _)| id).collect();
+        ended.sort_unstable();
+        for id in ended {
+            if let Some(block) = self.live.remove(&id) {
+                self.lanes[block.lane] = false;
+                self.events.push(block_event(id, &block, now));
+            }
+        }
+
+        let mut started: Vec<(usize, (usize, usize))> = current.into_iter().filter(|(id, _)| !self.live.contains_key(id)).collect();
+        started.sort_unstable();
+        for (id, (address, size)) in started {
+            let lane = match self.lanes.iter().position(|busy| !busy) {
+                Some(lane) => lane,
+                None => {
+                    self.lanes.push(false);
+                    self.lanes.len() - 1
+                }
+            };
+            self.lanes[lane] = true;
+            self.live.insert(id, LiveBlock { address, size, lane, since: now });
+        }
+
+        let used: usize = self.live.values().map(|block| block.size).sum();
+        self.events.push(counter("used bytes", now, used));
+        self.events.push(counter("largest free block", now, largest_free));
+        if let Some(error) = error {
+            self.events.push(Json::object([
+                ("name", format!("{} failed", operation).into()),
+                ("ph", "i".into()),
+                ("s", "p".into()),
+                ("ts", now.into()),
+                ("pid", PID.into()),
+                ("tid", 0usize.into()),
+                ("args", Json::object([("error", error.into())])),
+            ]));
+        }
+        self.last = now;
+    }
+
+    /// The trace-event JSON object. Blocks still allocated end at the last operation.
+    pub fn to_json(&self) -> Json {
+        let mut events = vec![metadata("process_name", 0, "memory_manager")];
+        events.extend((0..self.lanes.len()).map(|lane| metadata("thread_name", lane + 1, &format!("lane {}", lane + 1))));
+        events.extend(self.events.iter().cloned());
+        let mut live: Vec<(&usize, &LiveBlock)> = self.live.iter().collect();
+        live.sort_unstable_by_key(|(&id, _)| id);
+        events.extend(live.into_iter().map(|(&id, block)| block_event(id, block, self.last)));
+        Json::object([("traceEvents", Json::Array(events)), ("displayTimeUnit", "ms".into())])
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, format!("{}\n", self.to_json())).map_err(|e| format!("Failed to write Chrome trace {}: {}", path, e))
+    }
+}
+
+fn block_event(id: usize, block: &LiveBlock, end: f64) -> Json {
+    Json::object([
+        ("name", format!("block {}", id).into()),
+        ("cat", "block".into()),
+        ("ph", "X".into()),
+        ("ts", block.since.into()),
+        ("dur", (((end - block.since) * 1000.0).round() / 1000.0).into()),
+        ("pid", PID.into()),
+        ("tid", (block.lane + 1).into()),
+        (
+            "args",
+            Json::object([("id", id.into()), ("address", format!("0x{:04X}", block.address).into()), ("size", block.size.into())]),
+        ),
+    ])
+}
+
+fn counter(name: &str, ts: f64, value: usize) -> Json {
+    Json::object([("name", name.into()), ("ph", "C".into()), ("ts", ts.into()), ("pid", PID.into()), ("args", Json::object([(name, value.into())]))])
+}
+
+fn metadata(name: &str, tid: usize, value: &str) -> Json {
+    Json::object([("name", name.into()), ("ph", "M".into()), ("pid", PID.into()), ("tid", tid.into()), ("args", Json::object([("name", value.into())]))])
+}
@@ -0,0 +1,284 @@
+use crate::buddy_allocator::PlacementStrategy;
+use crate::memory_manager::ManagerConfig;
+use crate::script::Breakpoint;
+
+pub const USAGE: &str = "\
+Usage: memory_manager [options] run <file>...   Run command files in order ('-' reads stdin)
+       memory_manager [options] repl            Interactive prompt (the default without a file)
+       memory_manager [options] replay <trace>  Re-execute a recorded trace
+       memory_manager [options] import <trace>  Run an allocation trace from another allocator
+       memory_manager [options] inspect <image> Describe a saved memory image
+       memory_manager [options] test <dir>      Run every .cmmd file in a directory
+       memory_manager [options] report --html <out> <file>
+                                                Run a command file and write an HTML report of each step
+       memory_manager [options] bench [bench options]
+       memory_manager [options] <file>          Same as 'run <file>'
+
+Options:
+  --arena-size <bytes>     Arena size, a power of two; K and M suffixes allowed (default 64K)
+  --min-block <bytes>      Smallest block handed out, a power of two (default 1)
+  --strategy <name>        first-fit | best-fit | worst-fit
+  --format text|json       Report results as text or one JSON object per command
+  --block-headers          Keep a header at the start of every block in the arena
+  --explain                Narrate each buddy split and merge (run and repl)
+  -q, --quiet              Only print errors and command output
+  -v, --verbose            Also report blocks still allocated at exit
+  -h, --help               Show this help
+
+Run options:
+  --keep-going             Carry on after a failed command (also test and report)
+  --record <trace>         Record every operation to a trace
+  --chrome-trace <file>    Write block lifetimes and usage as a Chrome trace-event file
+  --journal <dir>          Journal every operation to <dir> and pick up the state saved there
+  -i, --interactive        Continue at the prompt after the files have run
+  --step                   Pause after each command and show how the blocks changed
+  --until [<file>:]<line>  Stop before that line and continue at the prompt
+  --snapshot-every <n>     Save a memory image and a text map after every n commands
+  --snapshot-dir <dir>     Where snapshots go (default snapshots)
+
+Exit status: 0 on success, 1 if a command, expectation or replay failed, 2 for usage or input errors.";
+
+/// How results are reported: progress lines on stderr, or one JSON object per command on stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+/// Options accepted before or after any subcommand. Unset sizes and strategy fall back to
+/// the manager's defaults (or, for replay, to the strategy the trace was recorded with).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobalOptions {
+    pub arena_size: Option<usize>,
+    pub min_block: Option<usize>,
+    pub strategy: Option<PlacementStrategy>,
+    pub verbosity: Verbosity,
+    pub format: Format,
+    pub explain: bool,
+    pub block_headers: bool,
+}
+
+impl Default for GlobalOptions {
+    fn default() -> Self {
+        GlobalOptions { arena_size: None, min_block: None, strategy: None, verbosity: Verbosity::Normal, format: Format::Text, explain: false, block_headers: false }
+    }
+}
+
+impl GlobalOptions {
+    /// The manager configuration these options describe, using `strategy` if none was given
+    pub fn config(&self, strategy: PlacementStrategy) -> Result<ManagerConfig, String> {
+        let defaults = ManagerConfig::default();
+        let config = ManagerConfig {
+            arena_size: self.arena_size.unwrap_or(defaults.arena_size),
+            min_block: self.min_block.unwrap_or(defaults.min_block),
+            strategy: self.strategy.unwrap_or(strategy),
+            block_headers: self.block_headers,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// What `run` does besides running its files in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunOptions {
+    pub files: Vec<String>, // "-" is stdin
+    pub keep_going: bool,
+    pub record: Option<String>,
+    pub chrome_trace: Option<String>,
+    pub journal: Option<String>,
+    pub interactive: bool,
+    pub step: bool,
+    pub until: Option<Breakpoint>,
+    pub snapshot_every: Option<usize>,
+    pub snapshot_dir: Option<String>,
+}
+
+impl RunOptions {
+    // Options that only make sense for `run`, by flag
+    fn run_only_flag(&self) -> Option<&'static str> {
+        [
+            (self.interactive, "-i"),
+            (self.chrome_trace.is_some(), "--chrome-trace"),
+            (self.journal.is_some(), "--journal"),
+            (self.step, "--step"),
+            (self.until.is_some(), "--until"),
+            (self.snapshot_every.is_some(), "--snapshot-every"),
+            (self.snapshot_dir.is_some(), "--snapshot-dir"),
+        ]
+        .into_iter()
+        .find_map(|(set, flag)| set.then_some(flag))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Subcommand {
+    Run(RunOptions),
+    Repl { record: Option<String> },
+    Replay { trace: String },
+    Import { trace: String },
+    Inspect { image: String },
+    Test { dir: String, keep_going: bool },
+    Report { file: String, html: String, keep_going: bool },
+    Bench { args: Vec<String> }, // Passed on to `BenchOptions::parse`
+    Help,
+}
+
+const SUBCOMMANDS: [&str; 9] = ["run", "repl", "replay", "import", "inspect", "test", "report", "bench", "help"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cli {
+    pub options: GlobalOptions,
+    pub subcommand: Subcommand,
+}
+
+/// Parses a byte count such as `4096`, `64K` or `1M`
+pub fn parse_size(text: &str) -> Result<usize, String> {
+    let (digits, multiplier) = match text.chars().last() {
+        Some('k' | 'K') => (&text[..text.len() - 1], 1 << 10),
+        Some('m' | 'M') => (&text[..text.len() - 1], 1 << 20),
+        _ => (text, 1),
+    };
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or(format!("Invalid size '{}'", text))
+}
+
+impl Cli {
+    /// Parses the arguments after the program name. A bare file is `run <file>`, no
+    /// arguments is `repl` and `--test <dir>` is `test <dir>`, as in earlier versions.
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = GlobalOptions::default();
+        let mut command: Option<&str> = None;
+        let mut positionals: Vec<String> = Vec::new();
+        let mut bench_args: Vec<String> = Vec::new();
+        let mut run = RunOptions::default();
+        let mut test_dir = None;
+        let mut html = None;
+        let mut help = false;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            // Everything after `bench` is the benchmark's own options
+            if command == Some("bench") {
+                bench_args.push(arg.clone());
+                continue;
+            }
+            let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+            match arg.as_str() {
+                "-h" | "--help" => help = true,
+                "-q" | "--quiet" => options.verbosity = Verbosity::Quiet,
+                "-v" | "--verbose" => options.verbosity = Verbosity::Verbose,
+                "--explain" => options.explain = true,
+                "--block-headers" => options.block_headers = true,
+                "--format" => {
+                    options.format = match value()?.as_str() {
+                        "text" => Format::Text,
+                        "json" => Format::Json,
+                        other => return Err(format!("Unknown format '{}' (expected text or json)", other)),
+                    }
+                }
+                "--arena-size" => options.arena_size = Some(parse_size(value()?).map_err(|e| format!("{} for --arena-size", e))?),
+                "--min-block" => options.min_block = Some(parse_size(value()?).map_err(|e| format!("{} for --min-block", e))?),
+                "--strategy" => options.strategy = Some(value()?.parse()?),
+                "--keep-going" => run.keep_going = true,
+                "-i" | "--interactive" => run.interactive = true,
+                "--record" => run.record = Some(value()?.clone()),
+                "--chrome-trace" => run.chrome_trace = Some(value()?.clone()),
+                "--journal" => run.journal = Some(value()?.clone()),
+                "--step" => run.step = true,
+                "--until" => run.until = Some(value()?.parse()?),
+                "--snapshot-every" => {
+                    let value = value()?;
+                    match value.parse::<usize>() {
+                        Ok(n) if n > 0 => run.snapshot_every = Some(n),
+                        _ => return Err(format!("Invalid count '{}' for --snapshot-every", value)),
+                    }
+                }
+                "--snapshot-dir" => run.snapshot_dir = Some(value()?.clone()),
+                "--test" => test_dir = Some(value()?.clone()),
+                "--html" => html = Some(value()?.clone()),
+                "-" => positionals.push(arg.clone()),
+                _ if arg.starts_with('-') => return Err(format!("Unknown option '{}'", arg)),
+                name if command.is_none() && positionals.is_empty() && SUBCOMMANDS.contains(&name) => command = Some(name),
+                _ => positionals.push(arg.clone()),
+            }
+        }
+
+        if help || command == Some("help") {
+            return Ok(Cli { options, subcommand: Subcommand::Help });
+        }
+        if let Some(dir) = test_dir {
+            if command.is_some() || !positionals.is_empty() {
+                return Err("--test cannot be combined with a subcommand or command file".to_string());
+            }
+            positionals.push(dir);
+            command = Some("test");
+        }
+
+        let is_run = matches!(command, None | Some("run"));
+        if let Some(flag) = run.run_only_flag().filter(|_| !is_run) {
+            return Err(format!("{} only applies to run", flag));
+        }
+        if !is_run && command != Some("repl") && run.record.is_some() {
+            return Err("--record only applies to run and repl".to_string());
+        }
+        if run.keep_going && !is_run && !matches!(command, Some("test" | "report")) {
+            return Err("--keep-going only applies to run, test and report".to_string());
+        }
+        if html.is_some() && command != Some("report") {
+            return Err("--html only applies to report".to_string());
+        }
+
+        let expect_one = |what: &str, positionals: &mut Vec<String>| match positionals.len() {
+            1 => Ok(positionals.remove(0)),
+            0 => Err(format!("Missing <{}> after {}", what, command.unwrap_or_default())),
+            _ => Err(format!("Unexpected argument '{}'", positionals[1])),
+        };
+        let subcommand = match command {
+            None if positionals.is_empty() && run.run_only_flag().is_none_or(|flag| flag == "-i") => Subcommand::Repl { record: run.record },
+            None | Some("run") => {
+                if positionals.is_empty() {
+                    return Err("Missing <file> to run".to_string());
+                }
+                Subcommand::Run(RunOptions { files: positionals, ..run })
+            }
+            Some("repl") => {
+                if let Some(extra) = positionals.first() {
+                    return Err(format!("Unexpected argument '{}'", extra));
+                }
+                Subcommand::Repl { record: run.record }
+            }
+            Some(name @ ("replay" | "import")) => {
+                // Earlier versions took the strategy as a second argument
+                if positionals.len() == 2 {
+                    if options.strategy.is_some() {
+                        return Err("Strategy given twice".to_string());
+                    }
+                    options.strategy = Some(positionals.pop().unwrap_or_default().parse()?);
+                }
+                let trace = expect_one("trace", &mut positionals)?;
+                if name == "replay" { Subcommand::Replay { trace } } else { Subcommand::Import { trace } }
+            }
+            Some("inspect") => Subcommand::Inspect { image: expect_one("image", &mut positionals)? },
+            Some("test") => Subcommand::Test { dir: expect_one("dir", &mut positionals)?, keep_going: run.keep_going },
+            Some("report") => {
+                let file = expect_one("file", &mut positionals)?;
+                let html = html.ok_or("Missing --html <out> for report")?;
+                Subcommand::Report { file, html, keep_going: run.keep_going }
+            }
+            Some("bench") => Subcommand::Bench { args: bench_args },
+            Some(other) => return Err(format!("Unknown subcommand '{}'", other)),
+        };
+        Ok(Cli { options, subcommand })
+    }
+}
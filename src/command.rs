@@ -0,0 +1,185 @@
+use crate::json::{self, Json};
+use crate::memory_manager::{MemoryBlock, MemoryManager};
+
+/// Cells in a MAP drawn without an explicit width
+pub const DEFAULT_MAP_WIDTH: usize = 64;
+
+/// The name of every command, as `Command::name` gives it
+pub const COMMAND_NAMES: [&str; 13] = ["INSERT", "DELETE", "READ", "UPDATE", "DUMP", "STATS", "MAP", "DOT", "HEXDUMP", "FIND", "WHICH", "SAVE", "LOAD"];
+
+/// A command from a command file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Insert { size: usize, data: Vec<u8> },
+    Delete { id: usize },
+    Read { id: usize },
+    Update { id: usize, data: Vec<u8> },
+    Dump,
+    Stats,
+    Map { width: usize },
+    Dot,
+    Hexdump { id: usize },
+    HexdumpRange { start: usize, end: usize },
+    Find { pattern: Vec<u8>, live_only: bool },
+    Which { address: usize },
+    Save { path: String },
+    Load { path: String },
+}
+
+/// What a successful command produced
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Outcome {
+    pub id: Option<usize>,           // The block the command acted on, or the one an UPDATE moved to
+    pub message: Option<String>,     // One-line status, e.g. "Allocated block with ID 0"
+    pub output: Option<String>,      // Multi-line report for stdout (DUMP, STATS, MAP, DOT, HEXDUMP, FIND)
+    pub details: Vec<(String, Json)>, // Structured fields for JSON output
+}
+
+impl Command {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Insert { .. } => "INSERT",
+            Command::Delete { .. } => "DELETE",
+            Command::Read { .. } => "READ",
+            Command::Update { .. } => "UPDATE",
+            Command::Dump => "DUMP",
+            Command::Stats => "STATS",
+            Command::Map { .. } => "MAP",
+            Command::Dot => "DOT",
+            Command::Hexdump { .. } | Command::HexdumpRange { .. } => "HEXDUMP",
+            Command::Find { .. } => "FIND",
+            Command::Which { .. } => "WHICH",
+            Command::Save { .. } => "SAVE",
+            Command::Load { .. } => "LOAD",
+        }
+    }
+
+    /// Runs the command, returning the text to show on success, if any
+    pub fn execute(&self, memory_manager: &mut MemoryManager) -> Result<Option<String>, String> {
+        let outcome = self.run(memory_manager)?;
+        Ok(match (outcome.message, outcome.output) {
+            (Some(message), Some(output)) => Some(format!("{}\n{}", message, output.trim_end())),
+            (message, output) => message.or(output.map(|output| output.trim_end().to_string())),
+        })
+    }
+
+    /// Runs the command without printing anything
+    pub fn run(&self, memory_manager: &mut MemoryManager) -> Result<Outcome, String> {
+        match self {
+            Command::Insert { size, data } => {
+                let id = memory_manager.insert(*size, data.clone())?;
+                Ok(Outcome { id: Some(id), message: Some(format!("Allocated block with ID {}", id)), ..Outcome::default() })
+            }
+            Command::Delete { id } => {
+                memory_manager.delete(*id)?;
+                Ok(Outcome { id: Some(*id), message: Some(format!("Deleted block with ID {}", id)), ..Outcome::default() })
+            }
+            Command::Read { id } => {
+                let block = memory_manager.find(*id)?;
+                let mut details = vec![("start".to_string(), block.start.into()), ("size".to_string(), block.size().into())];
+                details.extend(json::bytes_fields(memory_manager.get_data(block)).map(|(key, value)| (key.to_string(), value)));
+                Ok(Outcome { id: Some(*id), message: Some(format!("Block {} contains {:?}", id, block)), output: None, details })
+            }
+            Command::Update { id, data } => {
+                let new_id = memory_manager.update(*id, data.clone())?;
+                let message = if new_id == *id {
+                    format!("Updated block with ID {}", id)
+                } else {
+                    format!("Updated block with ID {} (moved to ID {})", id, new_id)
+                };
+                Ok(Outcome { id: Some(new_id), message: Some(message), ..Outcome::default() })
+            }
+            Command::Dump => Ok(Outcome {
+                output: Some(memory_manager.format_dump()),
+                details: vec![("blocks".to_string(), memory_manager.blocks_json())],
+                ..Outcome::default()
+            }),
+            Command::Stats => {
+                let stats = memory_manager.stats();
+                Ok(Outcome { output: Some(stats.to_string()), details: vec![("stats".to_string(), stats.to_json())], ..Outcome::default() })
+            }
+            Command::Map { width } => {
+                if *width == 0 {
+                    return Err("Map width must be at least 1".to_string());
+                }
+                let map = memory_manager.render_map(*width);
+                Ok(Outcome { details: vec![("map".to_string(), map.as_str().into())], output: Some(map), ..Outcome::default() })
+            }
+            Command::Dot => {
+                let dot = memory_manager.to_dot();
+                Ok(Outcome { details: vec![("dot".to_string(), dot.as_str().into())], output: Some(dot), ..Outcome::default() })
+            }
+            Command::Hexdump { id } => {
+                let block = memory_manager.find(*id)?;
+                let (start, end) = (block.start, block.end);
+                hexdump(memory_manager, Some(*id), start, end)
+            }
+            Command::HexdumpRange { start, end } => hexdump(memory_manager, None, *start, *end),
+            Command::Which { address } => {
+                let arena = memory_manager.get_buffer().len();
+                let (block, offset) = memory_manager
+                    .block_at(*address)
+                    .ok_or_else(|| format!("Address 0x{:04X} is outside the arena ({} bytes)", address, arena))?;
+                let (id, what) = match block {
+                    MemoryBlock::Allocated(allocated_block) => (Some(allocated_block.id), format!("block {}", allocated_block.id)),
+                    MemoryBlock::Free(_) => (None, "free space".to_string()),
+                };
+                let message = format!(
+                    "0x{:04X} is in {} at 0x{:04X} - 0x{:04X} ({} bytes), offset {}",
+                    address,
+                    what,
+                    block.start(),
+                    block.end(),
+                    block.size(),
+                    offset
+                );
+                let details = vec![("block".to_string(), memory_manager.block_json(block)), ("offset".to_string(), offset.into())];
+                Ok(Outcome { id, message: Some(message), output: None, details })
+            }
+            Command::Save { path } => {
+                memory_manager.save(path)?;
+                let message = format!("Saved {} blocks to {}", memory_manager.iter_allocated().count(), path);
+                Ok(Outcome { message: Some(message), details: vec![("path".to_string(), path.as_str().into())], ..Outcome::default() })
+            }
+            Command::Load { path } => {
+                memory_manager.load_from(path)?;
+                let message = format!("Loaded {} blocks from {}", memory_manager.iter_allocated().count(), path);
+                Ok(Outcome { message: Some(message), details: vec![("path".to_string(), path.as_str().into())], ..Outcome::default() })
+            }
+            Command::Find { pattern, live_only } => {
+                if pattern.is_empty() {
+                    return Err("Pattern is empty".to_string());
+                }
+                let matches = memory_manager.search(pattern, *live_only);
+                let place = if *live_only { "live blocks" } else { "the arena" };
+                let message = match matches.len() {
+                    0 => format!("No matches for \"{}\" in {}", pattern.escape_ascii(), place),
+                    1 => format!("Found 1 match for \"{}\" in {}", pattern.escape_ascii(), place),
+                    n => format!("Found {} matches for \"{}\" in {}", n, pattern.escape_ascii(), place),
+                };
+                let output: String = matches.iter().map(|found| format!("  {}\n", found)).collect();
+                let json = matches
+                    .iter()
+                    .map(|found| {
+                        let id = found.id.map_or(Json::Null, Json::from);
+                        Json::object([("address", found.address.into()), ("id", id), ("offset", found.offset.into())])
+                    })
+                    .collect();
+                Ok(Outcome {
+                    message: Some(message),
+                    output: (!output.is_empty()).then_some(output),
+                    details: vec![("matches".to_string(), Json::Array(json))],
+                    ..Outcome::default()
+                })
+            }
+        }
+    }
+}
+
+// HEXDUMP of a block or a range, with the bytes in the JSON output as well
+fn hexdump(memory_manager: &MemoryManager, id: Option<usize>, start: usize, end: usize) -> Result<Outcome, String> {
+    let output = memory_manager.hexdump(start, end)?;
+    let mut details = vec![("start".to_string(), start.into()), ("end".to_string(), end.into())];
+    details.extend(json::bytes_fields(&memory_manager.get_buffer()[start..end]).map(|(key, value)| (key.to_string(), value)));
+    Ok(Outcome { id, output: Some(output), details, ..Outcome::default() })
+}
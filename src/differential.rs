@@ -0,0 +1,348 @@
+use std::fmt::Write;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::memory_manager::{MemoryBlock, MemoryManager};
+use crate::model::ReferenceModel;
+use crate::workload::Rng;
+
+/// An operation the differential driver runs against both the model and the real manager
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffOp {
+    Insert { size: usize, data: Vec<u8> },
+    Delete { id: usize },
+    Update { id: usize, data: Vec<u8> },
+}
+
+impl std::fmt::Display for DiffOp {
+    /// Formats the operation as a command-file line
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DiffOp::Insert { size, data } => write!(f, "INSERT {} {}", size, data_literal(data)),
+            DiffOp::Delete { id } => write!(f, "DELETE {}", id),
+            DiffOp::Update { id, data } => write!(f, "UPDATE {} {}", id, data_literal(data)),
+        }
+    }
+}
+
+// Data as a hex literal, which reads back as exactly these bytes whatever they are
+fn data_literal(data: &[u8]) -> String {
+    if data.is_empty() {
+        return "\"\"".to_string();
+    }
+    let hex: String = data.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("0x{}", hex)
+}
+
+/// A sequence on which the manager and the model disagree; the last operation is the one that failed
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub ops: Vec<DiffOp>,
+    pub message: String,
+}
+
+impl Failure {
+    /// The failing sequence as a command file that can be run with the CLI
+    pub fn to_command_file(&self) -> String {
+        let mut out = String::new();
+        for op in &self.ops {
+            let _ = writeln!(out, "{}", op);
+        }
+        out.push_str("DUMP\n");
+        out
+    }
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "After {} operation(s): {}", self.ops.len(), self.message)?;
+        write!(f, "{}", self.to_command_file())
+    }
+}
+
+// Runs one operation on the manager, turning a panic into an error message
+fn guarded<T>(f: impl FnOnce() -> Result<T, String>) -> Result<Result<T, String>, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        let message = payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_default();
+        format!("manager panicked: {}", message)
+    })
+}
+
+fn block_range(memory_manager: &MemoryManager, id: usize) -> Result<(usize, usize), String> {
+    memory_manager.find(id)
+        .map(|block| (block.start, block.end))
+        .map_err(|e| format!("block {} was reported but cannot be found: {}", id, e))
+}
+
+/// Applies one operation to both sides and checks the real result is one the model allows
+pub fn step(memory_manager: &mut MemoryManager, model: &mut ReferenceModel, op: &DiffOp) -> Result<(), String> {
+    match op {
+        DiffOp::Insert { size, data } => {
+            let rounded = ReferenceModel::rounded_size(*size);
+            match guarded(|| memory_manager.insert(*size, data.clone()))? {
+                Ok(id) => {
+                    if data.len() > rounded {
+                        return Err(format!("insert of {} bytes of data into a {}-byte block succeeded", data.len(), rounded));
+                    }
+                    if id != model.next_id() {
+                        return Err(format!("insert returned ID {}, expected {}", id, model.next_id()));
+                    }
+                    let (start, end) = block_range(memory_manager, id)?;
+                    if end - start != rounded {
+                        return Err(format!("block {} is {} bytes, expected {}", id, end - start, rounded));
+                    }
+                    if !model.is_free(start, end) {
+                        return Err(format!("block {} was placed at 0x{:04X}-0x{:04X}, which is not free", id, start, end));
+                    }
+                    model.insert(start, *size, data.clone());
+                }
+                Err(e) => {
+                    if data.len() <= rounded && model.has_aligned_slot(rounded) {
+                        return Err(format!("insert of {} bytes failed ({}) although a free {}-byte range exists", size, e, rounded));
+                    }
+                }
+            }
+        }
+        DiffOp::Delete { id } => {
+            let result = guarded(|| memory_manager.delete(*id))?;
+            match (result, model.remove(*id)) {
+                (Ok(()), None) => return Err(format!("delete of unknown block {} succeeded", id)),
+                (Err(e), Some(_)) => return Err(format!("delete of block {} failed: {}", id, e)),
+                _ => {}
+            }
+        }
+        DiffOp::Update { id, data } => {
+            let existing = model.get(*id).cloned();
+            let result = guarded(|| memory_manager.update(*id, data.clone()))?;
+            match (result, existing) {
+                (Ok(_), None) => return Err(format!("update of unknown block {} succeeded", id)),
+                (Err(_), None) => {}
+                (result, Some(block)) if data.len() <= block.end - block.start => {
+                    // Fits: updated in place under the same ID
+                    match result {
+                        Ok(new_id) if new_id == *id => model.write(*id, data.clone()),
+                        Ok(new_id) => return Err(format!("in-place update of block {} returned ID {}", id, new_id)),
+                        Err(e) => return Err(format!("in-place update of block {} failed: {}", id, e)),
+                    }
+                }
+                (result, Some(_)) => {
+                    // Does not fit: moved to a new range under a new ID, reserved before the old one is freed
+                    let rounded = ReferenceModel::rounded_size(data.len());
+                    match result {
+                        Ok(new_id) => {
+                            if new_id != model.next_id() {
+                                return Err(format!("update of block {} returned ID {}, expected {}", id, new_id, model.next_id()));
+                            }
+                            let (start, end) = block_range(memory_manager, new_id)?;
+                            if end - start != rounded || !model.is_free(start, end) {
+                                return Err(format!("update of block {} moved it to 0x{:04X}-0x{:04X}, which is not a free {}-byte range", id, start, end, rounded));
+                            }
+                            model.remove(*id);
+                            model.insert(start, data.len(), data.clone());
+                        }
+                        Err(e) => {
+                            if model.has_aligned_slot(rounded) {
+                                return Err(format!("update of block {} failed ({}) although a free {}-byte range exists", id, e, rounded));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    check(memory_manager, model)
+}
+
+/// Compares every piece of the manager's state with the model
+pub fn check(memory_manager: &MemoryManager, model: &ReferenceModel) -> Result<(), String> {
+    let arena_size = memory_manager.get_buffer().len();
+
+    // The block list must tile the arena in address order, with free neighbours merged
+    let mut cursor = 0;
+    let mut previous_free = false;
+    let mut allocated = Vec::new();
+    let mut free = Vec::new();
+    for block in memory_manager.blocks() {
+        if block.start() != cursor {
+            return Err(format!("block list has a gap or overlap at 0x{:04X} (next block starts at 0x{:04X})", cursor, block.start()));
+        }
+        match block {
+            MemoryBlock::Free(free_block) => {
+                if previous_free {
+                    return Err(format!("adjacent free blocks were not merged at 0x{:04X}", free_block.start));
+                }
+                free.push((free_block.start, free_block.end));
+                previous_free = true;
+            }
+            MemoryBlock::Allocated(allocated_block) => {
+                allocated.push((allocated_block.id, allocated_block.start, allocated_block.end));
+                previous_free = false;
+            }
+        }
+        cursor = block.end();
+    }
+    if cursor != arena_size {
+        return Err(format!("block list ends at 0x{:04X}, arena is {} bytes", cursor, arena_size));
+    }
+
+    let expected: Vec<(usize, usize, usize)> = model.blocks().map(|block| (block.id, block.start, block.end)).collect();
+    if allocated != expected {
+        return Err(format!("allocated blocks {:?}, model has {:?}", allocated, expected));
+    }
+
+    let model_free = model.free_ranges();
+    if free != model_free {
+        return Err(format!("free blocks {:?}, model has {:?}", free, model_free));
+    }
+
+    // The allocator's free list may split a free range into pieces, but must cover exactly the same bytes
+    let mut allocator_free: Vec<(usize, usize)> = memory_manager.allocator().free_blocks().to_vec();
+    allocator_free.sort();
+    let mut coalesced: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in allocator_free {
+        match coalesced.last_mut() {
+            Some(last) if start < last.1 => {
+                return Err(format!("allocator free blocks overlap at 0x{:04X}", start));
+            }
+            Some(last) if start == last.1 => last.1 = end,
+            _ => coalesced.push((start, end)),
+        }
+    }
+    if coalesced != model_free {
+        return Err(format!("allocator free list covers {:?}, model has {:?}", coalesced, model_free));
+    }
+
+    for block in model.blocks() {
+        if memory_manager.get_buffer_slice(block.start, block.end) != block.contents.as_slice() {
+            return Err(format!("arena contents of block {} do not match", block.id));
+        }
+        let stored = memory_manager.find(block.id).map(|found| memory_manager.get_data(found)).unwrap_or(&[]);
+        if !stored.starts_with(&block.data) {
+            return Err(format!("stored data of block {} does not match", block.id));
+        }
+    }
+
+    if memory_manager.stats().used_bytes != model.used_bytes() {
+        return Err(format!("stats report {} used bytes, model has {}", memory_manager.stats().used_bytes, model.used_bytes()));
+    }
+    Ok(())
+}
+
+fn quiet_manager() -> MemoryManager {
+    let mut memory_manager = MemoryManager::with_strategy(Default::default());
+    memory_manager.set_verbose(false);
+    memory_manager
+}
+
+/// Runs a fixed sequence from a fresh manager; on failure returns the index of the failing operation
+pub fn run_ops(ops: &[DiffOp]) -> Result<(), (usize, String)> {
+    let mut memory_manager = quiet_manager();
+    let mut model = ReferenceModel::new(memory_manager.get_buffer().len());
+    for (i, op) in ops.iter().enumerate() {
+        step(&mut memory_manager, &mut model, op).map_err(|e| (i, e))?;
+    }
+    Ok(())
+}
+
+fn random_data(rng: &mut Rng, len: usize) -> Vec<u8> {
+    const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    (0..len.max(1)).map(|_| ALPHABET[rng.range(0, ALPHABET.len() - 1)]).collect()
+}
+
+fn random_op(rng: &mut Rng, model: &ReferenceModel) -> DiffOp {
+    let live: Vec<usize> = model.blocks().map(|block| block.id).collect();
+    let pick_id = |rng: &mut Rng| {
+        if live.is_empty() || rng.chance(0.1) {
+            rng.range(0, model.next_id() + 1) // Usually stale or unknown
+        } else {
+            live[rng.range(0, live.len() - 1)]
+        }
+    };
+
+    let roll = rng.next_f64();
+    if roll < 0.5 || live.is_empty() {
+        let size = match rng.next_f64() {
+            r if r < 0.70 => rng.range(1, 256),
+            r if r < 0.95 => rng.range(257, 8192),
+            _ => rng.range(8193, 70000),
+        };
+        let rounded = ReferenceModel::rounded_size(size);
+        let len = if rng.chance(0.05) { rounded + rng.range(1, 8) } else { rng.range(1, rounded.min(32)) };
+        DiffOp::Insert { size, data: random_data(rng, len) }
+    } else if roll < 0.75 {
+        DiffOp::Delete { id: pick_id(rng) }
+    } else {
+        let id = pick_id(rng);
+        let capacity = model.get(id).map(|block| block.end - block.start).unwrap_or(16);
+        let len = if rng.chance(0.5) { rng.range(1, capacity) } else { rng.range(capacity + 1, capacity * 2 + 64) };
+        DiffOp::Update { id, data: random_data(rng, len.min(1024)) }
+    }
+}
+
+/// Runs `steps` random operations chosen from `seed`, checking after each one. A failure is
+/// shrunk to a minimal sequence before it is returned.
+pub fn run_random(seed: u64, steps: usize) -> Result<(), Failure> {
+    let mut rng = Rng::new(seed);
+    let mut memory_manager = quiet_manager();
+    let mut model = ReferenceModel::new(memory_manager.get_buffer().len());
+    let mut ops = Vec::new();
+
+    for _ in 0..steps {
+        let op = random_op(&mut rng, &model);
+        ops.push(op.clone());
+        if let Err(message) = step(&mut memory_manager, &mut model, &op) {
+            let ops = shrink(ops);
+            let message = run_ops(&ops).err().map(|(_, e)| e).unwrap_or(message);
+            return Err(Failure { ops, message });
+        }
+    }
+    Ok(())
+}
+
+/// Delta-debugging: drops chunks of operations, then shortens data, as long as the sequence still fails
+pub fn shrink(ops: Vec<DiffOp>) -> Vec<DiffOp> {
+    shrink_by(ops, |ops| run_ops(ops).err().map(|(i, _)| i))
+}
+
+/// Like `shrink`, with `fails_at` giving the index of the operation a sequence fails at, if any
+pub fn shrink_by(ops: Vec<DiffOp>, fails_at: impl Fn(&[DiffOp]) -> Option<usize>) -> Vec<DiffOp> {
+    let Some(i) = fails_at(&ops) else {
+        return ops;
+    };
+    let mut current = ops[..=i].to_vec();
+
+    let mut chunk = current.len().div_ceil(2);
+    while chunk > 0 {
+        let mut start = 0;
+        while start < current.len() {
+            let end = (start + chunk).min(current.len());
+            let candidate = [&current[..start], &current[end..]].concat();
+            match fails_at(&candidate) {
+                Some(i) => current = candidate[..=i].to_vec(),
+                None => start += chunk,
+            }
+        }
+        chunk /= 2;
+    }
+
+    // Halve each operation's data for as long as the sequence keeps failing
+    for i in 0..current.len() {
+        loop {
+            let shorter = match &current[i] {
+                DiffOp::Insert { size, data } if data.len() > 1 => DiffOp::Insert { size: *size, data: data[..data.len() / 2].to_vec() },
+                DiffOp::Update { id, data } if data.len() > 1 => DiffOp::Update { id: *id, data: data[..data.len() / 2].to_vec() },
+                _ => break,
+            };
+            let mut candidate = current.clone();
+            candidate[i] = shorter;
+            if fails_at(&candidate).is_none() {
+                break;
+            }
+            current = candidate;
+        }
+    }
+    current
+}
@@ -0,0 +1,160 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// Allocation counters for one call site or one size class
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AllocationStats {
+    pub allocations: usize,
+    pub frees: usize,
+    pub allocated_bytes: usize, // Total over the whole run, including freed blocks
+    pub live_blocks: usize,
+    pub live_bytes: usize,
+    pub peak_bytes: usize,
+}
+
+impl AllocationStats {
+    fn allocate(&mut self, size: usize) {
+        self.allocations += 1;
+        self.allocated_bytes += size;
+        self.live_blocks += 1;
+        self.live_bytes += size;
+        self.peak_bytes = self.peak_bytes.max(self.live_bytes);
+    }
+
+    fn free(&mut self, size: usize) {
+        self.frees += 1;
+        self.live_blocks -= 1;
+        self.live_bytes -= size;
+    }
+}
+
+/// Total live bytes after one `MemoryManager` operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimelineSample {
+    pub operation: &'static str,
+    pub live_bytes: usize,
+}
+
+/// Records what is consuming the arena, by call site and by size class, over time
+#[derive(Debug, Clone, Default)]
+pub struct HeapProfiler {
+    sites: BTreeMap<String, AllocationStats>,
+    size_classes: BTreeMap<usize, AllocationStats>,
+    stacks: BTreeMap<(String, usize), usize>, // (call site, size class) -> bytes allocated
+    live: HashMap<usize, (String, usize)>,    // block ID -> (call site, size)
+    live_bytes: usize,
+    peak_bytes: usize,
+    timeline: Vec<TimelineSample>,
+}
+
+impl HeapProfiler {
+    pub fn new() -> Self {
+        HeapProfiler::default()
+    }
+
+    /// Counts a block of `size` bytes (already rounded to its size class) allocated from `site`
+    pub fn record_allocation(&mut self, id: usize, site: &str, size: usize) {
+        self.sites.entry(site.to_string()).or_default().allocate(size);
+        self.size_classes.entry(size).or_default().allocate(size);
+        *self.stacks.entry((site.to_string(), size)).or_insert(0) += size;
+        self.live.insert(id, (site.to_string(), size));
+        self.live_bytes += size;
+        self.peak_bytes = self.peak_bytes.max(self.live_bytes);
+    }
+
+    pub fn record_free(&mut self, id: usize) {
+        if let Some((site, size)) = self.live.remove(&id) {
+            if let Some(stats) = self.sites.get_mut(&site) {
+                stats.free(size);
+            }
+            if let Some(stats) = self.size_classes.get_mut(&size) {
+                stats.free(size);
+            }
+            self.live_bytes -= size;
+        }
+    }
+
+    /// Appends a timeline sample; called once per public `MemoryManager` operation
+    pub fn record_operation(&mut self, operation: &'static str) {
+        self.timeline.push(TimelineSample { operation, live_bytes: self.live_bytes });
+    }
+
+    pub fn live_bytes(&self) -> usize {
+        self.live_bytes
+    }
+
+    pub fn peak_bytes(&self) -> usize {
+        self.peak_bytes
+    }
+
+    pub fn sites(&self) -> &BTreeMap<String, AllocationStats> {
+        &self.sites
+    }
+
+    pub fn size_classes(&self) -> &BTreeMap<usize, AllocationStats> {
+        &self.size_classes
+    }
+
+    pub fn timeline(&self) -> &[TimelineSample] {
+        &self.timeline
+    }
+
+    /// Human-readable summary: per-site and per-size-class tables followed by the usage timeline
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        let live_blocks: usize = self.sites.values().map(|stats| stats.live_blocks).sum();
+        let _ = writeln!(
+            out,
+            "Heap profile: {} operations, {} live blocks, {} live bytes, peak {} bytes",
+            self.timeline.len(),
+            live_blocks,
+            self.live_bytes,
+            self.peak_bytes
+        );
+
+        let _ = writeln!(out, "\nBy call site:");
+        Self::write_table(&mut out, "site", self.sites.iter().map(|(site, stats)| (site.clone(), stats)));
+
+        let _ = writeln!(out, "\nBy size class:");
+        Self::write_table(&mut out, "size", self.size_classes.iter().map(|(size, stats)| (size.to_string(), stats)));
+
+        let _ = writeln!(out, "\nTimeline (live bytes after each operation):");
+        for (i, sample) in self.timeline.iter().enumerate() {
+            let _ = writeln!(out, "{:>6}  {:<8} {}", i, sample.operation, sample.live_bytes);
+        }
+        out
+    }
+
+    fn write_table<'a, I>(out: &mut String, key: &str, rows: I)
+    where
+        I: Iterator<Item = (String, &'a AllocationStats)>,
+    {
+        let rows: Vec<(String, &AllocationStats)> = rows.collect();
+        let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max(key.len());
+        let _ = writeln!(
+            out,
+            "  {:<width$}  {:>7} {:>7} {:>7} {:>11} {:>11} {:>11}",
+            key, "allocs", "frees", "live", "live bytes", "peak bytes", "total bytes",
+            width = width
+        );
+        for (name, stats) in rows {
+            let _ = writeln!(
+                out,
+                "  {:<width$}  {:>7} {:>7} {:>7} {:>11} {:>11} {:>11}",
+                name, stats.allocations, stats.frees, stats.live_blocks, stats.live_bytes, stats.peak_bytes, stats.allocated_bytes,
+                width = width
+            );
+        }
+    }
+
+    /// Allocated bytes as `site;size-class weight` lines, the collapsed-stack input of flamegraph tools
+    pub fn collapsed_stacks(&self) -> String {
+        let mut out = String::new();
+        for ((site, size), bytes) in &self.stacks {
+            // ';' separates frames and ' ' separates the weight, so neither may appear in a frame
+            let frame = site.replace([';', ' '], "_");
+            let _ = writeln!(out, "{};{}B {}", frame, size, bytes);
+        }
+        out
+    }
+}
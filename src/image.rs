@@ -0,0 +1,190 @@
+use crate::buddy_allocator::PlacementStrategy;
+use crate::memory_manager::ManagerConfig;
+
+const MAGIC: &[u8; 8] = b"MMIMAGE\0";
+pub const IMAGE_VERSION: u32 = 2; // Version 1 had no flags byte
+
+/// One block of a memory image: allocated blocks have an ID, the size they were asked
+/// for and their data, free ones only a range
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageBlock {
+    pub id: Option<usize>,
+    pub start: usize,
+    pub end: usize,
+    pub requested: usize,
+    pub data: Vec<u8>,
+}
+
+/// Everything needed to bring a `MemoryManager` back: its configuration, the allocator's free
+/// and allocated blocks, the block list with IDs and data, the next ID and the arena bytes.
+///
+/// Stored little-endian as the magic `MMIMAGE\0`, a `u32` version and the fields in that order,
+/// with counts and lengths as `u64` and the header mode as a flags byte after the strategy,
+/// followed by an FNV-1a checksum of everything before it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryImage {
+    pub config: ManagerConfig,
+    pub next_id: usize,
+    pub free: Vec<(usize, usize)>,      // The allocator's free blocks as (start, end)
+    pub allocated: Vec<(usize, usize)>, // The allocator's allocated blocks as (start, end)
+    pub blocks: Vec<ImageBlock>,
+    pub arena: Vec<u8>,
+}
+
+impl MemoryImage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Writer(MAGIC.to_vec());
+        out.u32(IMAGE_VERSION);
+        out.u64(self.config.arena_size);
+        out.u64(self.config.min_block);
+        out.u8(PlacementStrategy::ALL.iter().position(|&strategy| strategy == self.config.strategy).unwrap_or(0) as u8);
+        out.u8(self.config.block_headers as u8);
+        out.u64(self.next_id);
+        for ranges in [&self.free, &self.allocated] {
+            out.u64(ranges.len());
+            for &(start, end) in ranges {
+                out.u64(start);
+                out.u64(end);
+            }
+        }
+        out.u64(self.blocks.len());
+        for block in &self.blocks {
+            out.u8(block.id.is_some() as u8);
+            out.u64(block.start);
+            out.u64(block.end);
+            if let Some(id) = block.id {
+                out.u64(id);
+                out.u64(block.requested);
+                out.bytes(&block.data);
+            }
+        }
+        out.bytes(&self.arena);
+
+        let checksum = checksum(&out.0);
+        out.0.extend_from_slice(&checksum.to_le_bytes());
+        out.0
+    }
+
+    /// Reads an image, checking its magic, version and checksum. Whether the blocks make
+    /// sense together is left to `MemoryManager::from_image`.
+    pub fn decode(bytes: &[u8]) -> Result<MemoryImage, String> {
+        if !bytes.starts_with(MAGIC) {
+            return Err("not a memory image".to_string());
+        }
+        if bytes.len() < MAGIC.len() + 4 + 8 {
+            return Err("image is truncated".to_string());
+        }
+        let (body, stored) = bytes.split_at(bytes.len() - 8);
+        let mut input = Reader { bytes: body, pos: MAGIC.len() };
+        let version = input.u32()?;
+        if !(1..=IMAGE_VERSION).contains(&version) {
+            return Err(format!("unsupported image version {} (expected at most {})", version, IMAGE_VERSION));
+        }
+        if u64::from_le_bytes(stored.try_into().expect("8 bytes")) != checksum(body) {
+            return Err("checksum mismatch: the image is corrupt or truncated".to_string());
+        }
+
+        let arena_size = input.u64()?;
+        let min_block = input.u64()?;
+        let strategy = *PlacementStrategy::ALL.get(input.u8()? as usize).ok_or("unknown placement strategy")?;
+        let flags = if version >= 2 { input.u8()? } else { 0 };
+        let next_id = input.u64()?;
+        let mut ranges = [Vec::new(), Vec::new()];
+        for list in &mut ranges {
+            for _ in 0..input.count(16)? {
+                list.push((input.u64()?, input.u64()?));
+            }
+        }
+        let [free, allocated] = ranges;
+        let mut blocks = Vec::new();
+        for _ in 0..input.count(17)? {
+            let is_allocated = match input.u8()? {
+                0 => false,
+                1 => true,
+                tag => return Err(format!("invalid block tag {}", tag)),
+            };
+            let (start, end) = (input.u64()?, input.u64()?);
+            let block = if is_allocated {
+                ImageBlock { id: Some(input.u64()?), start, end, requested: input.u64()?, data: input.bytes()? }
+            } else {
+                ImageBlock { id: None, start, end, requested: 0, data: Vec::new() }
+            };
+            blocks.push(block);
+        }
+        let arena = input.bytes()?;
+        if input.pos != body.len() {
+            return Err(format!("{} unexpected bytes after the arena", body.len() - input.pos));
+        }
+        Ok(MemoryImage { config: ManagerConfig { arena_size, min_block, strategy, block_headers: flags & 1 != 0 }, next_id, free, allocated, blocks, arena })
+    }
+}
+
+/// 64-bit FNV-1a, see http://www.isthe.com/chongo/tech/comp/fnv/
+pub fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+// Little-endian encoding of the image fields
+pub(crate) struct Writer(pub Vec<u8>);
+
+impl Writer {
+    pub fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: usize) {
+        self.0.extend_from_slice(&(value as u64).to_le_bytes());
+    }
+
+    // A length-prefixed byte string
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.u64(bytes.len());
+        self.0.extend_from_slice(bytes);
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    pub bytes: &'a [u8],
+    pub pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], String> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.bytes.len()).ok_or_else(|| format!("image ends early at byte {}", self.bytes.len()))?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().expect("4 bytes")))
+    }
+
+    pub fn u64(&mut self) -> Result<usize, String> {
+        let value = u64::from_le_bytes(self.take(8)?.try_into().expect("8 bytes"));
+        usize::try_from(value).map_err(|_| format!("value {} is too large", value))
+    }
+
+    // A count of records of at least `record_size` bytes each, checked against what is left
+    // so a corrupt count cannot make us allocate huge vectors
+    pub fn count(&mut self, record_size: usize) -> Result<usize, String> {
+        let count = self.u64()?;
+        if count.saturating_mul(record_size) > self.bytes.len() - self.pos {
+            return Err(format!("count {} at byte {} runs past the end of the image", count, self.pos - 8));
+        }
+        Ok(count)
+    }
+
+    pub fn bytes(&mut self) -> Result<Vec<u8>, String> {
+        let len = self.count(1)?;
+        Ok(self.take(len)?.to_vec())
+    }
+}
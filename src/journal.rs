@@ -0,0 +1,220 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::image::{checksum, MemoryImage, Reader, Writer};
+
+const JOURNAL_MAGIC: &[u8; 8] = b"MMWAL\0\0\0";
+const JOURNAL_VERSION: u32 = 1;
+const HEADER_LEN: usize = 8 + 4 + 8; // Magic, version and the checksum of the checkpoint
+const CHECKPOINT_FILE: &str = "checkpoint.img";
+const JOURNAL_FILE: &str = "journal.wal";
+
+/// When journal writes are flushed to disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncPolicy {
+    #[default]
+    Always, // fsync after every record, so an operation that returned survives a crash
+    Never,  // Leave it to the OS; a crash can lose the last operations, but never tears the state
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JournalOptions {
+    pub sync: SyncPolicy,
+    pub checkpoint_every: usize, // Records between checkpoints; 0 only checkpoints when asked
+}
+
+impl Default for JournalOptions {
+    fn default() -> Self {
+        JournalOptions { sync: SyncPolicy::default(), checkpoint_every: 1000 }
+    }
+}
+
+/// A mutating `MemoryManager` operation, as written to the journal before it is applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JournalRecord {
+    Insert { size: usize, data: Vec<u8> },
+    Delete { id: usize },
+    Update { id: usize, data: Vec<u8> },
+    Resize { id: usize, new_size: usize },
+}
+
+impl JournalRecord {
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Writer(Vec::new());
+        match self {
+            JournalRecord::Insert { size, data } => {
+                out.u8(b'i');
+                out.u64(*size);
+                out.bytes(data);
+            }
+            JournalRecord::Delete { id } => {
+                out.u8(b'd');
+                out.u64(*id);
+            }
+            JournalRecord::Update { id, data } => {
+                out.u8(b'u');
+                out.u64(*id);
+                out.bytes(data);
+            }
+            JournalRecord::Resize { id, new_size } => {
+                out.u8(b'r');
+                out.u64(*id);
+                out.u64(*new_size);
+            }
+        }
+
+        // Framed as length, payload and checksum, so a torn write at the end is recognized
+        let mut record = (out.0.len() as u32).to_le_bytes().to_vec();
+        record.extend_from_slice(&out.0);
+        record.extend_from_slice(&checksum(&out.0).to_le_bytes());
+        record
+    }
+
+    // The record at the start of `bytes` and its length, or `None` if it is cut off or corrupt
+    fn decode(bytes: &[u8]) -> Option<(JournalRecord, usize)> {
+        let len = u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?) as usize;
+        let payload = bytes.get(4..4 + len)?;
+        let stored = u64::from_le_bytes(bytes.get(4 + len..4 + len + 8)?.try_into().ok()?);
+        if stored != checksum(payload) {
+            return None;
+        }
+        let mut input = Reader { bytes: payload, pos: 1 };
+        let record = match payload.first()? {
+            b'i' => JournalRecord::Insert { size: input.u64().ok()?, data: input.bytes().ok()? },
+            b'd' => JournalRecord::Delete { id: input.u64().ok()? },
+            b'u' => JournalRecord::Update { id: input.u64().ok()?, data: input.bytes().ok()? },
+            b'r' => JournalRecord::Resize { id: input.u64().ok()?, new_size: input.u64().ok()? },
+            _ => return None,
+        };
+        (input.pos == payload.len()).then_some((record, 4 + len + 8))
+    }
+}
+
+/// A write-ahead journal in a directory: a checkpoint image of the whole manager and a log of
+/// the operations since. The log's header holds the checksum of the checkpoint it follows, and
+/// a checkpoint replaces both files by renaming, image first. A crash between the two renames
+/// leaves a log that names the old image, and its operations are already in the new one.
+pub struct Journal {
+    dir: PathBuf,
+    file: File,
+    options: JournalOptions,
+    records: usize,         // Since the last checkpoint
+    broken: Option<String>, // Why the log may end in a torn record, until the next checkpoint
+}
+
+impl Journal {
+    /// Whether `dir` holds a journal to open
+    pub fn exists(dir: &Path) -> bool {
+        dir.join(CHECKPOINT_FILE).is_file()
+    }
+
+    /// Starts a journal in `dir`, creating it if needed, with `image` as the first checkpoint
+    pub fn create(dir: &Path, image: &MemoryImage, options: JournalOptions) -> Result<Journal, String> {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let file = write_checkpoint(dir, image)?;
+        Ok(Journal { dir: dir.to_path_buf(), file, options, records: 0, broken: None })
+    }
+
+    /// Opens the journal in `dir`, returning the checkpoint and the records written after it.
+    /// A record cut off or corrupted by a crash ends the log, and is cut from the file.
+    pub fn open(dir: &Path, options: JournalOptions) -> Result<(MemoryImage, Vec<JournalRecord>, Journal), String> {
+        let checkpoint_path = dir.join(CHECKPOINT_FILE);
+        let checkpoint = fs::read(&checkpoint_path).map_err(|e| format!("Failed to read {}: {}", checkpoint_path.display(), e))?;
+        let image = MemoryImage::decode(&checkpoint).map_err(|e| format!("{}: {}", checkpoint_path.display(), e))?;
+
+        let path = dir.join(JOURNAL_FILE);
+        let log = fs::read(&path).unwrap_or_default();
+        if log.len() < HEADER_LEN || log[..HEADER_LEN] != header(&checkpoint) {
+            // No log yet, or one from before the checkpoint was replaced
+            let file = write_log(dir, &checkpoint)?;
+            return Ok((image, Vec::new(), Journal { dir: dir.to_path_buf(), file, options, records: 0, broken: None }));
+        }
+
+        let mut records = Vec::new();
+        let mut end = HEADER_LEN;
+        while let Some((record, len)) = JournalRecord::decode(&log[end..]) {
+            records.push(record);
+            end += len;
+        }
+        let file = OpenOptions::new().append(true).open(&path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        if end < log.len() {
+            file.set_len(end as u64).map_err(|e| format!("Failed to truncate {}: {}", path.display(), e))?;
+        }
+        let journal = Journal { dir: dir.to_path_buf(), file, options, records: records.len(), broken: None };
+        Ok((image, records, journal))
+    }
+
+    pub fn options(&self) -> JournalOptions {
+        self.options
+    }
+
+    /// Appends a record, syncing it to disk if the policy says so. A failed write is cut back
+    /// off the log; if even that fails, appends are refused until the next checkpoint.
+    pub fn append(&mut self, record: &JournalRecord) -> Result<(), String> {
+        let path = self.dir.join(JOURNAL_FILE);
+        if let Some(error) = &self.broken {
+            return Err(format!("{} may end in a torn record ({}); checkpoint to start a new log", path.display(), error));
+        }
+        let length = self.file.metadata().map_err(|e| format!("Failed to read {}: {}", path.display(), e))?.len();
+        let written = self.file.write_all(&record.encode()).map_err(|e| format!("Failed to write {}: {}", path.display(), e)).and_then(|_| {
+            if self.options.sync == SyncPolicy::Always {
+                self.file.sync_data().map_err(|e| format!("Failed to sync {}: {}", path.display(), e))?;
+            }
+            Ok(())
+        });
+        if let Err(error) = written {
+            // Records appended after a torn one would be lost when the log is next opened
+            if self.file.set_len(length).is_err() {
+                self.broken = Some(error.clone());
+            }
+            return Err(error);
+        }
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Whether enough records have been written since the last checkpoint to take another
+    pub fn checkpoint_due(&self) -> bool {
+        self.options.checkpoint_every > 0 && self.records >= self.options.checkpoint_every
+    }
+
+    /// Replaces the checkpoint with `image` and starts an empty log after it
+    pub fn checkpoint(&mut self, image: &MemoryImage) -> Result<(), String> {
+        self.file = write_checkpoint(&self.dir, image)?;
+        self.records = 0;
+        self.broken = None;
+        Ok(())
+    }
+}
+
+fn header(checkpoint: &[u8]) -> Vec<u8> {
+    let mut header = JOURNAL_MAGIC.to_vec();
+    header.extend_from_slice(&JOURNAL_VERSION.to_le_bytes());
+    header.extend_from_slice(&checksum(checkpoint).to_le_bytes());
+    header
+}
+
+// Writes `bytes` to `name` in `dir` through a temporary file and a rename, so the file is
+// either the old one or the new one after a crash
+fn replace_file(dir: &Path, name: &str, bytes: &[u8]) -> Result<(), String> {
+    let (path, temporary) = (dir.join(name), dir.join(format!("{}.tmp", name)));
+    let mut file = File::create(&temporary).map_err(|e| format!("Failed to create {}: {}", temporary.display(), e))?;
+    file.write_all(bytes).and_then(|_| file.sync_all()).map_err(|e| format!("Failed to write {}: {}", temporary.display(), e))?;
+    fs::rename(&temporary, &path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))?;
+    // Make the rename itself durable; not every platform can sync a directory
+    let _ = File::open(dir).and_then(|dir| dir.sync_all());
+    Ok(())
+}
+
+fn write_log(dir: &Path, checkpoint: &[u8]) -> Result<File, String> {
+    replace_file(dir, JOURNAL_FILE, &header(checkpoint))?;
+    let path = dir.join(JOURNAL_FILE);
+    OpenOptions::new().append(true).open(&path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))
+}
+
+fn write_checkpoint(dir: &Path, image: &MemoryImage) -> Result<File, String> {
+    let checkpoint = image.encode();
+    replace_file(dir, CHECKPOINT_FILE, &checkpoint)?;
+    write_log(dir, &checkpoint)
+}
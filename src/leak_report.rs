@@ -0,0 +1,121 @@
+use std::backtrace::Backtrace;
+use std::collections::HashMap;
+use std::panic::Location;
+
+/// What a `MemoryManager` does with blocks that are still live when it is dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeakCheck {
+    Ignore,
+    Print,
+    Panic,
+}
+
+/// Where a block was allocated (or last resized)
+#[derive(Debug)]
+pub struct AllocationSite {
+    pub location: &'static Location<'static>,
+    pub backtrace: Option<Backtrace>,
+}
+
+impl AllocationSite {
+    #[track_caller]
+    pub fn capture(with_backtrace: bool) -> Self {
+        AllocationSite {
+            location: Location::caller(),
+            backtrace: if with_backtrace { Some(Backtrace::force_capture()) } else { None },
+        }
+    }
+}
+
+/// Live blocks that share a call site
+#[derive(Debug, Clone)]
+pub struct LeakEntry {
+    pub location: String,
+    pub ids: Vec<usize>,
+    pub bytes: usize,
+    pub backtrace: Option<String>, // Backtrace of the first block from this site, if captured
+}
+
+impl LeakEntry {
+    pub fn count(&self) -> usize {
+        self.ids.len()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LeakReport {
+    pub entries: Vec<LeakEntry>,
+}
+
+impl LeakReport {
+    /// Groups `(id, size, site)` triples by call site, largest byte total first
+    pub fn from_blocks<'a, I>(blocks: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, usize, Option<&'a AllocationSite>)>,
+    {
+        let mut entries: Vec<LeakEntry> = Vec::new();
+        let mut by_location: HashMap<String, usize> = HashMap::new();
+
+        for (id, size, site) in blocks {
+            let location = match site {
+                Some(site) => site.location.to_string(),
+                None => "<unknown>".to_string(),
+            };
+            let index = *by_location.entry(location.clone()).or_insert_with(|| {
+                entries.push(LeakEntry {
+                    location,
+                    ids: Vec::new(),
+                    bytes: 0,
+                    backtrace: site.and_then(|site| site.backtrace.as_ref()).map(|bt| bt.to_string()),
+                });
+                entries.len() - 1
+            });
+            entries[index].ids.push(id);
+            entries[index].bytes += size;
+        }
+
+        entries.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.location.cmp(&b.location)));
+        LeakReport { entries }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn block_count(&self) -> usize {
+        self.entries.iter().map(|entry| entry.count()).sum()
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.entries.iter().map(|entry| entry.bytes).sum()
+    }
+}
+
+impl std::fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} live block(s), {} bytes, from {} call site(s):",
+            self.block_count(),
+            self.total_bytes(),
+            self.entries.len()
+        )?;
+        for entry in &self.entries {
+            let ids: Vec<String> = entry.ids.iter().map(|id| id.to_string()).collect();
+            writeln!(
+                f,
+                "  {}: {} block(s), {} bytes (IDs {})",
+                entry.location,
+                entry.count(),
+                entry.bytes,
+                ids.join(", ")
+            )?;
+            if let Some(backtrace) = &entry.backtrace {
+                for line in backtrace.lines() {
+                    writeln!(f, "      {}", line)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
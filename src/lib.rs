@@ -1,11 +1,46 @@
 pub mod free_block;
 pub mod allocated_block;
 pub mod buddy_allocator;
+pub mod block_header;
 pub mod memory_block;
 pub mod memory_manager;
+pub mod leak_report;
+pub mod heap_profiler;
+pub mod chrome_trace;
+pub mod trace;
+pub mod malloc_trace;
+pub mod workload;
+pub mod bench;
+pub mod model;
+pub mod differential;
+pub mod command;
+pub mod json;
+pub mod parser;
+pub mod script;
+pub mod line_editor;
+pub mod repl;
+pub mod memory_map;
+pub mod cli;
+pub mod report;
+pub mod image;
+pub mod journal;
 
 pub use free_block::FreeBlock;
 pub use allocated_block::AllocatedBlock;
-pub use buddy_allocator::BuddyAllocator;
+pub use buddy_allocator::{BuddyAllocator, PlacementStrategy};
+pub use block_header::BlockHeader;
 pub use memory_block::MemoryBlock;
-pub use memory_manager::MemoryManager;
+pub use memory_manager::{BlockInfo, ManagerConfig, MemoryManager, MemoryStats, SearchMatch};
+pub use command::{Command, COMMAND_NAMES};
+pub use parser::ParseError;
+pub use repl::Repl;
+pub use script::{Breakpoint, Interpreter, Script};
+pub use memory_map::{MapChange, MemoryMap};
+pub use report::{Report, ReportStep};
+pub use image::{ImageBlock, MemoryImage};
+pub use journal::{Journal, JournalOptions, JournalRecord, SyncPolicy};
+pub use leak_report::{LeakCheck, LeakReport};
+pub use heap_profiler::HeapProfiler;
+pub use chrome_trace::ChromeTrace;
+pub use trace::{replay, replay_with_config, ReplayReport, Trace};
+pub use malloc_trace::{replay_malloc_trace, replay_malloc_trace_with_config, MallocReplayReport, MallocTrace};
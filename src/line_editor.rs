@@ -0,0 +1,231 @@
+use std::io::{self, BufRead, Write};
+
+const STDIN: libc::c_int = 0;
+
+// Puts the terminal in raw mode for as long as it lives, restoring the saved settings on drop
+struct RawMode {
+    saved: libc::termios,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(STDIN, &mut saved) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = saved;
+        raw.c_iflag &= !(libc::ICRNL | libc::IXON);
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(STDIN, libc::TCSAFLUSH, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(RawMode { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(STDIN, libc::TCSAFLUSH, &self.saved) };
+    }
+}
+
+fn read_byte() -> io::Result<Option<u8>> {
+    let mut byte = 0u8;
+    loop {
+        let n = unsafe { libc::read(STDIN, &mut byte as *mut u8 as *mut libc::c_void, 1) };
+        match n {
+            1 => return Ok(Some(byte)),
+            0 => return Ok(None),
+            _ => {
+                let error = io::Error::last_os_error();
+                if error.kind() != io::ErrorKind::Interrupted {
+                    return Err(error);
+                }
+            }
+        }
+    }
+}
+
+// Reads the rest of a UTF-8 character whose first byte has been read
+fn read_char(first: u8) -> io::Result<Option<char>> {
+    let len = match first {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => 1,
+    };
+    let mut bytes = vec![first];
+    while bytes.len() < len {
+        match read_byte()? {
+            Some(byte) => bytes.push(byte),
+            None => break,
+        }
+    }
+    Ok(std::str::from_utf8(&bytes).ok().and_then(|text| text.chars().next()))
+}
+
+fn common_prefix(words: &[String]) -> String {
+    let mut prefix: Vec<char> = words.first().map(|word| word.chars().collect()).unwrap_or_default();
+    for word in &words[1..] {
+        let matching = prefix.iter().zip(word.chars()).take_while(|(a, b)| **a == *b).count();
+        prefix.truncate(matching);
+    }
+    prefix.into_iter().collect()
+}
+
+/// Minimal line editor with history and tab completion. Falls back to plain buffered reads
+/// (and no prompt) when stdin is not a terminal.
+#[derive(Debug, Default)]
+pub struct LineEditor {
+    history: Vec<String>,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        LineEditor::default()
+    }
+
+    pub fn is_terminal() -> bool {
+        unsafe { libc::isatty(STDIN) == 1 }
+    }
+
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Adds a line to the history, skipping blanks and immediate repeats
+    pub fn add_history(&mut self, line: &str) {
+        if !line.trim().is_empty() && self.history.last().map(String::as_str) != Some(line) {
+            self.history.push(line.to_string());
+        }
+    }
+
+    /// Reads a line, returning `None` at end of input. `complete` is given the text before the
+    /// cursor and returns candidates for the word being typed.
+    pub fn read_line(&mut self, prompt: &str, complete: &dyn Fn(&str) -> Vec<String>) -> io::Result<Option<String>> {
+        if !Self::is_terminal() {
+            let mut line = String::new();
+            if io::stdin().lock().read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            return Ok(Some(line.trim_end_matches(['\n', '\r']).to_string()));
+        }
+
+        let _raw = RawMode::enable()?;
+        let mut line: Vec<char> = Vec::new();
+        let mut cursor = 0;
+        let mut history_index = self.history.len();
+        let mut pending = String::new(); // The unsent line while browsing history
+        let mut out = io::stdout();
+        self.redraw(&mut out, prompt, &line, cursor)?;
+
+        loop {
+            let Some(byte) = read_byte()? else {
+                return Ok(None);
+            };
+            match byte {
+                b'\r' | b'\n' => {
+                    write!(out, "\r\n")?;
+                    out.flush()?;
+                    return Ok(Some(line.into_iter().collect()));
+                }
+                // Ctrl-D ends input on an empty line and deletes forward otherwise
+                4 if line.is_empty() => {
+                    write!(out, "\r\n")?;
+                    out.flush()?;
+                    return Ok(None);
+                }
+                4 if cursor < line.len() => {
+                    line.remove(cursor);
+                }
+                // Ctrl-C discards the line
+                3 => {
+                    write!(out, "^C\r\n")?;
+                    line.clear();
+                    cursor = 0;
+                    history_index = self.history.len();
+                }
+                1 => cursor = 0,          // Ctrl-A
+                5 => cursor = line.len(), // Ctrl-E
+                21 => {
+                    // Ctrl-U
+                    line.drain(..cursor);
+                    cursor = 0;
+                }
+                127 | 8 if cursor > 0 => {
+                    cursor -= 1;
+                    line.remove(cursor);
+                }
+                b'\t' => {
+                    let before: String = line[..cursor].iter().collect();
+                    let word_start = before.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+                    let word = &before[word_start..];
+                    let candidates = complete(&before);
+                    if candidates.is_empty() {
+                        continue;
+                    }
+                    let mut completion = common_prefix(&candidates);
+                    if candidates.len() == 1 {
+                        completion.push(' ');
+                    } else if completion.chars().count() <= word.chars().count() {
+                        write!(out, "\r\n{}\r\n", candidates.join("  "))?;
+                    }
+                    let word_chars = word.chars().count();
+                    if completion.chars().count() > word_chars {
+                        let start = cursor - word_chars;
+                        line.splice(start..cursor, completion.chars());
+                        cursor = start + completion.chars().count();
+                    }
+                }
+                0x1b => {
+                    if read_byte()? != Some(b'[') {
+                        continue;
+                    }
+                    match read_byte()? {
+                        Some(b'A') if history_index > 0 => {
+                            if history_index == self.history.len() {
+                                pending = line.iter().collect();
+                            }
+                            history_index -= 1;
+                            line = self.history[history_index].chars().collect();
+                            cursor = line.len();
+                        }
+                        Some(b'B') if history_index < self.history.len() => {
+                            history_index += 1;
+                            let text = self.history.get(history_index).unwrap_or(&pending);
+                            line = text.chars().collect();
+                            cursor = line.len();
+                        }
+                        Some(b'C') if cursor < line.len() => cursor += 1,
+                        Some(b'D') if cursor > 0 => cursor -= 1,
+                        Some(b'H') => cursor = 0,
+                        Some(b'F') => cursor = line.len(),
+                        Some(b'3') if read_byte()? == Some(b'~') && cursor < line.len() => {
+                            line.remove(cursor);
+                        }
+                        _ => {}
+                    }
+                }
+                byte if byte >= 0x20 && byte != 127 => {
+                    if let Some(c) = read_char(byte)? {
+                        line.insert(cursor, c);
+                        cursor += 1;
+                    }
+                }
+                _ => {}
+            }
+            self.redraw(&mut out, prompt, &line, cursor)?;
+        }
+    }
+
+    fn redraw(&self, out: &mut io::Stdout, prompt: &str, line: &[char], cursor: usize) -> io::Result<()> {
+        let text: String = line.iter().collect();
+        write!(out, "\r{}{}\x1b[K", prompt, text)?;
+        if cursor < line.len() {
+            write!(out, "\x1b[{}D", line.len() - cursor)?;
+        }
+        out.flush()
+    }
+}
@@ -1,66 +1,491 @@
 use std::env;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::fs;
+use std::io::{BufRead, Read};
+use std::path::{Path, PathBuf};
 
-use memory_manager::MemoryManager;
+use memory_manager::bench::{run_bench, BenchOptions, BENCH_USAGE};
+use memory_manager::cli::{Cli, Format, GlobalOptions, RunOptions, Subcommand, Verbosity, USAGE};
+use memory_manager::json::Json;
+use memory_manager::line_editor::LineEditor;
+use memory_manager::script::Event;
+use memory_manager::{
+    replay_malloc_trace_with_config, replay_with_config, Interpreter, JournalOptions, MallocTrace, MemoryImage, MemoryManager, MemoryMap, PlacementStrategy,
+    Repl, Report, Script, Trace,
+};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <command_file>", args[0]);
-        return;
+    let cli = match Cli::parse(&args[1..]) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("Error: {}\nRun '{} --help' for usage.", e, args[0]);
+            std::process::exit(2);
+        }
+    };
+
+    let options = &cli.options;
+    let status = match cli.subcommand {
+        Subcommand::Help => {
+            println!("{}", USAGE);
+            0
+        }
+        Subcommand::Run(run_options) => run(options, &run_options),
+        Subcommand::Repl { record } => run(options, &RunOptions { record, interactive: true, ..RunOptions::default() }),
+        Subcommand::Replay { trace } => replay_trace(options, &trace),
+        Subcommand::Import { trace } => import_malloc_trace(options, &trace),
+        Subcommand::Inspect { image } => inspect(options, &image),
+        Subcommand::Test { dir, keep_going } => run_tests(options, &dir, keep_going),
+        Subcommand::Report { file, html, keep_going } => report(options, &file, &html, keep_going),
+        Subcommand::Bench { args } => bench(options, &args),
+    };
+    std::process::exit(status);
+}
+
+// Reads a command file, or all of stdin for "-"; returns the name to report it under
+fn read_source(path: &str) -> Result<(String, String), String> {
+    if path == "-" {
+        let mut source = String::new();
+        return match std::io::stdin().read_to_string(&mut source) {
+            Ok(_) => Ok(("<stdin>".to_string(), source)),
+            Err(e) => Err(format!("Failed to read stdin: {}", e)),
+        };
+    }
+    match fs::read_to_string(path) {
+        Ok(source) => Ok((path.to_string(), source)),
+        Err(e) => Err(format!("Failed to open {}: {}", path, e)),
     }
+}
+
+// Runs the command files in order against one manager, then the prompt if asked for or a
+// breakpoint was reached. Returns the exit status: 0 if everything succeeded, 1 if anything
+// failed, 2 for bad input.
+fn run(options: &GlobalOptions, run: &RunOptions) -> i32 {
+    // Read every file up front so a missing one is reported before anything runs
+    let mut sources = Vec::new();
+    for file in &run.files {
+        match read_source(file) {
+            Ok(source) => sources.push(source),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return 2;
+            }
+        }
+    }
+    let config = options.config(PlacementStrategy::default());
+    let memory_manager = match &run.journal {
+        Some(dir) => config.and_then(|config| MemoryManager::open_durable(dir, config, JournalOptions::default())),
+        None => config.and_then(MemoryManager::with_config),
+    };
+    let mut memory_manager = match memory_manager {
+        Ok(memory_manager) => memory_manager,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 2;
+        }
+    };
 
-    let file = File::open(&args[1]).expect("Failed to open file");
-    let reader = BufReader::new(file);
+    // The manager's own log lines are part of the normal text output; JSON output is for
+    // machines, so they stay off there
+    let logging = options.format == Format::Text && options.verbosity >= Verbosity::Normal;
+    if logging && !run.files.is_empty() {
+        println!("Initializing MemoryManager with buffer size {}", memory_manager.config().arena_size);
+    }
+    memory_manager.set_verbose(logging);
+    memory_manager.set_explain(options.explain);
+    if run.record.is_some() {
+        memory_manager.start_recording();
+    }
+    if run.chrome_trace.is_some() {
+        memory_manager.enable_chrome_trace();
+    }
 
-    let mut memory_manager = MemoryManager::new();
+    let snapshot_dir = PathBuf::from(run.snapshot_dir.as_deref().unwrap_or("snapshots"));
+    if run.snapshot_every.is_some() {
+        if let Err(e) = fs::create_dir_all(&snapshot_dir) {
+            eprintln!("Error: Failed to create {}: {}", snapshot_dir.display(), e);
+            return 2;
+        }
+    }
+    let mut observer = Observer {
+        stepping: run.step,
+        pause: LineEditor::is_terminal(),
+        previous: MemoryMap::capture(&memory_manager),
+        commands: 0,
+        snapshot_every: run.snapshot_every,
+        snapshot_dir: &snapshot_dir,
+        format: options.format,
+    };
 
-    for line in reader.lines() {
-        let line = line.expect("Failed to read line");
-        eprintln!("Processing command: {}", line);
+    let mut interpreter = Interpreter::new(run.keep_going);
+    interpreter.set_until(run.until.clone());
+    interpreter.set_capture_maps(run.step || run.snapshot_every.is_some());
+    for (file, source) in &sources {
+        run_commands(&mut memory_manager, &mut interpreter, file, source, options, &mut observer);
+        if (interpreter.failed() && !run.keep_going) || interpreter.stopped_at().is_some() {
+            break;
+        }
+    }
+    let mut failed = interpreter.failed();
 
-        let mut parts = line.split_whitespace();
+    let mut interactive = run.interactive;
+    if let Some((file, line)) = interpreter.stopped_at() {
+        eprintln!("Stopped before {}:{}", file, line);
+        interactive = true;
+    } else if let Some(breakpoint) = &run.until {
+        eprintln!("Error: Never reached {}", breakpoint);
+        failed = true;
+    }
+    if interactive {
+        memory_manager.set_verbose(false);
+        let _ = memory_manager.take_explanation();
+        let mut repl = Repl::new(memory_manager);
+        failed |= repl.run();
+        memory_manager = repl.into_memory_manager();
+    }
 
-        match parts.next().unwrap() {
-            "INSERT" => {
-                let size = parts.next().unwrap().parse::<usize>().unwrap();
-                let data = parts.next().unwrap().as_bytes().to_vec();
-                match memory_manager.insert(size, data) {
-                    Ok(id) => eprintln!("Allocated block with ID {}", id),
-                    Err(e) => eprintln!("Error: {}", e),
+    if let (Some(path), Some(trace)) = (&run.record, memory_manager.take_trace()) {
+        if let Err(e) = trace.save(path) {
+            eprintln!("Error: {}", e);
+        }
+    }
+    if let (Some(path), Some(timeline)) = (&run.chrome_trace, memory_manager.take_chrome_trace()) {
+        if let Err(e) = timeline.save(path) {
+            eprintln!("Error: {}", e);
+        }
+    }
+    if options.verbosity == Verbosity::Verbose {
+        let report = memory_manager.leak_report();
+        if !report.is_empty() {
+            eprint!("Still allocated at exit: {}", report);
+        }
+    }
+    if failed { 1 } else { 0 }
+}
+
+// What `run` does with the memory map after each command: shows what changed and waits for
+// Enter when stepping, and saves a memory image and the map as text every `snapshot_every`
+// commands
+struct Observer<'a> {
+    stepping: bool,
+    pause: bool, // Only when there is a terminal to answer
+    previous: MemoryMap,
+    commands: usize,
+    snapshot_every: Option<usize>,
+    snapshot_dir: &'a Path,
+    format: Format,
+}
+
+impl Observer<'_> {
+    fn observe(&mut self, file: &str, line: usize, map: &MemoryMap, memory_manager: &MemoryManager) {
+        self.commands += 1;
+        if self.stepping {
+            let changes = self.previous.diff(map);
+            if self.format == Format::Json {
+                let changes: Vec<String> = changes.iter().map(ToString::to_string).collect();
+                println!("{}", Json::object([("file", file.into()), ("line", line.into()), ("status", "step".into()), ("changes", changes.into())]));
+            } else if changes.is_empty() {
+                eprintln!("  (no change to the blocks)");
+            } else {
+                for change in &changes {
+                    eprintln!("  {}", change);
                 }
             }
-            "DELETE" => {
-                let id = parts.next().unwrap().parse::<usize>().unwrap();
-                if let Err(e) = memory_manager.delete(id) {
-                    eprintln!("Error: {}", e);
-                } else {
-                    eprintln!("Deleted block with ID {}", id);
+            if self.pause {
+                eprint!("-- {}:{}: Enter for the next command, c to continue --", file, line);
+                let mut answer = String::new();
+                if std::io::stdin().lock().read_line(&mut answer).is_err() || answer.is_empty() || answer.trim() == "c" {
+                    self.stepping = false;
                 }
             }
-            "READ" => {
-                let id = parts.next().unwrap().parse::<usize>().unwrap();
-                match memory_manager.find(id) {
-                    Ok(block) => eprintln!("Block {} contains {:?}", id, block),
-                    Err(e) => eprintln!("Error: {}", e),
+            self.previous = map.clone();
+        }
+
+        if self.snapshot_every.is_some_and(|every| self.commands.is_multiple_of(every)) {
+            let name = format!("snapshot-{:06}", self.commands);
+            let text = format!("# After {} commands, at {}:{}\n{}", self.commands, file, line, map);
+            let files = [("img", memory_manager.to_image().encode()), ("txt", text.into_bytes())];
+            for (extension, bytes) in files {
+                let path = self.snapshot_dir.join(format!("{}.{}", name, extension));
+                if let Err(e) = fs::write(&path, bytes) {
+                    eprintln!("Error: Failed to write {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+}
+
+// Runs one command file, stopping at the first parse or command error unless the
+// interpreter keeps going
+fn run_commands(
+    memory_manager: &mut MemoryManager,
+    interpreter: &mut Interpreter,
+    file: &str,
+    source: &str,
+    options: &GlobalOptions,
+    observer: &mut Observer,
+) {
+    let script = Script::parse_for(file, source, memory_manager.config().arena_size);
+    let quiet = options.verbosity == Verbosity::Quiet;
+    interpreter.run(memory_manager, &script.statements, &mut |event| {
+        if let Event::State { file, line, map, memory_manager } = event {
+            observer.observe(file, line, map, memory_manager);
+            return;
+        }
+        if options.format == Format::Json {
+            if let Some(json) = event.to_json() {
+                println!("{}", json);
+            }
+            return;
+        }
+        match event {
+            Event::Command { text, .. } if !quiet => eprintln!("Processing command: {}", text),
+            Event::Command { .. } => {}
+            Event::Finished { outcome, explanation, .. } => {
+                if let Some(message) = outcome.message.as_ref().filter(|_| !quiet) {
+                    eprintln!("{}", message);
+                }
+                for line in explanation {
+                    println!("{}", line);
+                }
+                if let Some(output) = &outcome.output {
+                    print!("{}", output);
                 }
             }
-            "UPDATE" => {
-                let id = parts.next().unwrap().parse::<usize>().unwrap();
-                let data = parts.next().unwrap().as_bytes().to_vec();
-                if let Err(e) = memory_manager.update(id, data) {
-                    eprintln!("Error: {}", e);
-                } else {
-                    eprintln!("Updated block with ID {}", id);
+            Event::Error { message, explanation, .. } => {
+                for line in explanation {
+                    println!("{}", line);
                 }
+                eprintln!("{}", message);
             }
-            "DUMP" => {
-                memory_manager.dump();
+            Event::State { .. } => {}
+        }
+    });
+}
+
+// Runs every `.cmmd` file in `dir` against a fresh manager, printing PASS or FAIL with the
+// errors for each and a summary. Returns the exit status: 0 if all passed, 1 if any failed.
+fn run_tests(options: &GlobalOptions, dir: &str, keep_going: bool) -> i32 {
+    let config = match options.config(PlacementStrategy::default()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 2;
+        }
+    };
+    let mut files: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "cmmd"))
+            .collect(),
+        Err(e) => {
+            eprintln!("Error: Failed to read {}: {}", dir, e);
+            return 2;
+        }
+    };
+    if files.is_empty() {
+        eprintln!("Error: No .cmmd files in {}", dir);
+        return 2;
+    }
+    files.sort();
+
+    let mut failures = 0;
+    for path in &files {
+        let file = path.to_string_lossy();
+        let mut errors = Vec::new();
+        let mut expectations = 0;
+        match fs::read_to_string(path) {
+            Ok(source) => {
+                let mut memory_manager = MemoryManager::with_config(config).expect("config was validated");
+                memory_manager.set_verbose(false);
+                let mut interpreter = Interpreter::new(keep_going);
+                interpreter.run(&mut memory_manager, &Script::parse_for(&file, &source, config.arena_size).statements, &mut |event| {
+                    if let Event::Error { message, .. } = event {
+                        errors.push(message.to_string());
+                    }
+                });
+                expectations = interpreter.expectations_passed() + interpreter.expectations_failed();
             }
-            _ => {
-                eprintln!("Invalid command: {}", line);
+            Err(e) => errors.push(format!("Failed to open {}: {}", file, e)),
+        }
+
+        if errors.is_empty() {
+            if options.verbosity >= Verbosity::Normal {
+                println!("PASS {} ({} expectations)", file, expectations);
             }
+        } else {
+            failures += 1;
+            println!("FAIL {}", file);
+            for error in &errors {
+                println!("  {}", error.replace('\n', "\n  "));
+            }
+        }
+    }
+    println!("\n{} passed, {} failed", files.len() - failures, failures);
+    if failures > 0 { 1 } else { 0 }
+}
+
+// Runs a command file and writes an HTML report of every step. The report is written even
+// if a command failed, so the failure can be looked at.
+fn report(options: &GlobalOptions, path: &str, html: &str, keep_going: bool) -> i32 {
+    let (file, source) = match read_source(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 2;
+        }
+    };
+    let mut memory_manager = match options.config(PlacementStrategy::default()).and_then(MemoryManager::with_config) {
+        Ok(memory_manager) => memory_manager,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 2;
+        }
+    };
+    memory_manager.set_verbose(false);
+
+    let script = Script::parse_for(&file, &source, memory_manager.config().arena_size);
+    let report = Report::run(&file, &mut memory_manager, &script.statements, keep_going);
+    let errors: Vec<&str> = report.steps.iter().filter_map(|step| step.error.as_deref()).collect();
+    for error in &errors {
+        eprintln!("{}", error);
+    }
+    if let Err(e) = fs::write(html, report.to_html()) {
+        eprintln!("Error: Failed to write {}: {}", html, e);
+        return 2;
+    }
+    if options.verbosity >= Verbosity::Normal {
+        eprintln!("Wrote a report of {} steps to {}", report.steps.len() - 1, html);
+    }
+    if errors.is_empty() { 0 } else { 1 }
+}
+
+// Describes a memory image written by SAVE: its configuration, blocks and statistics.
+// Returns 2 if the image cannot be read or is inconsistent.
+fn inspect(options: &GlobalOptions, path: &str) -> i32 {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("Error: Failed to read image {}: {}", path, e);
+            return 2;
+        }
+    };
+    let (next_id, mut memory_manager) = match MemoryImage::decode(&bytes).and_then(|image| Ok((image.next_id, MemoryManager::from_image(image)?))) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("Error: {}: {}", path, e);
+            return 2;
+        }
+    };
+    memory_manager.set_verbose(false);
+
+    let config = memory_manager.config();
+    let stats = memory_manager.stats();
+    if options.format == Format::Json {
+        let json = Json::object([
+            ("image", path.into()),
+            ("bytes", bytes.len().into()),
+            ("arena_size", config.arena_size.into()),
+            ("min_block", config.min_block.into()),
+            ("strategy", config.strategy.name().into()),
+            ("block_headers", config.block_headers.into()),
+            ("next_id", next_id.into()),
+            ("blocks", memory_manager.blocks_json()),
+            ("stats", stats.to_json()),
+        ]);
+        println!("{}", json);
+        return 0;
+    }
+    println!("Memory image {} ({} bytes)", path, bytes.len());
+    println!(
+        "Arena of {} bytes, minimum block size {}, {}{}; next ID {}",
+        config.arena_size,
+        config.min_block,
+        config.strategy,
+        if config.block_headers { ", block headers" } else { "" },
+        next_id
+    );
+    print!("{}{}", memory_manager.format_dump(), stats);
+    0
+}
+
+// Replays a recorded trace, by default with the strategy it was recorded with, and
+// returns the exit status: 0 for an exact replay, 1 for divergences, 2 for bad input
+fn replay_trace(options: &GlobalOptions, path: &str) -> i32 {
+    let trace = match Trace::load(path) {
+        Ok(trace) => trace,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 2;
+        }
+    };
+    let config = match options.config(trace.strategy) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 2;
+        }
+    };
+
+    let report = replay_with_config(&trace, config);
+    print!("{}", report);
+    if report.is_exact() { 0 } else { 1 }
+}
+
+// Runs an "a <ptr> <size> / f <ptr> / r <old> <new> <size>" trace from another allocator
+// and prints how this one copes with the workload
+fn import_malloc_trace(options: &GlobalOptions, path: &str) -> i32 {
+    let config = match options.config(PlacementStrategy::FirstFit) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 2;
+        }
+    };
+    let trace = match MallocTrace::load_for(path, config.arena_size) {
+        Ok(trace) => trace,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 2;
+        }
+    };
+
+    print!("{}", replay_malloc_trace_with_config(&trace, config));
+    0
+}
+
+// Same as mm-bench; global sizes and strategy come first so the bench's own options can add to them
+fn bench(options: &GlobalOptions, args: &[String]) -> i32 {
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("Usage: memory_manager [options] bench [bench options]\n{}", BENCH_USAGE);
+        return 0;
+    }
+    let mut bench_args = Vec::new();
+    if let Some(arena_size) = options.arena_size {
+        bench_args.extend(["--arena-size".to_string(), arena_size.to_string()]);
+    }
+    if let Some(min_block) = options.min_block {
+        bench_args.extend(["--min-block".to_string(), min_block.to_string()]);
+    }
+    if let Some(strategy) = options.strategy {
+        bench_args.extend(["--strategy".to_string(), strategy.name().to_string()]);
+    }
+    bench_args.extend(args.iter().cloned());
+
+    let bench_options = match BenchOptions::parse(&bench_args) {
+        Ok(bench_options) => bench_options,
+        Err(e) => {
+            eprintln!("Error: {}\nUsage: memory_manager [options] bench [bench options]\n{}", e, BENCH_USAGE);
+            return 2;
+        }
+    };
+    match run_bench(&bench_options) {
+        Ok(table) => {
+            print!("{}", table);
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+
+use crate::buddy_allocator::{PlacementStrategy, DEFAULT_ARENA_SIZE};
+use crate::memory_manager::{ManagerConfig, MemoryManager};
+
+/// One event from a foreign allocator trace. Pointers are kept as written; only their identity matters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MallocEvent {
+    Alloc { ptr: String, size: usize },
+    Free { ptr: String },
+    Realloc { old: String, new: String, size: usize },
+}
+
+/// A parsed `a <ptr> <size>` / `f <ptr>` / `r <old> <new> <size>` trace; `#` starts a comment
+#[derive(Debug, Clone, Default)]
+pub struct MallocTrace {
+    pub events: Vec<(usize, MallocEvent)>, // (1-based line number, event)
+}
+
+impl MallocTrace {
+    /// Reads a trace for a manager with the default arena size
+    pub fn load(path: &str) -> Result<Self, String> {
+        Self::load_for(path, DEFAULT_ARENA_SIZE)
+    }
+
+    /// Reads a trace whose sizes must fit an arena of `arena_size` bytes
+    pub fn load_for(path: &str, arena_size: usize) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Failed to read trace {}: {}", path, e))?;
+        Self::parse_for(&text, arena_size).map_err(|e| format!("{}:{}", path, e))
+    }
+
+    /// Parses a trace for a manager with the default arena size
+    pub fn parse(text: &str) -> Result<Self, String> {
+        Self::parse_for(text, DEFAULT_ARENA_SIZE)
+    }
+
+    /// Parses a trace whose sizes must fit an arena of `arena_size` bytes
+    pub fn parse_for(text: &str, arena_size: usize) -> Result<Self, String> {
+        let mut events = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let size = |field: &str| match field.parse::<usize>() {
+                Ok(size) if size > arena_size => Err(format!("{}: size {} is larger than the {}-byte arena", i + 1, size, arena_size)),
+                Ok(size) => Ok(size),
+                Err(_) => Err(format!("{}: invalid size '{}'", i + 1, field)),
+            };
+            let event = match fields.as_slice() {
+                ["a", ptr, bytes] => MallocEvent::Alloc { ptr: ptr.to_string(), size: size(bytes)? },
+                ["f", ptr] => MallocEvent::Free { ptr: ptr.to_string() },
+                ["r", old, new, bytes] => MallocEvent::Realloc { old: old.to_string(), new: new.to_string(), size: size(bytes)? },
+                _ => return Err(format!("{}: expected 'a <ptr> <size>', 'f <ptr>' or 'r <old> <new> <size>'", i + 1)),
+            };
+            events.push((i + 1, event));
+        }
+        Ok(MallocTrace { events })
+    }
+}
+
+fn is_null(ptr: &str) -> bool {
+    matches!(ptr, "0" | "0x0" | "(nil)" | "NULL" | "null")
+}
+
+/// An allocation or reallocation the manager could not satisfy
+#[derive(Debug, Clone)]
+pub struct FailurePoint {
+    pub line: usize,
+    pub size: usize,
+    pub error: String,
+    pub used_bytes: usize,
+    pub largest_free: usize,
+}
+
+/// Arena usage after one trace event
+#[derive(Debug, Clone, Copy)]
+pub struct UtilizationSample {
+    pub line: usize,
+    pub live_blocks: usize,
+    pub used_bytes: usize,
+    pub requested_bytes: usize, // What the trace asked for, before rounding
+    pub utilization: f64,
+    pub fragmentation: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MallocReplayReport {
+    pub strategy: PlacementStrategy,
+    pub events: usize,
+    pub failures: Vec<FailurePoint>,
+    pub unknown_frees: usize, // Frees of pointers that were never (successfully) allocated
+    pub malformed: Vec<(usize, String)>, // Events that cannot happen in a real program, by line; they are skipped
+    pub peak_used_bytes: usize,
+    pub peak_fragmentation: f64,
+    pub peak_fragmentation_line: usize,
+    pub samples: Vec<UtilizationSample>,
+}
+
+impl MallocReplayReport {
+    /// The utilization curve, one row per event
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("line,live_blocks,used_bytes,requested_bytes,utilization,fragmentation\n");
+        for sample in &self.samples {
+            let _ = writeln!(
+                out,
+                "{},{},{},{},{:.4},{:.4}",
+                sample.line, sample.live_blocks, sample.used_bytes, sample.requested_bytes, sample.utilization, sample.fragmentation
+            );
+        }
+        out
+    }
+}
+
+impl std::fmt::Display for MallocReplayReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "Replayed {} events with {}", self.events, self.strategy)?;
+        writeln!(f, "  failed allocations: {}", self.failures.len())?;
+        writeln!(f, "  frees of unknown pointers: {}", self.unknown_frees)?;
+        writeln!(f, "  malformed lines: {}", self.malformed.len())?;
+        for (line, problem) in self.malformed.iter().take(10) {
+            writeln!(f, "  line {}: {}", line, problem)?;
+        }
+        if self.malformed.len() > 10 {
+            writeln!(f, "  ... {} more malformed lines", self.malformed.len() - 10)?;
+        }
+        writeln!(f, "  peak used bytes: {}", self.peak_used_bytes)?;
+        writeln!(
+            f,
+            "  peak fragmentation: {:.1}% (line {})",
+            self.peak_fragmentation * 100.0,
+            self.peak_fragmentation_line
+        )?;
+
+        for failure in self.failures.iter().take(10) {
+            writeln!(
+                f,
+                "  line {}: {} bytes failed ({}) with {} bytes used, largest free block {}",
+                failure.line, failure.size, failure.error, failure.used_bytes, failure.largest_free
+            )?;
+        }
+        if self.failures.len() > 10 {
+            writeln!(f, "  ... {} more failures", self.failures.len() - 10)?;
+        }
+
+        // Coarse utilization curve, at most 20 rows
+        if !self.samples.is_empty() {
+            writeln!(f, "  {:>8}  {:>6}  {:>6}", "line", "used", "frag")?;
+            let step = self.samples.len().div_ceil(20);
+            for sample in self.samples.iter().step_by(step) {
+                writeln!(
+                    f,
+                    "  {:>8}  {:>5.1}%  {:>5.1}%",
+                    sample.line,
+                    sample.utilization * 100.0,
+                    sample.fragmentation * 100.0
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Drives a fresh manager with a foreign trace, mapping the trace's pointers to block IDs.
+/// A failed realloc leaves the old block in place, as in C. A realloc of a pointer that is not
+/// live, or an allocation that returns one that is, means the trace is broken; such lines are
+/// reported as malformed and skipped.
+pub fn replay_malloc_trace(trace: &MallocTrace, strategy: PlacementStrategy) -> MallocReplayReport {
+    replay_malloc_trace_with_config(trace, ManagerConfig { strategy, ..ManagerConfig::default() })
+}
+
+/// Like `replay_malloc_trace`, with a manager built from `config`. Panics if `config` is invalid.
+pub fn replay_malloc_trace_with_config(trace: &MallocTrace, config: ManagerConfig) -> MallocReplayReport {
+    let strategy = config.strategy;
+    let mut memory_manager = MemoryManager::with_config(config).expect("invalid manager configuration");
+    memory_manager.set_verbose(false);
+
+    let mut blocks: HashMap<&str, (usize, usize)> = HashMap::new(); // ptr -> (block ID, requested size)
+    let mut requested_bytes = 0;
+    let mut report = MallocReplayReport {
+        strategy,
+        events: trace.events.len(),
+        failures: Vec::new(),
+        unknown_frees: 0,
+        malformed: Vec::new(),
+        peak_used_bytes: 0,
+        peak_fragmentation: 0.0,
+        peak_fragmentation_line: 0,
+        samples: Vec::with_capacity(trace.events.len()),
+    };
+
+    let fail = |memory_manager: &MemoryManager, line: usize, size: usize, error: String| {
+        let stats = memory_manager.stats();
+        FailurePoint { line, size, error, used_bytes: stats.used_bytes, largest_free: stats.largest_free }
+    };
+
+    for (line, event) in &trace.events {
+        let line = *line;
+        match event {
+            MallocEvent::Alloc { ptr, .. } if blocks.contains_key(ptr.as_str()) => {
+                report.malformed.push((line, format!("allocation returned {}, which is still live", ptr)));
+            }
+            MallocEvent::Alloc { ptr, size } => match memory_manager.insert(*size, Vec::new()) {
+                Ok(id) => {
+                    blocks.insert(ptr.as_str(), (id, *size));
+                    requested_bytes += size;
+                }
+                Err(e) => report.failures.push(fail(&memory_manager, line, *size, e)),
+            },
+            // free(NULL) is a no-op
+            MallocEvent::Free { ptr } if is_null(ptr) => {}
+            MallocEvent::Free { ptr } => match blocks.remove(ptr.as_str()) {
+                Some((id, size)) => {
+                    let _ = memory_manager.delete(id);
+                    requested_bytes -= size;
+                }
+                None => report.unknown_frees += 1,
+            },
+            MallocEvent::Realloc { old, new, size } => {
+                let existing = if is_null(old) { None } else { blocks.get(old.as_str()).copied() };
+                let moved_onto_live = new != old && blocks.contains_key(new.as_str());
+                match existing {
+                    None if !is_null(old) => report.malformed.push((line, format!("realloc of {}, which is not live", old))),
+                    _ if moved_onto_live => report.malformed.push((line, format!("realloc returned {}, which is still live", new))),
+                    // realloc(NULL, n) is malloc(n)
+                    None => match memory_manager.insert(*size, Vec::new()) {
+                        Ok(id) => {
+                            blocks.insert(new.as_str(), (id, *size));
+                            requested_bytes += size;
+                        }
+                        Err(e) => report.failures.push(fail(&memory_manager, line, *size, e)),
+                    },
+                    // realloc(p, 0) frees p
+                    Some((id, old_size)) if *size == 0 => {
+                        let _ = memory_manager.delete(id);
+                        blocks.remove(old.as_str());
+                        requested_bytes -= old_size;
+                    }
+                    Some((id, old_size)) => match memory_manager.resize(id, *size) {
+                        Ok(()) => {
+                            blocks.remove(old.as_str());
+                            blocks.insert(new.as_str(), (id, *size));
+                            requested_bytes = requested_bytes - old_size + size;
+                        }
+                        Err(e) => report.failures.push(fail(&memory_manager, line, *size, e)),
+                    },
+                }
+            }
+        }
+
+        let stats = memory_manager.stats();
+        let sample = UtilizationSample {
+            line,
+            live_blocks: stats.allocated_blocks,
+            used_bytes: stats.used_bytes,
+            requested_bytes,
+            utilization: stats.utilization(),
+            fragmentation: stats.fragmentation(),
+        };
+        report.peak_used_bytes = report.peak_used_bytes.max(sample.used_bytes);
+        if sample.fragmentation > report.peak_fragmentation {
+            report.peak_fragmentation = sample.fragmentation;
+            report.peak_fragmentation_line = line;
+        }
+        report.samples.push(sample);
+    }
+
+    report
+}
@@ -1,6 +1,26 @@
 use crate::free_block::FreeBlock;
 use crate::allocated_block::AllocatedBlock;
-use crate::buddy_allocator::BuddyAllocator;
+use crate::block_header::{self, BlockHeader, HEADER_SIZE};
+use crate::buddy_allocator::{BuddyAllocator, PlacementStrategy, DEFAULT_ARENA_SIZE};
+use crate::chrome_trace::ChromeTrace;
+use crate::heap_profiler::HeapProfiler;
+use crate::image::{ImageBlock, MemoryImage};
+use crate::journal::{Journal, JournalOptions, JournalRecord};
+use crate::json::{self, Json};
+use crate::leak_report::{AllocationSite, LeakCheck, LeakReport};
+use crate::trace::{Placement, Trace, TraceOp};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// Operation logging, silenced with `set_verbose(false)`
+macro_rules! log {
+    ($manager:expr, $($arg:tt)*) => {
+        if $manager.verbose {
+            println!($($arg)*);
+        }
+    };
+}
 
 #[derive(Debug, Clone)]
 pub enum MemoryBlock {
@@ -28,33 +48,414 @@ impl MemoryBlock {
     }
 }
 
+/// Point-in-time usage of the arena
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryStats {
+    pub total_bytes: usize,
+    pub used_bytes: usize,  // Sum of allocated block sizes, including rounding
+    pub free_bytes: usize,
+    pub largest_free: usize, // Largest block the allocator could hand out right now
+    pub allocated_blocks: usize,
+    pub free_blocks: usize,
+}
+
+impl MemoryStats {
+    /// Fraction of the arena in allocated blocks
+    pub fn utilization(&self) -> f64 {
+        if self.total_bytes == 0 { 0.0 } else { self.used_bytes as f64 / self.total_bytes as f64 }
+    }
+
+    /// External fragmentation: 0 when all free memory is one block, approaching 1 as it scatters
+    pub fn fragmentation(&self) -> f64 {
+        if self.free_bytes == 0 { 0.0 } else { 1.0 - self.largest_free as f64 / self.free_bytes as f64 }
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::object([
+            ("total_bytes", self.total_bytes.into()),
+            ("used_bytes", self.used_bytes.into()),
+            ("free_bytes", self.free_bytes.into()),
+            ("largest_free", self.largest_free.into()),
+            ("allocated_blocks", self.allocated_blocks.into()),
+            ("free_blocks", self.free_blocks.into()),
+            ("utilization", self.utilization().into()),
+            ("fragmentation", self.fragmentation().into()),
+        ])
+    }
+}
+
+impl std::fmt::Display for MemoryStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "Memory Stats:")?;
+        writeln!(f, "  Total bytes:      {}", self.total_bytes)?;
+        writeln!(f, "  Used bytes:       {} ({:.1}%)", self.used_bytes, self.utilization() * 100.0)?;
+        writeln!(f, "  Free bytes:       {}", self.free_bytes)?;
+        writeln!(f, "  Largest free:     {}", self.largest_free)?;
+        writeln!(f, "  Fragmentation:    {:.1}%", self.fragmentation() * 100.0)?;
+        writeln!(f, "  Allocated blocks: {}", self.allocated_blocks)?;
+        writeln!(f, "  Free blocks:      {}", self.free_blocks)
+    }
+}
+
+/// A copy of one block's details, as the `iter_*` methods of `MemoryManager` return them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockInfo {
+    pub id: Option<usize>, // `None` for free blocks
+    pub start: usize,
+    pub end: usize,
+    pub requested: usize, // Bytes asked for before rounding; 0 for free blocks
+    pub data_len: usize,  // Bytes of data stored; 0 for free blocks
+}
+
+impl BlockInfo {
+    pub fn size(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_free(&self) -> bool {
+        self.id.is_none()
+    }
+}
+
+/// Where `MemoryManager::search` found a pattern
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub address: usize,
+    pub id: Option<usize>, // The allocated block the match starts in; `None` in free space
+    pub offset: usize,     // From the start of that block, or of the free range
+}
+
+impl std::fmt::Display for SearchMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.id {
+            Some(id) => write!(f, "0x{:04X}  block {}, offset {}", self.address, id, self.offset),
+            None => write!(f, "0x{:04X}  free, offset {}", self.address, self.offset),
+        }
+    }
+}
+
+/// Arena size, smallest block, placement strategy and header mode of a `MemoryManager`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ManagerConfig {
+    pub arena_size: usize, // A power of two
+    pub min_block: usize,  // A power of two; smaller requests are rounded up to it
+    pub strategy: PlacementStrategy,
+    pub block_headers: bool, // Whether every block starts with a `BlockHeader` in the arena
+}
+
+impl Default for ManagerConfig {
+    fn default() -> Self {
+        ManagerConfig { arena_size: DEFAULT_ARENA_SIZE, min_block: 1, strategy: PlacementStrategy::default(), block_headers: false }
+    }
+}
+
+impl ManagerConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.arena_size.is_power_of_two() {
+            return Err(format!("Arena size {} is not a power of two", self.arena_size));
+        }
+        if !self.min_block.is_power_of_two() {
+            return Err(format!("Minimum block size {} is not a power of two", self.min_block));
+        }
+        if self.min_block > self.arena_size {
+            return Err(format!("Minimum block size {} is larger than the {}-byte arena", self.min_block, self.arena_size));
+        }
+        if self.block_headers && self.arena_size < HEADER_SIZE {
+            return Err(format!("Arena size {} cannot hold a {}-byte block header", self.arena_size, HEADER_SIZE));
+        }
+        Ok(())
+    }
+}
+
 pub struct MemoryManager {
     allocator: BuddyAllocator,
+    min_block: usize,
     buffer: Vec<u8>,
     data_storage: Vec<Vec<u8>>,
     blocks: Vec<MemoryBlock>,
     next_id: usize,
+    sites: HashMap<usize, AllocationSite>, // Call site of each live block, keyed by block ID
+    requested: HashMap<usize, usize>,      // Size each live block was asked for, keyed by block ID
+    capture_backtraces: bool,
+    leak_check: LeakCheck,
+    profiler: Option<HeapProfiler>,
+    recorder: Option<Trace>,
+    timeline: Option<ChromeTrace>,
+    journal: Option<Journal>,
+    headers: bool, // Whether blocks carry a `BlockHeader` at their start
+    verbose: bool,
+}
+
+impl Default for MemoryManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MemoryManager {
     pub fn new() -> Self {
-        let buffer_size = 65536;
+        let memory_manager = Self::with_strategy(PlacementStrategy::FirstFit);
+        println!("Initializing MemoryManager with buffer size {}", memory_manager.buffer.len());
+        memory_manager
+    }
+
+    /// Like `new`, but with the given placement strategy and without the startup log line
+    pub fn with_strategy(strategy: PlacementStrategy) -> Self {
+        Self::build(ManagerConfig { strategy, ..ManagerConfig::default() })
+    }
+
+    /// Like `with_strategy`, with the arena size and minimum block size from `config` as well
+    pub fn with_config(config: ManagerConfig) -> Result<Self, String> {
+        config.validate()?;
+        Ok(Self::build(config))
+    }
+
+    fn build(config: ManagerConfig) -> Self {
+        let buffer_size = config.arena_size;
         let buffer = vec![0u8; buffer_size];
 
         // Initially, the entire buffer is a free block
         let initial_free_block = MemoryBlock::Free(FreeBlock::new(0, buffer_size));
 
-        println!("Initializing MemoryManager with buffer size {}", buffer_size);
 
-        MemoryManager {
-            allocator: BuddyAllocator::new(),
+        let mut memory_manager = MemoryManager {
+            allocator: BuddyAllocator::with_arena(buffer_size, config.strategy),
+            min_block: config.min_block,
             buffer,
             data_storage: Vec::new(),
             blocks: vec![initial_free_block],
             next_id: 0,
+            sites: HashMap::new(),
+            requested: HashMap::new(),
+            capture_backtraces: false,
+            leak_check: LeakCheck::Ignore,
+            profiler: None,
+            recorder: None,
+            timeline: None,
+            journal: None,
+            headers: config.block_headers,
+            verbose: true,
+        };
+        memory_manager.write_free_headers();
+        memory_manager
+    }
+
+    pub fn strategy(&self) -> PlacementStrategy {
+        self.allocator.strategy()
+    }
+
+    pub fn config(&self) -> ManagerConfig {
+        ManagerConfig { arena_size: self.buffer.len(), min_block: self.min_block, strategy: self.strategy(), block_headers: self.headers }
+    }
+
+    // Bytes at the start of each block taken by its header
+    fn header_size(&self) -> usize {
+        if self.headers { HEADER_SIZE } else { 0 }
+    }
+
+    // Writes a header at the start of every free block of the allocator. Headers of blocks
+    // merged into larger ones are left behind in the free space, where the walk skips them.
+    fn write_free_headers(&mut self) {
+        if !self.headers {
+            return;
+        }
+        for (start, end) in self.allocator.free_blocks() {
+            let header = BlockHeader::free((end - start).trailing_zeros() as u8);
+            self.buffer[start..start + HEADER_SIZE].copy_from_slice(&header.encode());
+        }
+    }
+
+    // Writes an allocated block's header, after any change to its data
+    fn write_header(&mut self, id: usize, start: usize, end: usize, data_len: usize) {
+        if self.headers {
+            let header = BlockHeader { order: (end - start).trailing_zeros() as u8, allocated: true, id, data_len };
+            self.buffer[start..start + HEADER_SIZE].copy_from_slice(&header.encode());
         }
     }
 
+    /// Turns the per-operation log lines on stdout on or off
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    /// Turns the allocator's explain mode on or off; see `take_explanation`
+    pub fn set_explain(&mut self, explain: bool) {
+        self.allocator.set_explain(explain);
+    }
+
+    pub fn is_explaining(&self) -> bool {
+        self.allocator.is_explaining()
+    }
+
+    /// How the allocator split and merged blocks since the last call: the order requested,
+    /// the free block chosen, each halving, each buddy check, and the buddy tree after each step
+    pub fn take_explanation(&mut self) -> Vec<String> {
+        self.allocator.take_explanation()
+    }
+
+    /// Starts appending every insert, delete, update and resize, with its result, to a trace
+    pub fn start_recording(&mut self) {
+        self.recorder = Some(Trace::new(self.strategy()));
+    }
+
+    /// Stops recording and returns the trace recorded so far
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.recorder.take()
+    }
+
+    // Trace hook; `id` is the block the operation left behind, if any
+    fn record(&mut self, op: TraceOp, result: Result<Option<usize>, &String>) {
+        if self.recorder.is_none() {
+            return;
+        }
+        let result = match result {
+            Ok(Some(id)) => self.find_allocated(id)
+                .map(|block| Some(Placement { id, offset: block.start })),
+            Ok(None) => Ok(None),
+            Err(e) => Err(e.clone()),
+        };
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.push(op, result);
+        }
+    }
+
+    /// Records a full backtrace, not just the caller location, for every insert and resize
+    pub fn set_capture_backtraces(&mut self, enabled: bool) {
+        self.capture_backtraces = enabled;
+    }
+
+    pub fn captures_backtraces(&self) -> bool {
+        self.capture_backtraces
+    }
+
+    /// Chooses whether live blocks are ignored, printed or treated as a panic when the manager is dropped
+    pub fn set_leak_check(&mut self, leak_check: LeakCheck) {
+        self.leak_check = leak_check;
+    }
+
+    pub fn leak_check(&self) -> LeakCheck {
+        self.leak_check
+    }
+
+    /// Starts recording per-site and per-size-class usage; see `HeapProfiler`
+    pub fn enable_profiling(&mut self) {
+        if self.profiler.is_none() {
+            self.profiler = Some(HeapProfiler::new());
+        }
+    }
+
+    pub fn profiler(&self) -> Option<&HeapProfiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Stops profiling and hands back everything recorded so far
+    pub fn take_profiler(&mut self) -> Option<HeapProfiler> {
+        self.profiler.take()
+    }
+
+    /// Starts recording block lifetimes and usage counters for a trace viewer; see `ChromeTrace`
+    pub fn enable_chrome_trace(&mut self) {
+        if self.timeline.is_none() {
+            self.timeline = Some(ChromeTrace::new());
+        }
+    }
+
+    /// Stops recording block lifetimes and hands back the trace recorded so far
+    pub fn take_chrome_trace(&mut self) -> Option<ChromeTrace> {
+        self.timeline.take()
+    }
+
+    // Chrome trace hook, called once per public operation with its error, if it failed
+    fn trace_timeline<T>(&mut self, operation: &str, result: &Result<T, String>) {
+        if let Some(timeline) = self.timeline.as_mut() {
+            timeline.record(operation, &self.blocks, self.allocator.largest_free_block(), result.as_ref().err().map(String::as_str));
+        }
+    }
+
+    // Profiler hooks, called by the public operations only so nested calls are not counted twice
+    fn profile_allocation(&mut self, id: usize) {
+        if self.profiler.is_none() {
+            return;
+        }
+        let size = self.find_allocated(id).map(|block| block.size()).unwrap_or(0);
+        let site = self.sites.get(&id).map(|site| site.location.to_string()).unwrap_or_default();
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record_allocation(id, &site, size);
+        }
+    }
+
+    fn profile_free(&mut self, id: usize) {
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record_free(id);
+        }
+    }
+
+    fn profile_operation(&mut self, operation: &'static str) {
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record_operation(operation);
+        }
+    }
+
+    /// Every block, free and allocated, in address order
+    pub fn blocks(&self) -> &[MemoryBlock] {
+        &self.blocks
+    }
+
+    fn block_info(&self, block: &MemoryBlock) -> BlockInfo {
+        match block {
+            MemoryBlock::Free(free_block) => BlockInfo { id: None, start: free_block.start, end: free_block.end, requested: 0, data_len: 0 },
+            MemoryBlock::Allocated(allocated_block) => BlockInfo {
+                id: Some(allocated_block.id),
+                start: allocated_block.start,
+                end: allocated_block.end,
+                requested: self.requested.get(&allocated_block.id).copied().unwrap_or(allocated_block.size()),
+                data_len: self.get_data(allocated_block).len(),
+            },
+        }
+    }
+
+    /// Every block, free and allocated, in address order
+    pub fn iter_all(&self) -> impl Iterator<Item = BlockInfo> + '_ {
+        self.blocks.iter().map(|block| self.block_info(block))
+    }
+
+    /// Allocated blocks in address order
+    pub fn iter_allocated(&self) -> impl Iterator<Item = BlockInfo> + '_ {
+        self.iter_all().filter(|block| !block.is_free())
+    }
+
+    /// Free ranges in address order. Adjacent free buddies are shown as one range; the
+    /// allocator's own lists are in `allocator().free_lists()`.
+    pub fn iter_free(&self) -> impl Iterator<Item = BlockInfo> + '_ {
+        self.iter_all().filter(BlockInfo::is_free)
+    }
+
+    /// Allocated blocks from largest to smallest, ties in address order
+    pub fn iter_by_size(&self) -> impl Iterator<Item = BlockInfo> {
+        let mut blocks: Vec<BlockInfo> = self.iter_allocated().collect();
+        blocks.sort_by_key(|block| std::cmp::Reverse(block.size()));
+        blocks.into_iter()
+    }
+
+    /// The free or allocated block containing `address`, and how far into it the address is.
+    /// A binary search of the block list, which is kept in address order.
+    pub fn block_at(&self, address: usize) -> Option<(&MemoryBlock, usize)> {
+        let index = self.blocks.partition_point(|block| block.start() <= address).checked_sub(1)?;
+        let block = &self.blocks[index];
+        (address < block.end()).then(|| (block, address - block.start()))
+    }
+
+    /// The blocks overlapping `[start, end)`, in address order
+    pub fn blocks_in_range(&self, start: usize, end: usize) -> &[MemoryBlock] {
+        let first = self.blocks.partition_point(|block| block.end() <= start);
+        let last = self.blocks.partition_point(|block| block.start() < end);
+        &self.blocks[first..last.max(first)]
+    }
+
+    pub fn allocator(&self) -> &BuddyAllocator {
+        &self.allocator
+    }
+
     pub fn get_buffer(&self) -> &[u8] {
         &self.buffer
     }
@@ -63,34 +464,67 @@ impl MemoryManager {
         &self.buffer[start..end]
     }
 
-    fn round_up_to_power_of_two(size: usize) -> usize {
-        let mut power = 1;
-        while power < size {
-            power <<= 1;
-        }
-        power
+    // The block for `size` bytes of data and its header, or an error if no block that large fits the arena
+    fn block_size_for(&self, size: usize) -> Result<usize, String> {
+        let size = size.checked_add(self.header_size()).ok_or_else(|| "Insufficient memory".to_string())?;
+        self.round_up_to_power_of_two(size)
+    }
+
+    // The smallest block that holds `size` bytes, or an error if no block that large fits the arena
+    fn round_up_to_power_of_two(&self, size: usize) -> Result<usize, String> {
+        size.checked_next_power_of_two()
+            .map(|power| power.max(self.min_block))
+            .filter(|&power| power <= self.buffer.len())
+            .ok_or_else(|| "Insufficient memory".to_string())
     }
 
+    #[track_caller]
     pub fn insert(&mut self, size: usize, data: Vec<u8>) -> Result<usize, String> {
-        let rounded_size = Self::round_up_to_power_of_two(size);
-        println!("Attempting to insert block of size {} (rounded to {}) with data {:?}", size, rounded_size, data);
+        self.write_ahead(|| JournalRecord::Insert { size, data: data.clone() })?;
+        let site = AllocationSite::capture(self.capture_backtraces);
+        let data_len = data.len();
+        let result = self.insert_block(size, data, site);
+        if let Ok(id) = result {
+            self.profile_allocation(id);
+        }
+        self.profile_operation("insert");
+        self.trace_timeline("insert", &result);
+        self.record(TraceOp::Insert { size, data_len }, result.as_ref().map(|&id| Some(id)));
+        self.checkpoint_if_due();
+        result
+    }
+
+    fn insert_block(&mut self, size: usize, data: Vec<u8>, site: AllocationSite) -> Result<usize, String> {
+        let rounded_size = self.block_size_for(size)?;
+        log!(self, "Attempting to insert block of size {} (rounded to {}) with data {:?}", size, rounded_size, data);
+        if data.len() > rounded_size - self.header_size() {
+            return Err(format!("Data of {} bytes does not fit in a {}-byte block", data.len(), rounded_size));
+        }
         
         // Use buddy allocator to find a suitable block
         let start = self.allocator.allocate(rounded_size).map_err(|e| e.to_string())?;
-        let end = start + rounded_size;
-    
-        // Create allocated block
+        self.add_block(start, size, rounded_size, data, site)
+    }
+
+    // Creates an allocated block with the next ID in a range the allocator already handed out
+    fn add_block(&mut self, start: usize, requested: usize, rounded_size: usize, data: Vec<u8>, site: AllocationSite) -> Result<usize, String> {
         let id = self.next_id;
-        let padded_data = {
-            let mut padded_data = vec![0u8; rounded_size];
-            let data_len = data.len();
-            padded_data[..data_len].copy_from_slice(&data); // Copy original data
-            padded_data
-        };
-    
-        // Store data and get the index
+        self.place_block(id, start, rounded_size, data)?;
+        self.sites.insert(id, site);
+        self.requested.insert(id, requested);
+
+        self.next_id += 1;
+        Ok(id)
+    }
+
+    // Turns the range [start, start + rounded_size) handed out by the allocator into an
+    // allocated block with the given ID, splitting the free block that contains it.
+    fn place_block(&mut self, id: usize, start: usize, rounded_size: usize, data: Vec<u8>) -> Result<(), String> {
+        let end = start + rounded_size;
+
+        // Store data and get the index; the arena holds it zero-padded to the block size
         let data_index = self.data_storage.len();
-        self.data_storage.push(padded_data); // Store the padded data
+        self.data_storage.push(data);
     
         let allocated_block = MemoryBlock::Allocated(AllocatedBlock::new(id, start, end, data_index));
     
@@ -108,33 +542,51 @@ impl MemoryManager {
         // Add allocated block
         self.blocks.push(allocated_block);
     
-        // Handle remaining free space
+        // Handle remaining free space on either side of the allocation
         match original_block {
             MemoryBlock::Free(free_block) => {
-                let remaining_size = free_block.size() - rounded_size;
-    
-                // Split the free block if there is remaining space
-                if remaining_size > 0 {
-                    let (_allocated_part, remaining_part) = free_block.split(rounded_size); // Prefix with underscore
-                    self.blocks.push(MemoryBlock::Free(remaining_part));
+                if free_block.start < start {
+                    self.blocks.push(MemoryBlock::Free(FreeBlock::new(free_block.start, start)));
+                }
+                if end < free_block.end {
+                    self.blocks.push(MemoryBlock::Free(FreeBlock::new(end, free_block.end)));
                 }
             }
             _ => unreachable!(),
         }
     
-        // Copy data to buffer
-        self.buffer[start..end].copy_from_slice(&self.data_storage[data_index]);
+        // Copy data to buffer, after the header if there is one
+        let payload = start + self.header_size();
+        let data = &self.data_storage[data_index];
+        self.buffer[payload..payload + data.len()].copy_from_slice(data);
+        self.buffer[payload + data.len()..end].fill(0);
+        let data_len = data.len();
+        self.write_header(id, start, end, data_len);
+        self.write_free_headers();
     
         // Sort blocks by start address
         self.blocks.sort_by_key(|block| block.start());
-    
-        self.next_id += 1;
-        Ok(id)
+        Ok(())
     }
 
     pub fn delete(&mut self, id: usize) -> Result<(), String> {
-        println!("Attempting to delete block ID {}", id);
-        
+        self.write_ahead(|| JournalRecord::Delete { id })?;
+        log!(self, "Attempting to delete block ID {}", id);
+        let result = self.release_block(id);
+        if result.is_ok() {
+            self.sites.remove(&id);
+            self.requested.remove(&id);
+            self.profile_free(id);
+        }
+        self.profile_operation("delete");
+        self.trace_timeline("delete", &result);
+        self.record(TraceOp::Delete { id }, result.as_ref().map(|_| None));
+        self.checkpoint_if_due();
+        result
+    }
+
+    // Returns the block's range to the allocator and the free list without touching its call site
+    fn release_block(&mut self, id: usize) -> Result<(), String> {
         // Find the block by ID
         let block_index = self.blocks.iter()
             .position(|block| match block {
@@ -179,6 +631,7 @@ impl MemoryManager {
 
         // Merge adjacent free blocks
         self.merge_free_blocks();
+        self.write_free_headers();
 
         // Sort blocks by start address
         self.blocks.sort_by_key(|block| block.start());
@@ -187,6 +640,9 @@ impl MemoryManager {
     }
 
     fn merge_free_blocks(&mut self) {
+        // Only blocks that follow each other in the list are compared, so put them in address order first
+        self.blocks.sort_by_key(|block| block.start());
+
         let mut merged = false;
         while !merged {
             merged = true;
@@ -215,8 +671,23 @@ impl MemoryManager {
         }
     }
 
-    pub fn update(&mut self, id: usize, data: Vec<u8>) -> Result<(), String> {
-        println!("Attempting to update block ID {} with data {:?}", id, data);
+    /// Replaces a block's data. Data that no longer fits moves the block to a new range under a
+    /// new ID; the returned ID is the one the block has afterwards.
+    #[track_caller]
+    pub fn update(&mut self, id: usize, data: Vec<u8>) -> Result<usize, String> {
+        self.write_ahead(|| JournalRecord::Update { id, data: data.clone() })?;
+        let data_len = data.len();
+        let result = self.update_block(id, data);
+        self.profile_operation("update");
+        self.trace_timeline("update", &result);
+        self.record(TraceOp::Update { id, data_len }, result.as_ref().map(|&id| Some(id)));
+        self.checkpoint_if_due();
+        result
+    }
+
+    #[track_caller]
+    fn update_block(&mut self, id: usize, data: Vec<u8>) -> Result<usize, String> {
+        log!(self, "Attempting to update block ID {} with data {:?}", id, data);
         
         // Find the block by ID
         let block_index = self.blocks.iter()
@@ -233,15 +704,27 @@ impl MemoryManager {
         };
 
         // Check if new data fits in existing block
-        if data.len() > current_end - current_start {
-            // Need to reallocate
-            self.delete(id)?;
+        if data.len() > current_end - current_start - self.header_size() {
+            // Need to reallocate. Reserve the new range before releasing the old one, like
+            // resize, so a failed update leaves the block where it was.
+            let site = AllocationSite::capture(self.capture_backtraces);
+            let rounded_size = self.block_size_for(data.len())?;
+            let start = self.allocator.allocate(rounded_size).map_err(|e| e.to_string())?;
+
+            self.release_block(id)?;
+            self.sites.remove(&id);
+            self.requested.remove(&id);
+            self.profile_free(id);
 
             // Insert new data with the correct size
-            self.insert(data.len(), data)?;
+            let new_id = self.add_block(start, data.len(), rounded_size, data, site)?;
+            self.profile_allocation(new_id);
+            return Ok(new_id);
         } else {
             // Update buffer in-place
-            self.buffer[current_start..current_start + data.len()].copy_from_slice(&data);
+            let payload = current_start + self.header_size();
+            self.buffer[payload..payload + data.len()].copy_from_slice(&data);
+            self.write_header(id, current_start, current_end, data.len());
 
             // Update data storage using the data index from the block
             let data_index = match &self.blocks[block_index] {
@@ -252,11 +735,50 @@ impl MemoryManager {
             self.data_storage[data_index] = data; // Update with new data
         }
 
+        Ok(id)
+    }
+
+    /// Moves a block to a range of `new_size` bytes, keeping its ID and as much of its data as fits.
+    /// If no such range is free, the block is left where it was.
+    #[track_caller]
+    pub fn resize(&mut self, id: usize, new_size: usize) -> Result<(), String> {
+        self.write_ahead(|| JournalRecord::Resize { id, new_size })?;
+        let result = self.resize_block(id, new_size);
+        self.profile_operation("resize");
+        self.trace_timeline("resize", &result);
+        self.record(TraceOp::Resize { id, new_size }, result.as_ref().map(|_| Some(id)));
+        self.checkpoint_if_due();
+        result
+    }
+
+    #[track_caller]
+    fn resize_block(&mut self, id: usize, new_size: usize) -> Result<(), String> {
+        let site = AllocationSite::capture(self.capture_backtraces);
+        log!(self, "Attempting to resize block ID {} to {} bytes", id, new_size);
+
+        let block = self.find_allocated(id)?.clone();
+        let rounded_size = self.block_size_for(new_size)?;
+
+        if rounded_size != block.size() {
+            // Reserve the new range before releasing the old one, like realloc
+            let start = self.allocator.allocate(rounded_size).map_err(|e| e.to_string())?;
+
+            let mut data = self.data_storage[block.data_index].clone();
+            data.truncate(new_size);
+
+            self.release_block(id)?;
+            self.profile_free(id);
+            self.place_block(id, start, rounded_size, data)?;
+            self.sites.insert(id, site);
+            self.profile_allocation(id);
+        } else {
+            self.sites.insert(id, site);
+        }
+        self.requested.insert(id, new_size);
         Ok(())
     }
 
-    pub fn find(&self, id: usize) -> Result<&AllocatedBlock, String> {
-        println!("Attempting to find block ID {}", id);
+    fn find_allocated(&self, id: usize) -> Result<&AllocatedBlock, String> {
         self.blocks.iter()
             .find_map(|block| match block {
                 MemoryBlock::Allocated(allocated_block) if allocated_block.id == id => Some(allocated_block),
@@ -265,30 +787,538 @@ impl MemoryManager {
             .ok_or("Block not found".to_string())
     }
 
+    pub fn find(&self, id: usize) -> Result<&AllocatedBlock, String> {
+        log!(self, "Attempting to find block ID {}", id);
+        self.find_allocated(id)
+    }
+
+    pub fn stats(&self) -> MemoryStats {
+        let mut used_bytes = 0;
+        let mut allocated_blocks = 0;
+        for block in &self.blocks {
+            if let MemoryBlock::Allocated(allocated_block) = block {
+                used_bytes += allocated_block.size();
+                allocated_blocks += 1;
+            }
+        }
+        MemoryStats {
+            total_bytes: self.buffer.len(),
+            used_bytes,
+            free_bytes: self.buffer.len() - used_bytes,
+            largest_free: self.allocator.largest_free_block(),
+            allocated_blocks,
+            free_blocks: self.allocator.free_blocks().len(),
+        }
+    }
+
     pub fn get_data(&self, block: &AllocatedBlock) -> &[u8] {
         let data_index = block.data_index; // Accessing the data index directly from the block
         &self.data_storage[data_index]
     }
 
     pub fn dump(&self) {
-        println!("Memory Manager Dump:");
-        
+        print!("{}", self.format_dump());
+    }
+
+    /// The text `dump` prints: every free and allocated block in address order
+    pub fn format_dump(&self) -> String {
+        let mut text = String::from("Memory Manager Dump:\n");
         for (i, block) in self.blocks.iter().enumerate() {
             match block {
                 MemoryBlock::Free(free_block) => {
-                    println!(
-                        "Block {}: Free Block, Start: 0x{:04X}, End: 0x{:04X}, Size: {}",
+                    text.push_str(&format!(
+                        "Block {}: Free Block, Start: 0x{:04X}, End: 0x{:04X}, Size: {}\n",
                         i, free_block.start, free_block.end, free_block.end - free_block.start
-                    );
+                    ));
                 }
                 MemoryBlock::Allocated(allocated_block) => {
                     let data = self.get_data(allocated_block);
-                    println!(
-                        "Block {}: Allocated Block ID: {}, Start: 0x{:04X}, End: 0x{:04X}, Size: {}, Data: {:?}",
+                    text.push_str(&format!(
+                        "Block {}: Allocated Block ID: {}, Start: 0x{:04X}, End: 0x{:04X}, Size: {}, Data: {:?}\n",
                         i, allocated_block.id, allocated_block.start, allocated_block.end, allocated_block.end - allocated_block.start, data
-                    );
+                    ));
+                }
+            }
+        }
+        text
+    }
+
+    /// The arena drawn as a bar of `width` cells with an address ruler underneath. Each cell
+    /// shows what its bytes hold: `#` allocated, `-` free but split off a larger block, `.`
+    /// free and never split, `%` partly allocated. Block IDs are written over their cells
+    /// where they fit.
+    pub fn render_map(&self, width: usize) -> String {
+        let arena = self.buffer.len();
+        let width = width.clamp(1, arena.max(1));
+        let cell_of = |address: usize| address * width / arena;
+        let address_of = |cell: usize| cell * arena / width;
+
+        // What each cell contains, as bits: 1 allocated, 2 split free, 4 unsplit free
+        let mut contents = vec![0u8; width];
+        let mut mark = |start: usize, end: usize, bit: u8| {
+            for cell in &mut contents[cell_of(start)..=cell_of(end - 1)] {
+                *cell |= bit;
+            }
+        };
+        let top_level = self.allocator.top_level_blocks();
+        for (start, end) in self.allocator.free_blocks() {
+            mark(start, end, if top_level.contains(&(start, end)) { 4 } else { 2 });
+        }
+        for block in &self.blocks {
+            if let MemoryBlock::Allocated(allocated_block) = block {
+                mark(allocated_block.start, allocated_block.end, 1);
+            }
+        }
+        let mut bar: Vec<char> = contents
+            .iter()
+            .map(|&bits| match bits {
+                1 => '#',
+                0 | 4 => '.',
+                _ if bits & 1 != 0 => '%',
+                _ => '-',
+            })
+            .collect();
+
+        // Label a block on the cells it covers completely
+        for block in &self.blocks {
+            if let MemoryBlock::Allocated(allocated_block) = block {
+                let first = cell_of(allocated_block.start);
+                let first = if address_of(first) < allocated_block.start { first + 1 } else { first };
+                let last = cell_of(allocated_block.end); // Exclusive
+                let label: Vec<char> = allocated_block.id.to_string().chars().collect();
+                if last >= first + label.len() {
+                    bar[first..first + label.len()].copy_from_slice(&label);
                 }
             }
         }
+
+        // A tick wherever a label fits, plus one at the end of the arena
+        let label_width = format!("0x{:04X}", arena).len() + 1;
+        let step = label_width.next_power_of_two();
+        let (mut ticks, mut labels) = (String::new(), String::new());
+        for cell in (0..width).step_by(step).chain([width]) {
+            if cell > 0 && labels.chars().count() >= cell {
+                continue;
+            }
+            ticks.push_str(&" ".repeat(cell - ticks.chars().count()));
+            ticks.push('|');
+            labels.push_str(&" ".repeat(cell - labels.chars().count()));
+            labels.push_str(&format!("0x{:04X}", address_of(cell)));
+        }
+
+        let cell_size = match (arena % width, arena / width) {
+            (0, 1) => "1 byte".to_string(),
+            (0, bytes) => format!("{} bytes", bytes),
+            (_, bytes) => format!("about {} bytes", bytes),
+        };
+        format!(
+            "{}\n{}\n{}\n# allocated  - free (split)  . free  % partly allocated; each cell is {}\n",
+            bar.iter().collect::<String>(),
+            ticks,
+            labels,
+            cell_size
+        )
+    }
+
+    /// The arena bytes in `[start, end)` in `xxd` style, 16 to a line, with a header line
+    /// where each block (or free range) begins. Runs of identical lines are shown as `*`.
+    pub fn hexdump(&self, start: usize, end: usize) -> Result<String, String> {
+        if start >= end || end > self.buffer.len() {
+            return Err(format!("Invalid range 0x{:04X} - 0x{:04X} (the arena is {} bytes)", start, end, self.buffer.len()));
+        }
+        let mut out = String::new();
+        for block in self.blocks_in_range(start, end) {
+            match block {
+                MemoryBlock::Allocated(allocated_block) => out.push_str(&format!(
+                    "-- block {} at 0x{:04X} - 0x{:04X} ({} bytes)\n",
+                    allocated_block.id,
+                    block.start(),
+                    block.end(),
+                    block.size()
+                )),
+                MemoryBlock::Free(_) => out.push_str(&format!("-- free at 0x{:04X} - 0x{:04X} ({} bytes)\n", block.start(), block.end(), block.size())),
+            }
+
+            // Lines never cross into the next block
+            let (from, to) = (block.start().max(start), block.end().min(end));
+            let line = |address: usize| (address, &self.buffer[address..(address + 16).min(to)]);
+            let mut previous: Option<&[u8]> = None;
+            let mut skipping = false;
+            for (address, bytes) in (from..to).step_by(16).map(line) {
+                if previous == Some(bytes) {
+                    if !skipping {
+                        out.push_str("*\n");
+                        skipping = true;
+                    }
+                    continue;
+                }
+                skipping = false;
+                previous = Some(bytes);
+                out.push_str(&hex_line(address, bytes));
+            }
+            // Show where a skipped run ends
+            if skipping {
+                let (address, bytes) = line(from + (to - from - 1) / 16 * 16);
+                out.push_str(&hex_line(address, bytes));
+            }
+        }
+        Ok(out)
+    }
+
+    /// `hexdump` of one allocated block, including the padding after its data
+    pub fn hexdump_block(&self, id: usize) -> Result<String, String> {
+        let block = self.find_allocated(id)?;
+        self.hexdump(block.start, block.end)
+    }
+
+    /// Every address where `pattern` occurs, in the whole arena (free space included, which
+    /// may still hold the data of freed blocks) or, with `live_only`, entirely inside an
+    /// allocated block
+    pub fn search(&self, pattern: &[u8], live_only: bool) -> Vec<SearchMatch> {
+        if pattern.is_empty() {
+            return Vec::new();
+        }
+        let in_block = |block: &MemoryBlock, address: usize| SearchMatch {
+            address,
+            id: match block {
+                MemoryBlock::Allocated(allocated_block) => Some(allocated_block.id),
+                MemoryBlock::Free(_) => None,
+            },
+            offset: address - block.start(),
+        };
+        if live_only {
+            return self
+                .blocks
+                .iter()
+                .filter(|block| matches!(block, MemoryBlock::Allocated(_)))
+                .flat_map(|block| {
+                    let bytes = &self.buffer[block.start()..block.end()];
+                    bytes.windows(pattern.len()).enumerate().filter(|(_, window)| *window == pattern).map(move |(i, _)| in_block(block, block.start() + i))
+                })
+                .collect();
+        }
+        self.buffer
+            .windows(pattern.len())
+            .enumerate()
+            .filter(|(_, window)| *window == pattern)
+            .filter_map(|(address, _)| self.block_at(address).map(|(block, _)| in_block(block, address)))
+            .collect()
+    }
+
+    /// The allocator's buddy tree as a Graphviz digraph, with allocated blocks labelled by ID
+    pub fn to_dot(&self) -> String {
+        let ids: HashMap<usize, usize> = self
+            .blocks
+            .iter()
+            .filter_map(|block| match block {
+                MemoryBlock::Allocated(allocated_block) => Some((allocated_block.start, allocated_block.id)),
+                MemoryBlock::Free(_) => None,
+            })
+            .collect();
+        self.allocator.to_dot_labelled(|start| ids.get(&start).map(|id| format!("block {}", id)))
     }
-}
\ No newline at end of file
+
+    /// One block as a JSON object, with its data if it is allocated
+    pub fn block_json(&self, block: &MemoryBlock) -> Json {
+        match block {
+            MemoryBlock::Free(free_block) => Json::object([
+                ("type", "free".into()),
+                ("start", free_block.start.into()),
+                ("end", free_block.end.into()),
+                ("size", free_block.size().into()),
+            ]),
+            MemoryBlock::Allocated(allocated_block) => {
+                let mut json = Json::object([
+                    ("type", "allocated".into()),
+                    ("id", allocated_block.id.into()),
+                    ("start", allocated_block.start.into()),
+                    ("end", allocated_block.end.into()),
+                    ("size", allocated_block.size().into()),
+                ]);
+                for (key, value) in json::bytes_fields(self.get_data(allocated_block)) {
+                    json.push(key, value);
+                }
+                json
+            }
+        }
+    }
+
+    /// Every block in address order, as a JSON array
+    pub fn blocks_json(&self) -> Json {
+        Json::Array(self.blocks.iter().map(|block| self.block_json(block)).collect())
+    }
+
+    /// The whole state of the manager, ready to be written out with `MemoryImage::encode`
+    pub fn to_image(&self) -> MemoryImage {
+        MemoryImage {
+            config: self.config(),
+            next_id: self.next_id,
+            free: self.allocator.free_blocks(),
+            allocated: self.allocator.allocated_blocks(),
+            blocks: self
+                .blocks
+                .iter()
+                .map(|block| {
+                    let info = self.block_info(block);
+                    let data = match block {
+                        MemoryBlock::Allocated(allocated_block) => self.get_data(allocated_block).to_vec(),
+                        MemoryBlock::Free(_) => Vec::new(),
+                    };
+                    ImageBlock { id: info.id, start: info.start, end: info.end, requested: info.requested, data }
+                })
+                .collect(),
+            arena: self.buffer.clone(),
+        }
+    }
+
+    /// A manager in the state an image describes, after checking that its blocks, the
+    /// allocator's blocks and the arena agree. Call sites of the blocks are not kept.
+    pub fn from_image(image: MemoryImage) -> Result<MemoryManager, String> {
+        let config = image.config;
+        config.validate()?;
+        if image.arena.len() != config.arena_size {
+            return Err(format!("Arena is {} bytes but the configuration says {}", image.arena.len(), config.arena_size));
+        }
+        let allocator = BuddyAllocator::from_blocks(config.arena_size, config.strategy, &image.free, &image.allocated)?;
+
+        let mut memory_manager = Self::build(config);
+        memory_manager.allocator = allocator;
+        memory_manager.buffer = image.arena;
+        memory_manager.next_id = image.next_id;
+        memory_manager.blocks.clear();
+
+        let mut expected = 0;
+        let mut allocated = Vec::new();
+        for block in image.blocks {
+            if block.start != expected || block.end <= block.start {
+                return Err(format!("Block list has a gap or overlap at 0x{:04X}", block.start.min(expected)));
+            }
+            if block.end > config.arena_size {
+                return Err(format!("Block at 0x{:04X} ends at 0x{:04X}, past the {}-byte arena", block.start, block.end, config.arena_size));
+            }
+            expected = block.end;
+            let Some(id) = block.id else {
+                if matches!(memory_manager.blocks.last(), Some(MemoryBlock::Free(_))) {
+                    return Err(format!("Free blocks at 0x{:04X} were not merged", block.start));
+                }
+                memory_manager.blocks.push(MemoryBlock::Free(FreeBlock::new(block.start, block.end)));
+                continue;
+            };
+            if id >= image.next_id || memory_manager.requested.contains_key(&id) {
+                return Err(format!("Block ID {} is repeated or not below the next ID {}", id, image.next_id));
+            }
+            let payload = block.start + memory_manager.header_size();
+            if block.data.len() + memory_manager.header_size() > block.end - block.start || memory_manager.buffer[payload..payload + block.data.len()] != block.data[..] {
+                return Err(format!("Data of block {} does not match the arena", id));
+            }
+            allocated.push((block.start, block.end));
+            memory_manager.requested.insert(id, block.requested);
+            memory_manager.blocks.push(MemoryBlock::Allocated(AllocatedBlock::new(id, block.start, block.end, memory_manager.data_storage.len())));
+            memory_manager.data_storage.push(block.data);
+        }
+        if expected != config.arena_size {
+            return Err(format!("Block list ends at 0x{:04X}, short of the {}-byte arena", expected, config.arena_size));
+        }
+        if allocated != memory_manager.allocator.allocated_blocks() {
+            return Err("Allocated blocks do not match the allocator's".to_string());
+        }
+        Ok(memory_manager)
+    }
+
+    /// Rebuilds a manager with block headers from its arena alone, by walking the headers.
+    /// The minimum block size and strategy are not in the arena, so the defaults are used,
+    /// and each block's requested size is taken to be its data length.
+    pub fn recover_from_buffer(buffer: &[u8]) -> Result<MemoryManager, String> {
+        Self::recover_from_buffer_with_config(buffer, ManagerConfig { arena_size: buffer.len(), block_headers: true, ..ManagerConfig::default() })
+    }
+
+    /// Like `recover_from_buffer`, with the minimum block size and strategy from `config`
+    pub fn recover_from_buffer_with_config(buffer: &[u8], config: ManagerConfig) -> Result<MemoryManager, String> {
+        if !config.block_headers || config.arena_size != buffer.len() {
+            return Err(format!("Recovery needs block headers and a {}-byte arena", buffer.len()));
+        }
+        let mut image = MemoryImage { config, next_id: 0, free: Vec::new(), allocated: Vec::new(), blocks: Vec::new(), arena: buffer.to_vec() };
+        for (start, header) in block_header::walk(buffer)? {
+            let end = start + header.size();
+            if !header.allocated {
+                image.free.push((start, end));
+                // The manager keeps adjacent free blocks as one range
+                match image.blocks.last_mut() {
+                    Some(last) if last.id.is_none() => last.end = end,
+                    _ => image.blocks.push(ImageBlock { id: None, start, end, requested: 0, data: Vec::new() }),
+                }
+                continue;
+            }
+            let data = buffer[start + HEADER_SIZE..start + HEADER_SIZE + header.data_len].to_vec();
+            image.allocated.push((start, end));
+            image.blocks.push(ImageBlock { id: Some(header.id), start, end, requested: header.data_len, data });
+            let next_id = header.id.checked_add(1).ok_or_else(|| format!("0x{:04X}: block ID {} leaves no ID to carry on from", start, header.id))?;
+            image.next_id = image.next_id.max(next_id);
+        }
+        Self::from_image(image)
+    }
+
+    /// Writes the state of the manager to a binary memory image; see `MemoryImage`
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_image().encode()).map_err(|e| format!("Failed to write image {}: {}", path, e))
+    }
+
+    /// Reads a manager back from an image written by `save`
+    pub fn load(path: &str) -> Result<MemoryManager, String> {
+        let bytes = fs::read(path).map_err(|e| format!("Failed to read image {}: {}", path, e))?;
+        MemoryImage::decode(&bytes).and_then(Self::from_image).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Replaces the blocks, arena and configuration with those of an image, keeping the
+    /// logging, explain and leak check settings. The loaded blocks are taken to be allocated
+    /// here. A trace, profile or Chrome trace cannot follow a load, so it is refused while
+    /// any of them is running.
+    #[track_caller]
+    pub fn load_from(&mut self, path: &str) -> Result<(), String> {
+        if self.recorder.is_some() || self.profiler.is_some() || self.timeline.is_some() {
+            return Err("Cannot load an image while recording, profiling or tracing".to_string());
+        }
+        let loaded = Self::load(path)?;
+        self.replace_state(loaded)?;
+        for id in self.requested.keys() {
+            self.sites.insert(*id, AllocationSite::capture(self.capture_backtraces));
+        }
+        Ok(())
+    }
+
+    /// Frees everything and starts again from an empty arena with IDs from 0, keeping the
+    /// configuration, the settings and the journal. Recording, profiling and the Chrome
+    /// trace carry on, seeing every live block freed.
+    pub fn reset(&mut self) -> Result<(), String> {
+        let live: Vec<usize> = self.requested.keys().copied().collect();
+        let result = self.replace_state(Self::build(self.config()));
+        for id in live {
+            self.profile_free(id);
+        }
+        self.profile_operation("reset");
+        self.trace_timeline("reset", &result);
+        self.record(TraceOp::Reset, result.as_ref().map(|_| None));
+        result
+    }
+
+    // Takes the blocks, arena and configuration of `other`, keeping our settings
+    fn replace_state(&mut self, mut other: MemoryManager) -> Result<(), String> {
+        other.allocator.set_explain(self.allocator.is_explaining());
+        self.allocator = std::mem::take(&mut other.allocator);
+        self.min_block = other.min_block;
+        self.headers = other.headers;
+        self.buffer = std::mem::take(&mut other.buffer);
+        self.data_storage = std::mem::take(&mut other.data_storage);
+        self.blocks = std::mem::take(&mut other.blocks);
+        self.next_id = other.next_id;
+        self.sites.clear();
+        self.requested = std::mem::take(&mut other.requested);
+        // The journal only replays onto its checkpoint, so the new state needs a new one
+        if self.journal.is_some() {
+            self.checkpoint()?;
+        }
+        Ok(())
+    }
+
+    /// A manager whose operations are journaled in `dir` before they are applied, picking up
+    /// the checkpoint and journal already there, or starting from `config` in a new directory.
+    /// The configuration of an existing journal wins over `config`.
+    pub fn open_durable(dir: &str, config: ManagerConfig, options: JournalOptions) -> Result<MemoryManager, String> {
+        let dir = Path::new(dir);
+        if !Journal::exists(dir) {
+            let mut memory_manager = Self::with_config(config)?;
+            memory_manager.journal = Some(Journal::create(dir, &memory_manager.to_image(), options)?);
+            return Ok(memory_manager);
+        }
+
+        let (image, records, journal) = Journal::open(dir, options)?;
+        let mut memory_manager = Self::from_image(image).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        memory_manager.set_verbose(false);
+        // Failed operations were journaled too, and fail again the same way
+        for record in records {
+            let _ = match record {
+                JournalRecord::Insert { size, data } => memory_manager.insert(size, data).map(|_| ()),
+                JournalRecord::Delete { id } => memory_manager.delete(id),
+                JournalRecord::Update { id, data } => memory_manager.update(id, data).map(|_| ()),
+                JournalRecord::Resize { id, new_size } => memory_manager.resize(id, new_size),
+            };
+        }
+        memory_manager.set_verbose(true);
+        memory_manager.journal = Some(journal);
+        memory_manager.checkpoint_if_due();
+        Ok(memory_manager)
+    }
+
+    /// Writes the whole state as the journal's checkpoint and empties its log. Does nothing
+    /// without a journal.
+    pub fn checkpoint(&mut self) -> Result<(), String> {
+        let image = self.to_image();
+        match self.journal.as_mut() {
+            Some(journal) => journal.checkpoint(&image),
+            None => Ok(()),
+        }
+    }
+
+    // Journals an operation before it is applied. If the record cannot be written, the
+    // operation fails without changing anything.
+    fn write_ahead(&mut self, record: impl FnOnce() -> JournalRecord) -> Result<(), String> {
+        match self.journal.as_mut() {
+            Some(journal) => journal.append(&record()),
+            None => Ok(()),
+        }
+    }
+
+    // A failed checkpoint leaves the log in place, to be tried again after the next operation
+    fn checkpoint_if_due(&mut self) {
+        if self.journal.as_ref().is_some_and(Journal::checkpoint_due) {
+            let _ = self.checkpoint();
+        }
+    }
+
+    /// Lists every live block grouped by the call site that allocated it
+    pub fn leak_report(&self) -> LeakReport {
+        LeakReport::from_blocks(self.blocks.iter().filter_map(|block| match block {
+            MemoryBlock::Allocated(allocated_block) => Some((
+                allocated_block.id,
+                allocated_block.size(),
+                self.sites.get(&allocated_block.id),
+            )),
+            _ => None,
+        }))
+    }
+}
+
+impl std::fmt::Debug for MemoryManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("MemoryManager")
+            .field("config", &self.config())
+            .field("live_blocks", &self.requested.len())
+            .field("next_id", &self.next_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for MemoryManager {
+    fn drop(&mut self) {
+        if self.leak_check == LeakCheck::Ignore {
+            return;
+        }
+        let report = self.leak_report();
+        if report.is_empty() {
+            return;
+        }
+        match self.leak_check {
+            LeakCheck::Print => eprintln!("MemoryManager dropped with leaks: {}", report),
+            // Don't turn an unrelated panic into an abort
+            LeakCheck::Panic if !std::thread::panicking() => panic!("MemoryManager dropped with leaks: {}", report),
+            _ => {}
+        }
+    }
+}
+
+// One `xxd`-style line: address, up to 16 bytes in pairs, then the printable ones
+fn hex_line(address: usize, bytes: &[u8]) -> String {
+    let hex: Vec<String> = bytes.chunks(2).map(|pair| pair.iter().map(|byte| format!("{:02x}", byte)).collect()).collect();
+    let text: String = bytes.iter().map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }).collect();
+    format!("{:08x}: {:<39}  {}\n", address, hex.join(" "), text)
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use crate::memory_manager::{MemoryBlock, MemoryManager, MemoryStats};
+
+/// One block in a captured memory map; `id` is `None` for free blocks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapEntry {
+    pub id: Option<usize>,
+    pub start: usize,
+    pub end: usize,
+    pub data: Vec<u8>,
+}
+
+/// A copy of the manager's block list at one point in time, for comparing with a later one
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryMap {
+    pub entries: Vec<MapEntry>,         // Address order
+    pub free_list: Vec<(usize, usize)>, // The allocator's free blocks as `(start, end)`, in address order
+}
+
+/// How the block list changed between two maps
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapChange {
+    Created { id: usize, start: usize, end: usize },
+    Freed { id: usize, start: usize, end: usize },
+    Changed { id: usize, start: usize, end: usize }, // New data or range under the same ID
+    Split { start: usize, end: usize, parts: usize }, // A free block now holds `parts` blocks
+    Merged { start: usize, end: usize, parts: usize }, // `parts` blocks are now one free block
+}
+
+impl MapChange {
+    // Splits come before the blocks carved out of them, and merges after the frees that caused them
+    fn order(&self) -> (u8, usize) {
+        match *self {
+            MapChange::Split { start, .. } => (0, start),
+            MapChange::Freed { start, .. } => (1, start),
+            MapChange::Changed { start, .. } => (2, start),
+            MapChange::Created { start, .. } => (3, start),
+            MapChange::Merged { start, .. } => (4, start),
+        }
+    }
+}
+
+impl std::fmt::Display for MapChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            MapChange::Created { id, start, end } => write!(f, "+ block {} at 0x{:04X} - 0x{:04X} ({} bytes)", id, start, end, end - start),
+            MapChange::Freed { id, start, end } => write!(f, "- block {} at 0x{:04X} - 0x{:04X} ({} bytes)", id, start, end, end - start),
+            MapChange::Changed { id, start, end } => write!(f, "~ block {} at 0x{:04X} - 0x{:04X} ({} bytes)", id, start, end, end - start),
+            MapChange::Split { start, end, parts } => write!(f, "split free 0x{:04X} - 0x{:04X} into {} blocks", start, end, parts),
+            MapChange::Merged { start, end, parts } => write!(f, "merged {} blocks into free 0x{:04X} - 0x{:04X}", parts, start, end),
+        }
+    }
+}
+
+impl MemoryMap {
+    pub fn capture(memory_manager: &MemoryManager) -> Self {
+        let entries = memory_manager
+            .blocks()
+            .iter()
+            .map(|block| match block {
+                MemoryBlock::Free(free_block) => MapEntry { id: None, start: free_block.start, end: free_block.end, data: Vec::new() },
+                MemoryBlock::Allocated(allocated_block) => MapEntry {
+                    id: Some(allocated_block.id),
+                    start: allocated_block.start,
+                    end: allocated_block.end,
+                    data: memory_manager.get_data(allocated_block).to_vec(),
+                },
+            })
+            .collect();
+        MemoryMap { entries, free_list: memory_manager.allocator().free_blocks() }
+    }
+
+    /// The same figures `MemoryManager::stats` gave when the map was captured
+    pub fn stats(&self) -> MemoryStats {
+        let allocated: Vec<&MapEntry> = self.entries.iter().filter(|entry| entry.id.is_some()).collect();
+        let total_bytes = self.entries.last().map_or(0, |entry| entry.end);
+        let used_bytes = allocated.iter().map(|entry| entry.end - entry.start).sum();
+        MemoryStats {
+            total_bytes,
+            used_bytes,
+            free_bytes: total_bytes - used_bytes,
+            largest_free: self.free_list.iter().map(|(start, end)| end - start).max().unwrap_or(0),
+            allocated_blocks: allocated.len(),
+            free_blocks: self.free_list.len(),
+        }
+    }
+
+    // Entries lying entirely inside [start, end)
+    fn count_within(&self, start: usize, end: usize) -> usize {
+        self.entries.iter().filter(|entry| entry.start >= start && entry.end <= end).count()
+    }
+
+    /// What changed from `self` to `after`: splits, frees, changes, new blocks and merges, each in address order
+    pub fn diff(&self, after: &MemoryMap) -> Vec<MapChange> {
+        let allocated = |map: &MemoryMap| -> HashMap<usize, MapEntry> {
+            map.entries.iter().filter_map(|entry| entry.id.map(|id| (id, entry.clone()))).collect()
+        };
+        let (before_ids, after_ids) = (allocated(self), allocated(after));
+        let mut changes = Vec::new();
+
+        for (&id, entry) in &after_ids {
+            match before_ids.get(&id) {
+                None => changes.push(MapChange::Created { id, start: entry.start, end: entry.end }),
+                Some(old) if old != entry => changes.push(MapChange::Changed { id, start: entry.start, end: entry.end }),
+                Some(_) => {}
+            }
+        }
+        for (&id, entry) in &before_ids {
+            if !after_ids.contains_key(&id) {
+                changes.push(MapChange::Freed { id, start: entry.start, end: entry.end });
+            }
+        }
+
+        // A free block that is gone was either carved up or absorbed into a larger one; a
+        // new free block either came out of a split or joined several blocks
+        for entry in self.entries.iter().filter(|entry| entry.id.is_none() && !after.entries.contains(entry)) {
+            let parts = after.count_within(entry.start, entry.end);
+            if parts > 1 {
+                changes.push(MapChange::Split { start: entry.start, end: entry.end, parts });
+            }
+        }
+        for entry in after.entries.iter().filter(|entry| entry.id.is_none() && !self.entries.contains(entry)) {
+            let parts = self.count_within(entry.start, entry.end);
+            if parts > 1 {
+                changes.push(MapChange::Merged { start: entry.start, end: entry.end, parts });
+            }
+        }
+
+        changes.sort_by_key(MapChange::order);
+        changes
+    }
+}
+
+impl std::fmt::Display for MemoryMap {
+    /// The same layout as `MemoryManager::format_dump`
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "Memory Manager Dump:")?;
+        for (i, entry) in self.entries.iter().enumerate() {
+            match entry.id {
+                None => writeln!(f, "Block {}: Free Block, Start: 0x{:04X}, End: 0x{:04X}, Size: {}", i, entry.start, entry.end, entry.end - entry.start)?,
+                Some(id) => writeln!(
+                    f,
+                    "Block {}: Allocated Block ID: {}, Start: 0x{:04X}, End: 0x{:04X}, Size: {}, Data: {:?}",
+                    i, id, entry.start, entry.end, entry.end - entry.start, entry.data
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+
+/// A live block in the reference model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelBlock {
+    pub id: usize,
+    pub start: usize,
+    pub end: usize,
+    pub data: Vec<u8>,     // Last data written to the block
+    pub contents: Vec<u8>, // What the arena must hold in [start, end)
+}
+
+/// Obviously correct bookkeeping of which byte ranges are allocated, used to check `MemoryManager`.
+///
+/// The model does not choose addresses; it accepts the address the real manager picked after
+/// checking that the range was free, and it knows when an allocation must have succeeded.
+#[derive(Debug, Clone)]
+pub struct ReferenceModel {
+    arena_size: usize,
+    blocks: BTreeMap<usize, ModelBlock>, // Keyed by start address
+    next_id: usize,
+}
+
+impl ReferenceModel {
+    pub fn new(arena_size: usize) -> Self {
+        ReferenceModel { arena_size, blocks: BTreeMap::new(), next_id: 0 }
+    }
+
+    pub fn rounded_size(size: usize) -> usize {
+        size.max(1).next_power_of_two()
+    }
+
+    pub fn next_id(&self) -> usize {
+        self.next_id
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = &ModelBlock> {
+        self.blocks.values()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&ModelBlock> {
+        self.blocks.values().find(|block| block.id == id)
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        self.blocks.values().map(|block| block.end - block.start).sum()
+    }
+
+    /// Free ranges in address order; adjacent free bytes always form a single range
+    pub fn free_ranges(&self) -> Vec<(usize, usize)> {
+        let mut ranges = Vec::new();
+        let mut cursor = 0;
+        for block in self.blocks.values() {
+            if block.start > cursor {
+                ranges.push((cursor, block.start));
+            }
+            cursor = block.end;
+        }
+        if cursor < self.arena_size {
+            ranges.push((cursor, self.arena_size));
+        }
+        ranges
+    }
+
+    pub fn is_free(&self, start: usize, end: usize) -> bool {
+        end <= self.arena_size
+            && self.free_ranges().iter().any(|&(free_start, free_end)| free_start <= start && end <= free_end)
+    }
+
+    /// Whether a `size`-aligned free range of `size` bytes exists. Any allocator that keeps its
+    /// free list coalesced must satisfy a request when one does.
+    pub fn has_aligned_slot(&self, size: usize) -> bool {
+        self.free_ranges().iter().any(|&(start, end)| {
+            let aligned = start.div_ceil(size) * size;
+            aligned + size <= end
+        })
+    }
+
+    /// Records an allocation at `start` under the next ID
+    pub fn insert(&mut self, start: usize, size: usize, data: Vec<u8>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.place(id, start, size, data);
+        id
+    }
+
+    /// Records a block with a known ID, e.g. one that was moved. New blocks are zero-padded.
+    pub fn place(&mut self, id: usize, start: usize, size: usize, data: Vec<u8>) {
+        let end = start + Self::rounded_size(size);
+        let mut contents = vec![0u8; end - start];
+        contents[..data.len()].copy_from_slice(&data);
+        self.blocks.insert(start, ModelBlock { id, start, end, data, contents });
+    }
+
+    /// Overwrites the start of a block in place; bytes past the new data keep their old values
+    pub fn write(&mut self, id: usize, data: Vec<u8>) {
+        if let Some(block) = self.blocks.values_mut().find(|block| block.id == id) {
+            block.contents[..data.len()].copy_from_slice(&data);
+            block.data = data;
+        }
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<ModelBlock> {
+        let start = self.get(id)?.start;
+        self.blocks.remove(&start)
+    }
+}
@@ -0,0 +1,420 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::buddy_allocator::DEFAULT_ARENA_SIZE;
+use crate::command::{Command, DEFAULT_MAP_WIDTH};
+
+/// A command-file error, reported as `file:line:col: message 'token'`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub file: String,
+    pub line: usize,   // 1-based
+    pub column: usize, // 1-based, in characters
+    pub token: String, // Empty when something is missing at the end of the line
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.message)?;
+        if !self.token.is_empty() {
+            write!(f, " '{}'", self.token)?;
+        }
+        Ok(())
+    }
+}
+
+/// A whitespace-separated word and the column it starts at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub text: &'a str,
+    pub column: usize,
+}
+
+/// Splits a line into tokens, stopping at a `#` that starts a token (a comment). A double-quoted
+/// section, which may contain spaces, `#` and backslash escapes, is part of the surrounding token.
+pub fn tokenize<'a>(file: &str, line: usize, text: &'a str) -> Result<Vec<Token<'a>>, ParseError> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let byte_at = |i: usize| chars.get(i).map(|&(offset, _)| offset).unwrap_or(text.len());
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i].1.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if chars[i].1 == '#' {
+            break;
+        }
+
+        let start = i;
+        while i < chars.len() && !chars[i].1.is_whitespace() {
+            if chars[i].1 == '"' {
+                let quote = i;
+                i += 1;
+                while i < chars.len() && chars[i].1 != '"' {
+                    i += if chars[i].1 == '\\' { 2 } else { 1 };
+                }
+                if i >= chars.len() {
+                    return Err(ParseError {
+                        file: file.to_string(),
+                        line,
+                        column: quote + 1,
+                        token: text[byte_at(start)..].to_string(),
+                        message: "unterminated string".to_string(),
+                    });
+                }
+            }
+            i += 1;
+        }
+        tokens.push(Token { text: &text[byte_at(start)..byte_at(i)], column: start + 1 });
+    }
+    Ok(tokens)
+}
+
+fn hex_bytes(digits: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<char> = digits.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return Err("hex data needs a non-zero, even number of digits".to_string());
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair: String = pair.iter().collect();
+            u8::from_str_radix(&pair, 16).map_err(|_| format!("invalid hex byte '{}'", pair))
+        })
+        .collect()
+}
+
+fn unescape(body: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0u8; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some('r') => bytes.push(b'\r'),
+            Some('0') => bytes.push(0),
+            Some('\\') => bytes.push(b'\\'),
+            Some('"') => bytes.push(b'"'),
+            Some('x') => {
+                let digits: String = chars.by_ref().take(2).collect();
+                bytes.extend(hex_bytes(&digits).map_err(|_| format!("invalid escape '\\x{}'", digits))?);
+            }
+            Some(other) => return Err(format!("invalid escape '\\{}'", other)),
+            None => return Err("string ends with a lone backslash".to_string()),
+        }
+    }
+    Ok(bytes)
+}
+
+/// Decodes a data literal, relative to `base_dir` for `@path`:
+///
+/// - `word` — the bytes of the word itself
+/// - `"a b\n"` — a string with `\n \t \r \0 \\ \" \xHH` escapes
+/// - `0xDEADBEEF` or `x"de ad be ef"` — hex bytes
+/// - `@path` — the contents of a file
+///
+/// Any literal but `@path` may be followed by `*<count>` to repeat it, e.g. `0x00*128`.
+/// Data longer than `max_len`, the arena size, is rejected: it could never be stored.
+pub fn parse_data(literal: &str, base_dir: &Path, max_len: usize) -> Result<Vec<u8>, String> {
+    if let Some(path) = literal.strip_prefix('@') {
+        let path = base_dir.join(path);
+        let bytes = fs::read(&path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        if bytes.len() > max_len {
+            return Err(format!("{} holds {} bytes, more than the {}-byte arena", path.display(), bytes.len(), max_len));
+        }
+        return Ok(bytes);
+    }
+
+    // A `*<count>` suffix is only a repeat outside the quotes
+    let (literal, count) = match literal.rsplit_once('*') {
+        Some((body, count)) if !body.is_empty() && !count.contains('"') && (!body.contains('"') || body.ends_with('"')) => {
+            let count = count.parse::<usize>().map_err(|_| format!("invalid repeat count '{}'", count))?;
+            (body, count)
+        }
+        _ => (literal, 1),
+    };
+
+    let bytes = if let Some(body) = literal.strip_prefix("x\"").and_then(|rest| rest.strip_suffix('"')) {
+        hex_bytes(body)?
+    } else if let Some(body) = literal.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+        unescape(body)?
+    } else if let Some(digits) = literal.strip_prefix("0x").or_else(|| literal.strip_prefix("0X")) {
+        hex_bytes(digits)?
+    } else if literal.contains('"') {
+        return Err("quotes must surround the whole literal".to_string());
+    } else {
+        literal.as_bytes().to_vec()
+    };
+    match bytes.len().checked_mul(count) {
+        Some(len) if len <= max_len => Ok(bytes.repeat(count)),
+        _ => Err(format!("data of {} bytes repeated {} times does not fit the {}-byte arena", bytes.len(), count, max_len)),
+    }
+}
+
+/// Variable values available to `$name` in numeric arguments
+pub type Variables = HashMap<String, i64>;
+
+/// An integer expression over decimal and `0x` hex numbers and `$name` variables, with
+/// `+ - * / %` and parentheses and no spaces
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    Variable(String),
+    Binary(Box<Expr>, char, Box<Expr>),
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Expr, String> {
+        let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut pos = 0;
+        let expr = Self::parse_sum(&chars, &mut pos)?;
+        if pos < chars.len() {
+            return Err(format!("unexpected '{}' in expression", chars[pos]));
+        }
+        Ok(expr)
+    }
+
+    fn parse_sum(chars: &[char], pos: &mut usize) -> Result<Expr, String> {
+        let mut left = Self::parse_product(chars, pos)?;
+        while let Some(&op) = chars.get(*pos).filter(|c| matches!(c, '+' | '-')) {
+            *pos += 1;
+            left = Expr::Binary(Box::new(left), op, Box::new(Self::parse_product(chars, pos)?));
+        }
+        Ok(left)
+    }
+
+    fn parse_product(chars: &[char], pos: &mut usize) -> Result<Expr, String> {
+        let mut left = Self::parse_atom(chars, pos)?;
+        while let Some(&op) = chars.get(*pos).filter(|c| matches!(c, '*' | '/' | '%')) {
+            *pos += 1;
+            left = Expr::Binary(Box::new(left), op, Box::new(Self::parse_atom(chars, pos)?));
+        }
+        Ok(left)
+    }
+
+    fn parse_atom(chars: &[char], pos: &mut usize) -> Result<Expr, String> {
+        let word = |pos: &mut usize, accept: fn(&char) -> bool| {
+            let start = *pos;
+            while chars.get(*pos).is_some_and(accept) {
+                *pos += 1;
+            }
+            chars[start..*pos].iter().collect::<String>()
+        };
+        match chars.get(*pos) {
+            Some('(') => {
+                *pos += 1;
+                let inner = Self::parse_sum(chars, pos)?;
+                if chars.get(*pos) != Some(&')') {
+                    return Err("missing ')' in expression".to_string());
+                }
+                *pos += 1;
+                Ok(inner)
+            }
+            Some('$') => {
+                *pos += 1;
+                let name = word(pos, |c| c.is_ascii_alphanumeric() || *c == '_');
+                if !is_identifier(&name) {
+                    return Err("expected a variable name after '$'".to_string());
+                }
+                Ok(Expr::Variable(name))
+            }
+            Some('0') if matches!(chars.get(*pos + 1), Some('x' | 'X')) => {
+                *pos += 2;
+                let digits = word(pos, char::is_ascii_hexdigit);
+                i64::from_str_radix(&digits, 16).map(Expr::Number).map_err(|_| format!("invalid hex number 0x{}", digits))
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let digits = word(pos, char::is_ascii_digit);
+                digits.parse().map(Expr::Number).map_err(|_| format!("number {} is too large", digits))
+            }
+            Some(c) => Err(format!("unexpected '{}' in expression", c)),
+            None => Err("expression ends early".to_string()),
+        }
+    }
+
+    pub fn eval(&self, variables: &Variables) -> Result<i64, String> {
+        match self {
+            Expr::Number(n) => Ok(*n),
+            Expr::Variable(name) => variables.get(name).copied().ok_or_else(|| format!("unknown variable ${}", name)),
+            Expr::Binary(left, op, right) => {
+                let (a, b) = (left.eval(variables)?, right.eval(variables)?);
+                let value = match op {
+                    '+' => a.checked_add(b),
+                    '-' => a.checked_sub(b),
+                    '*' => a.checked_mul(b),
+                    '/' | '%' if b == 0 => return Err("division by zero".to_string()),
+                    '/' => a.checked_div(b),
+                    _ => a.checked_rem(b),
+                };
+                value.ok_or_else(|| "arithmetic overflow".to_string())
+            }
+        }
+    }
+}
+
+/// Whether `name` can name a script variable
+pub fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Walks the tokens of one line, producing errors that point at the offending token
+struct LineParser<'a> {
+    file: &'a str,
+    base_dir: &'a Path, // Directory of the command file, for `@path` data
+    line: usize,
+    end_column: usize, // Where "missing argument" errors point
+    tokens: std::vec::IntoIter<Token<'a>>,
+    variables: Option<&'a Variables>, // None only checks the syntax of numeric arguments
+    max_data: usize, // Longest data literal, the arena size
+}
+
+impl<'a> LineParser<'a> {
+    fn error(&self, token: Option<Token>, message: String) -> ParseError {
+        ParseError {
+            file: self.file.to_string(),
+            line: self.line,
+            column: token.map(|token| token.column).unwrap_or(self.end_column),
+            token: token.map(|token| token.text.to_string()).unwrap_or_default(),
+            message,
+        }
+    }
+
+    fn next(&mut self, what: &str, command: &str) -> Result<Token<'a>, ParseError> {
+        self.tokens.next().ok_or_else(|| self.error(None, format!("missing {} after {}", what, command)))
+    }
+
+    fn number(&mut self, what: &str, command: &str) -> Result<usize, ParseError> {
+        let token = self.next(what, command)?;
+        let expr = Expr::parse(token.text).map_err(|_| self.error(Some(token), format!("invalid {}", what)))?;
+        let Some(variables) = self.variables else {
+            return Ok(0);
+        };
+        let value = expr.eval(variables).map_err(|message| self.error(Some(token), message))?;
+        usize::try_from(value).map_err(|_| self.error(Some(token), format!("{} is negative ({})", what, value)))
+    }
+
+    fn optional_number(&mut self, what: &str, command: &str) -> Result<Option<usize>, ParseError> {
+        if self.tokens.as_slice().is_empty() {
+            return Ok(None);
+        }
+        self.number(what, command).map(Some)
+    }
+
+    // Consumes the next token if it is `keyword`, in any case
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.tokens.as_slice().first().is_some_and(|token| token.text.eq_ignore_ascii_case(keyword));
+        if found {
+            self.tokens.next();
+        }
+        found
+    }
+
+    fn data(&mut self, command: &str) -> Result<Vec<u8>, ParseError> {
+        let token = self.next("<data>", command)?;
+        parse_data(token.text, self.base_dir, self.max_data).map_err(|message| self.error(Some(token), message))
+    }
+
+    // A file path, which may be quoted, relative to the command file like `@path` data
+    fn path(&mut self, command: &str) -> Result<String, ParseError> {
+        let token = self.next("<path>", command)?;
+        let path = match token.text.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+            Some(body) => unescape(body).map(|bytes| String::from_utf8_lossy(&bytes).into_owned()).map_err(|message| self.error(Some(token), message))?,
+            None => token.text.to_string(),
+        };
+        Ok(self.base_dir.join(path).to_string_lossy().into_owned())
+    }
+
+    fn finish(&mut self) -> Result<(), ParseError> {
+        match self.tokens.next() {
+            Some(token) => Err(self.error(Some(token), "unexpected argument".to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parses one line of a command file for a manager with the default arena size. Blank lines
+/// and comments yield `None`.
+pub fn parse_line(file: &str, line: usize, text: &str) -> Result<Option<Command>, ParseError> {
+    let tokens = tokenize(file, line, text)?;
+    parse_command(file, line, text, tokens, Some(&Variables::new()), DEFAULT_ARENA_SIZE)
+}
+
+/// Parses a command from the tokens of a line, which may start part-way through it. Numeric
+/// arguments are evaluated against `variables`; with `None` they are only checked and read as 0.
+/// Data literals may be at most `max_data` bytes, the arena size.
+pub fn parse_command(
+    file: &str,
+    line: usize,
+    text: &str,
+    tokens: Vec<Token>,
+    variables: Option<&Variables>,
+    max_data: usize,
+) -> Result<Option<Command>, ParseError> {
+    let mut parser = LineParser {
+        file,
+        base_dir: Path::new(file).parent().unwrap_or(Path::new("")),
+        line,
+        end_column: text.trim_end().chars().count() + 1,
+        tokens: tokens.into_iter(),
+        variables,
+        max_data,
+    };
+    let Some(keyword) = parser.tokens.next() else {
+        return Ok(None);
+    };
+    let name = keyword.text.to_ascii_uppercase();
+    let command = match name.as_str() {
+        "INSERT" => {
+            let size = parser.number("<size>", &name)?;
+            let data = parser.data(&name)?;
+            Command::Insert { size, data }
+        }
+        "DELETE" => Command::Delete { id: parser.number("<id>", &name)? },
+        "READ" => Command::Read { id: parser.number("<id>", &name)? },
+        "UPDATE" => {
+            let id = parser.number("<id>", &name)?;
+            let data = parser.data(&name)?;
+            Command::Update { id, data }
+        }
+        "DUMP" => Command::Dump,
+        "STATS" => Command::Stats,
+        "DOT" => Command::Dot,
+        "HEXDUMP" => {
+            let first = parser.number("<id>", &name)?;
+            match parser.optional_number("<end>", &name)? {
+                Some(end) => Command::HexdumpRange { start: first, end },
+                None => Command::Hexdump { id: first },
+            }
+        }
+        "WHICH" => Command::Which { address: parser.number("<address>", &name)? },
+        "FIND" => {
+            let pattern = parser.data(&name)?;
+            Command::Find { pattern, live_only: parser.keyword("LIVE") }
+        }
+        "SAVE" => Command::Save { path: parser.path(&name)? },
+        "LOAD" => Command::Load { path: parser.path(&name)? },
+        "MAP" => Command::Map { width: parser.optional_number("<width>", &name)?.unwrap_or(DEFAULT_MAP_WIDTH) },
+        _ => return Err(parser.error(Some(keyword), "unknown command".to_string())),
+    };
+    parser.finish()?;
+    Ok(Some(command))
+}
+
+/// Parses a whole command file, returning each command with its line number and every error
+pub fn parse_script(file: &str, source: &str) -> Vec<Result<(usize, Command), ParseError>> {
+    source
+        .lines()
+        .enumerate()
+        .filter_map(|(i, text)| parse_line(file, i + 1, text).transpose().map(|result| result.map(|command| (i + 1, command))))
+        .collect()
+}
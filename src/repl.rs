@@ -0,0 +1,163 @@
+use crate::command::COMMAND_NAMES;
+use crate::line_editor::LineEditor;
+use crate::memory_manager::{MemoryBlock, MemoryManager};
+use crate::parser::{self, Variables};
+
+pub const REPL_HELP: &str = "\
+Commands:
+  INSERT <size> <data>   Allocate a block and store data in it
+  DELETE <id>            Free a block
+  READ <id>              Show a block
+  UPDATE <id> <data>     Replace a block's data (it may move to a new ID)
+  DUMP                   Print all allocated and free blocks
+  STATS                  Print usage and fragmentation statistics
+  MAP [<width>]          Draw the arena as a bar of cells (default 64 wide)
+  DOT                    Print the buddy tree as a Graphviz graph
+  HEXDUMP <id>           Show a block's bytes, xxd style (or HEXDUMP <start> <end>)
+  FIND <data> [LIVE]     Search the arena, or only live blocks, for a pattern
+  WHICH <address>        Show the block containing an arena address
+  SAVE <path>            Write the whole manager to a binary memory image
+  LOAD <path>            Replace the manager's state with a saved image
+
+Data is a word, a \"quoted string\" with escapes, hex (0xDEAD or x\"de ad\"),
+a repeated literal (0x00*128) or @path to read a file.
+
+Other commands:
+  help                   Show this help
+  :reset                 Free everything and start again from an empty arena
+  :explain               Turn narration of buddy splits and merges on or off
+  :quit                  Leave (or press Ctrl-D)
+";
+
+const META_COMMANDS: [&str; 4] = ["help", ":reset", ":explain", ":quit"];
+
+/// An interactive session over a `MemoryManager`, one command per line
+pub struct Repl {
+    memory_manager: MemoryManager,
+    line: usize, // Number of lines read, for error positions
+    done: bool,
+}
+
+impl Repl {
+    pub fn new(memory_manager: MemoryManager) -> Self {
+        Repl { memory_manager, line: 0, done: false }
+    }
+
+    pub fn memory_manager(&self) -> &MemoryManager {
+        &self.memory_manager
+    }
+
+    pub fn into_memory_manager(self) -> MemoryManager {
+        self.memory_manager
+    }
+
+    /// Whether `:quit` has been entered
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Runs one line, returning the message to show on success, if any
+    pub fn eval(&mut self, text: &str) -> Result<Option<String>, String> {
+        self.line += 1;
+        match text.trim() {
+            "help" | "?" => return Ok(Some(REPL_HELP.trim_end().to_string())),
+            ":reset" => {
+                self.memory_manager.reset()?;
+                return Ok(Some("Started a fresh memory manager".to_string()));
+            }
+            ":explain" => {
+                let explain = !self.memory_manager.is_explaining();
+                self.memory_manager.set_explain(explain);
+                return Ok(Some(format!("Explain mode {}", if explain { "on" } else { "off" })));
+            }
+            ":quit" | ":q" => {
+                self.done = true;
+                return Ok(None);
+            }
+            _ => {}
+        }
+
+        let arena_size = self.memory_manager.config().arena_size;
+        let parsed = parser::tokenize("<stdin>", self.line, text)
+            .and_then(|tokens| parser::parse_command("<stdin>", self.line, text, tokens, Some(&Variables::new()), arena_size));
+        let command = match parsed {
+            Ok(Some(command)) => command,
+            Ok(None) => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        };
+        let result = command.execute(&mut self.memory_manager);
+        // The allocator's narration comes first, then the usual message or error
+        let explanation = self.memory_manager.take_explanation().join("\n");
+        let with_explanation = |text: String| if explanation.is_empty() { text } else { format!("{}\n{}", explanation, text) };
+        match result {
+            Ok(message) => Ok(message.map(with_explanation).or_else(|| (!explanation.is_empty()).then(|| explanation.clone()))),
+            Err(e) => Err(with_explanation(format!("{} failed: {}", command.name(), e))),
+        }
+    }
+
+    /// Completion candidates for the word at the end of `before_cursor`: command names for the
+    /// first word, and live block IDs for the argument of commands that take one
+    pub fn complete(&self, before_cursor: &str) -> Vec<String> {
+        let words: Vec<&str> = before_cursor.split_whitespace().collect();
+        let typing_new_word = before_cursor.is_empty() || before_cursor.ends_with(char::is_whitespace);
+        let (previous, word) = match (words.len(), typing_new_word) {
+            (0, _) => (&[][..], ""),
+            (_, true) => (&words[..], ""),
+            (n, false) => (&words[..n - 1], words[n - 1]),
+        };
+
+        match previous {
+            [] => {
+                // Offer lowercase names to someone typing in lowercase
+                let lowercase = !word.is_empty() && word.chars().all(|c| !c.is_ascii_uppercase());
+                let commands = COMMAND_NAMES.iter().map(|name| if lowercase { name.to_ascii_lowercase() } else { name.to_string() });
+                commands
+                    .chain(META_COMMANDS.iter().map(|name| name.to_string()))
+                    .filter(|name| name.starts_with(word))
+                    .collect()
+            }
+            [command] if ["DELETE", "READ", "UPDATE", "HEXDUMP"].contains(&command.to_ascii_uppercase().as_str()) => self
+                .memory_manager
+                .blocks()
+                .iter()
+                .filter_map(|block| match block {
+                    MemoryBlock::Allocated(block) => Some(block.id.to_string()),
+                    MemoryBlock::Free(_) => None,
+                })
+                .filter(|id| id.starts_with(word))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Reads and runs lines from stdin until `:quit` or end of input. Returns whether any line failed.
+    pub fn run(&mut self) -> bool {
+        let mut editor = LineEditor::new();
+        let interactive = LineEditor::is_terminal();
+        if interactive {
+            println!("Memory manager REPL. Type 'help' for commands, Ctrl-D to exit.");
+        }
+
+        let mut failed = false;
+        while !self.done {
+            let line = match editor.read_line("mm> ", &|before| self.complete(before)) {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return true;
+                }
+            };
+            editor.add_history(&line);
+            match self.eval(&line) {
+                Ok(Some(message)) => println!("{}", message),
+                Ok(None) => {}
+                Err(e) => {
+                    eprintln!("{}", e);
+                    failed = true;
+                }
+            }
+        }
+        failed
+    }
+}
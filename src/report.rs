@@ -0,0 +1,238 @@
+use std::fmt::Write;
+
+use crate::memory_manager::{ManagerConfig, MemoryManager};
+use crate::memory_map::MemoryMap;
+use crate::script::{Event, Interpreter, Statement};
+
+const MAP_WIDTH: f64 = 800.0; // Pixels across the arena in each step's map
+const CHART_WIDTH: f64 = 800.0;
+const CHART_HEIGHT: f64 = 160.0;
+
+/// The blocks after one statement of a report's script
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportStep {
+    pub file: String,
+    pub line: usize, // 0 for the state before the first statement
+    pub text: String,
+    pub error: Option<String>,
+    pub map: MemoryMap,
+}
+
+/// How the arena changed over a script, written out as a self-contained HTML page with a
+/// memory map per step, a usage and fragmentation chart and a table of blocks
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub title: String,
+    pub config: ManagerConfig,
+    pub steps: Vec<ReportStep>,
+}
+
+impl Report {
+    /// Runs `statements` against `memory_manager`, capturing the blocks before the first
+    /// statement and after every command and EXPECT
+    pub fn run(title: &str, memory_manager: &mut MemoryManager, statements: &[Statement], keep_going: bool) -> Report {
+        let start = ReportStep { file: title.to_string(), line: 0, text: "Start".to_string(), error: None, map: MemoryMap::capture(memory_manager) };
+        let mut steps = vec![start];
+        let (mut text, mut error) = (String::new(), None);
+
+        let mut interpreter = Interpreter::new(keep_going);
+        interpreter.set_capture_maps(true);
+        interpreter.run(memory_manager, statements, &mut |event| match event {
+            Event::Command { text: command, .. } => text = command.trim().to_string(),
+            Event::Error { message, .. } => error = Some(message.to_string()),
+            Event::State { file, line, map, .. } => {
+                steps.push(ReportStep { file: file.to_string(), line, text: std::mem::take(&mut text), error: error.take(), map: map.clone() });
+            }
+            Event::Finished { .. } => {}
+        });
+        Report { title: title.to_string(), config: memory_manager.config(), steps }
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        let title = format!("Memory report: {}", escape(&self.title));
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+            title, STYLE, title
+        );
+        let _ = writeln!(
+            html,
+            "<p>Arena of {} bytes, minimum block size {}, {}; {} steps.</p>",
+            self.config.arena_size,
+            self.config.min_block,
+            self.config.strategy.name(),
+            self.steps.len() - 1
+        );
+
+        html.push_str("<h2>Usage and fragmentation</h2>\n");
+        html.push_str(&self.chart_svg());
+
+        let last = self.steps.len() - 1;
+        html.push_str("<h2>Timeline</h2>\n");
+        let _ = writeln!(html, "<input type=\"range\" id=\"scrubber\" min=\"0\" max=\"{}\" value=\"{}\">", last, last);
+        for (i, step) in self.steps.iter().enumerate() {
+            let _ = writeln!(html, "<section class=\"step\" id=\"step-{}\"{}>", i, if i == last { "" } else { " hidden" });
+            if step.line == 0 {
+                let _ = writeln!(html, "<h3>Step {}: start</h3>", i);
+            } else {
+                let _ = writeln!(html, "<h3>Step {}: {}:{} <code>{}</code></h3>", i, escape(&step.file), step.line, escape(&step.text));
+            }
+            if let Some(error) = &step.error {
+                let _ = writeln!(html, "<p class=\"error\">{}</p>", escape(error));
+            }
+            html.push_str(&self.map_svg(&step.map));
+            html.push_str(&block_table(&step.map));
+            html.push_str("</section>\n");
+        }
+        let _ = write!(html, "<script>{}</script>\n</body>\n</html>\n", SCRIPT);
+        html
+    }
+
+    // Utilization and fragmentation after each step, with a cursor the scrubber moves
+    fn chart_svg(&self) -> String {
+        let x = |i: usize| if self.steps.len() > 1 { i as f64 * CHART_WIDTH / (self.steps.len() - 1) as f64 } else { 0.0 };
+        let y = |fraction: f64| CHART_HEIGHT - fraction * CHART_HEIGHT;
+        let line = |value: &dyn Fn(usize) -> f64| -> String {
+            (0..self.steps.len()).map(|i| format!("{:.1},{:.1}", x(i), y(value(i)))).collect::<Vec<_>>().join(" ")
+        };
+        let stats: Vec<_> = self.steps.iter().map(|step| step.map.stats()).collect();
+
+        let mut svg = String::new();
+        let _ = writeln!(svg, "<svg class=\"chart\" viewBox=\"-40 -10 {} {}\" width=\"{}\">", CHART_WIDTH + 50.0, CHART_HEIGHT + 40.0, CHART_WIDTH + 50.0);
+        for percent in [0, 50, 100] {
+            let height = y(percent as f64 / 100.0);
+            let _ = writeln!(svg, "<line class=\"grid\" x1=\"0\" y1=\"{:.1}\" x2=\"{}\" y2=\"{:.1}\"/>", height, CHART_WIDTH, height);
+            let _ = writeln!(svg, "<text x=\"-6\" y=\"{:.1}\" text-anchor=\"end\">{}%</text>", height + 4.0, percent);
+        }
+        let _ = writeln!(svg, "<polyline class=\"used\" points=\"{}\"/>", line(&|i| stats[i].utilization()));
+        let _ = writeln!(svg, "<polyline class=\"fragmentation\" points=\"{}\"/>", line(&|i| stats[i].fragmentation()));
+        let last = self.steps.len() - 1;
+        let _ = writeln!(svg, "<line id=\"cursor\" x1=\"{0:.1}\" y1=\"0\" x2=\"{0:.1}\" y2=\"{1}\" data-step-width=\"{2:.3}\"/>", x(last), CHART_HEIGHT, x(1));
+        let _ = writeln!(svg, "<text class=\"used\" x=\"0\" y=\"{}\">used</text>", CHART_HEIGHT + 24.0);
+        let _ = writeln!(svg, "<text class=\"fragmentation\" x=\"60\" y=\"{}\">fragmentation</text>", CHART_HEIGHT + 24.0);
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    // The arena as a bar: one rectangle per free-list block and per allocated block, with an address ruler
+    fn map_svg(&self, map: &MemoryMap) -> String {
+        let arena = self.config.arena_size as f64;
+        let x = |address: usize| address as f64 * MAP_WIDTH / arena;
+
+        let mut svg = String::new();
+        let _ = writeln!(svg, "<svg class=\"map\" viewBox=\"-20 0 {} 64\" width=\"{}\">", MAP_WIDTH + 40.0, MAP_WIDTH + 40.0);
+        for &(start, end) in &map.free_list {
+            let _ = writeln!(
+                svg,
+                "<rect class=\"free\" x=\"{:.2}\" y=\"0\" width=\"{:.2}\" height=\"32\"><title>Free 0x{:04X} - 0x{:04X} ({} bytes)</title></rect>",
+                x(start),
+                x(end) - x(start),
+                start,
+                end,
+                end - start
+            );
+        }
+        for entry in &map.entries {
+            let Some(id) = entry.id else { continue };
+            let (left, width) = (x(entry.start), x(entry.end) - x(entry.start));
+            let _ = writeln!(
+                svg,
+                "<rect class=\"allocated\" x=\"{:.2}\" y=\"0\" width=\"{:.2}\" height=\"32\"><title>Block {} at 0x{:04X} - 0x{:04X} ({} bytes, {} wasted)</title></rect>",
+                left,
+                width,
+                id,
+                entry.start,
+                entry.end,
+                entry.end - entry.start,
+                wasted(entry.end - entry.start, entry.data.len())
+            );
+            // Label blocks wide enough to hold their ID
+            if width >= 8.0 * id.to_string().len() as f64 + 4.0 {
+                let _ = writeln!(svg, "<text x=\"{:.2}\" y=\"21\" text-anchor=\"middle\">{}</text>", left + width / 2.0, id);
+            }
+        }
+        for tick in 0..=8 {
+            let address = self.config.arena_size * tick / 8;
+            let _ = writeln!(svg, "<line class=\"tick\" x1=\"{0:.2}\" y1=\"32\" x2=\"{0:.2}\" y2=\"40\"/>", x(address));
+            let _ = writeln!(svg, "<text x=\"{:.2}\" y=\"54\" text-anchor=\"middle\">0x{:04X}</text>", x(address), address);
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+// Bytes of a block not holding data
+fn wasted(size: usize, data: usize) -> usize {
+    size.saturating_sub(data)
+}
+
+// Every free and allocated block in address order, with totals
+fn block_table(map: &MemoryMap) -> String {
+    let mut rows: Vec<(usize, String)> = map
+        .free_list
+        .iter()
+        .map(|&(start, end)| (start, format!("<tr class=\"free\"><td>free</td><td>0x{:04X}</td><td>0x{:04X}</td><td>{}</td><td></td><td></td></tr>", start, end, end - start)))
+        .collect();
+    let (mut used, mut data, mut waste) = (0, 0, 0);
+    for entry in map.entries.iter().filter(|entry| entry.id.is_some()) {
+        let size = entry.end - entry.start;
+        let row = format!(
+            "<tr><td>{}</td><td>0x{:04X}</td><td>0x{:04X}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            entry.id.unwrap_or_default(),
+            entry.start,
+            entry.end,
+            size,
+            entry.data.len(),
+            wasted(size, entry.data.len())
+        );
+        rows.push((entry.start, row));
+        (used, data, waste) = (used + size, data + entry.data.len(), waste + wasted(size, entry.data.len()));
+    }
+    rows.sort_by_key(|(start, _)| *start);
+
+    let mut table = String::from("<table>\n<tr><th>Block</th><th>Start</th><th>End</th><th>Size</th><th>Data</th><th>Wasted</th></tr>\n");
+    for (_, row) in rows {
+        table.push_str(&row);
+        table.push('\n');
+    }
+    let _ = write!(table, "<tr class=\"total\"><td>Allocated</td><td></td><td></td><td>{}</td><td>{}</td><td>{}</td></tr>\n</table>\n", used, data, waste);
+    table
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+svg text { font-size: 11px; }
+.map .free { fill: #cfe8c6; stroke: #6da35e; }
+.map .allocated { fill: #4a78c2; stroke: #2b4f8a; }
+.map text { fill: #222; }
+.map .tick, .chart .grid { stroke: #999; }
+.chart polyline { fill: none; stroke-width: 2; }
+.chart .used { stroke: #4a78c2; fill: #4a78c2; }
+.chart .fragmentation { stroke: #d0763b; fill: #d0763b; }
+.chart polyline.used, .chart polyline.fragmentation { fill: none; }
+#cursor { stroke: #222; stroke-dasharray: 4 3; }
+#scrubber { width: 840px; }
+.error { color: #b00020; }
+table { border-collapse: collapse; margin-top: 1em; }
+td, th { border: 1px solid #ccc; padding: 2px 8px; text-align: right; }
+tr.free td { color: #5a7f50; }
+tr.total td { font-weight: bold; }
+";
+
+const SCRIPT: &str = "
+const scrubber = document.getElementById('scrubber');
+const cursor = document.getElementById('cursor');
+function show(step) {
+  document.querySelectorAll('.step').forEach((section) => { section.hidden = section.id !== 'step-' + step; });
+  const x = step * parseFloat(cursor.dataset.stepWidth);
+  cursor.setAttribute('x1', x);
+  cursor.setAttribute('x2', x);
+}
+scrubber.addEventListener('input', () => show(scrubber.value));
+show(scrubber.value);
+";
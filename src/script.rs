@@ -0,0 +1,772 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::buddy_allocator::DEFAULT_ARENA_SIZE;
+use crate::command::{Outcome, COMMAND_NAMES};
+use crate::json::Json;
+use crate::memory_manager::MemoryManager;
+use crate::memory_map::MemoryMap;
+use crate::parser::{self, is_identifier, Expr, ParseError, Token, Variables};
+
+/// A statement of a command file. Commands are kept as text and parsed again when they run,
+/// once the variables they use have values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    /// A command, preceded by `skip` tokens (`LET name =`) when its ID is captured in `capture`
+    Command { file: String, line: usize, text: String, skip: usize, capture: Option<String> },
+    /// `LET name = <expr>`
+    Let { file: String, line: usize, name: String, value: Argument },
+    /// `REPEAT <count> [AS <counter>] { ... }`, counting from 0
+    Repeat { file: String, line: usize, count: Argument, counter: Option<String>, body: Vec<Statement> },
+    /// `EXPECT <check>`; a failed check is reported with the expected and actual values
+    Expect { file: String, line: usize, text: String, check: Check },
+    /// A line that failed to parse; reported when execution reaches it
+    Error(ParseError),
+}
+
+impl Statement {
+    /// The file and line the statement came from
+    pub fn location(&self) -> (&str, usize) {
+        match self {
+            Statement::Command { file, line, .. }
+            | Statement::Let { file, line, .. }
+            | Statement::Repeat { file, line, .. }
+            | Statement::Expect { file, line, .. } => (file, *line),
+            Statement::Error(e) => (&e.file, e.line),
+        }
+    }
+}
+
+/// Where `Interpreter::set_until` stops: `<line>` in any file, or `<file>:<line>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub file: Option<String>,
+    pub line: usize,
+}
+
+impl Breakpoint {
+    /// A file given as a relative path matches any path ending with it
+    pub fn matches(&self, file: &str, line: usize) -> bool {
+        line == self.line && self.file.as_ref().is_none_or(|wanted| Path::new(file).ends_with(wanted))
+    }
+}
+
+impl std::str::FromStr for Breakpoint {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let (file, line) = match text.rsplit_once(':') {
+            Some((file, line)) => (Some(file.to_string()), line),
+            None => (None, text),
+        };
+        match line.parse::<usize>() {
+            Ok(line) if line > 0 && file.as_ref().is_none_or(|file| !file.is_empty()) => Ok(Breakpoint { file, line }),
+            _ => Err(format!("Invalid line '{}' (expected <line> or <file>:<line>)", text)),
+        }
+    }
+}
+
+impl std::fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}:{}", file, self.line),
+            None => write!(f, "line {}", self.line),
+        }
+    }
+}
+
+/// An expression and the column it starts at, for errors found when it is evaluated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Argument {
+    pub expr: Expr,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    pub fn parse(text: &str) -> Option<Comparison> {
+        match text {
+            "==" => Some(Comparison::Eq),
+            "!=" => Some(Comparison::Ne),
+            "<" => Some(Comparison::Lt),
+            "<=" => Some(Comparison::Le),
+            ">" => Some(Comparison::Gt),
+            ">=" => Some(Comparison::Ge),
+            _ => None,
+        }
+    }
+
+    pub fn holds<T: PartialOrd>(&self, actual: T, expected: T) -> bool {
+        match self {
+            Comparison::Eq => actual == expected,
+            Comparison::Ne => actual != expected,
+            Comparison::Lt => actual < expected,
+            Comparison::Le => actual <= expected,
+            Comparison::Gt => actual > expected,
+            Comparison::Ge => actual >= expected,
+        }
+    }
+}
+
+impl std::fmt::Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let symbol = match self {
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+/// A statistic from `MemoryManager::stats` that EXPECT can compare
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stat {
+    TotalBytes,
+    UsedBytes,
+    FreeBytes,
+    LargestFree,
+    AllocatedBlocks,
+    FreeBlocks,
+}
+
+impl Stat {
+    pub const ALL: [(Stat, &'static str); 6] = [
+        (Stat::TotalBytes, "TOTAL_BYTES"),
+        (Stat::UsedBytes, "USED_BYTES"),
+        (Stat::FreeBytes, "FREE_BYTES"),
+        (Stat::LargestFree, "LARGEST_FREE"),
+        (Stat::AllocatedBlocks, "ALLOCATED_BLOCKS"),
+        (Stat::FreeBlocks, "FREE_BLOCKS"),
+    ];
+
+    pub fn name(&self) -> &'static str {
+        Stat::ALL.iter().find(|(stat, _)| stat == self).map(|(_, name)| *name).unwrap_or_default()
+    }
+
+    pub fn value(&self, memory_manager: &MemoryManager) -> usize {
+        let stats = memory_manager.stats();
+        match self {
+            Stat::TotalBytes => stats.total_bytes,
+            Stat::UsedBytes => stats.used_bytes,
+            Stat::FreeBytes => stats.free_bytes,
+            Stat::LargestFree => stats.largest_free,
+            Stat::AllocatedBlocks => stats.allocated_blocks,
+            Stat::FreeBlocks => stats.free_blocks,
+        }
+    }
+}
+
+/// What an EXPECT statement checks
+#[derive(Debug, Clone, PartialEq)]
+pub enum Check {
+    /// `EXPECT READ <id> == <data>` (or `!=`) compares the data last written to a block
+    Read { id: Argument, comparison: Comparison, data: Vec<u8> },
+    /// `EXPECT ERROR <command>` passes when the command fails; `skip` tokens precede it
+    Error { skip: usize },
+    /// `EXPECT <STAT> <op> <expr>`
+    Stat { stat: Stat, comparison: Comparison, value: Argument },
+    /// `EXPECT BLOCK <id> AT <address>`
+    BlockAt { id: Argument, address: Argument },
+}
+
+// Shows bytes as an escaped string, like a data literal
+fn show_bytes(bytes: &[u8]) -> String {
+    format!("\"{}\" ({} bytes)", bytes.escape_ascii(), bytes.len())
+}
+
+/// A parsed command file, with any INCLUDEd files spliced in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Script {
+    pub statements: Vec<Statement>,
+}
+
+impl Script {
+    /// Parses a script for a manager with the default arena size
+    pub fn parse(file: &str, source: &str) -> Script {
+        Self::parse_for(file, source, DEFAULT_ARENA_SIZE)
+    }
+
+    /// Parses a script whose data literals must fit an arena of `arena_size` bytes
+    pub fn parse_for(file: &str, source: &str, arena_size: usize) -> Script {
+        let mut includes: Vec<PathBuf> = fs::canonicalize(file).into_iter().collect();
+        Script { statements: ScriptParser::new(file, source, &mut includes, arena_size).parse_block(None) }
+    }
+
+    /// Every parse error in the script, including those inside REPEAT bodies
+    pub fn errors(&self) -> Vec<&ParseError> {
+        fn collect<'a>(statements: &'a [Statement], errors: &mut Vec<&'a ParseError>) {
+            for statement in statements {
+                match statement {
+                    Statement::Error(e) => errors.push(e),
+                    Statement::Repeat { body, .. } => collect(body, errors),
+                    _ => {}
+                }
+            }
+        }
+        let mut errors = Vec::new();
+        collect(&self.statements, &mut errors);
+        errors
+    }
+}
+
+struct ScriptParser<'a> {
+    file: &'a str,
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+    includes: &'a mut Vec<PathBuf>, // Files being parsed, to reject INCLUDE cycles
+    arena_size: usize,              // Bounds data literals
+}
+
+impl<'a> ScriptParser<'a> {
+    fn new(file: &'a str, source: &'a str, includes: &'a mut Vec<PathBuf>, arena_size: usize) -> Self {
+        ScriptParser { file, lines: source.lines().enumerate(), includes, arena_size }
+    }
+
+    fn parse_error(&self, line: usize, text: &str, token: Option<&Token>, message: String) -> ParseError {
+        ParseError {
+            file: self.file.to_string(),
+            line,
+            column: token.map(|token| token.column).unwrap_or(text.trim_end().chars().count() + 1),
+            token: token.map(|token| token.text.to_string()).unwrap_or_default(),
+            message,
+        }
+    }
+
+    fn error(&self, line: usize, text: &str, token: Option<&Token>, message: String) -> Statement {
+        Statement::Error(self.parse_error(line, text, token, message))
+    }
+
+    // Parses statements up to the `}` closing the REPEAT on line `opener`, or to the end of the file
+    fn parse_block(&mut self, opener: Option<usize>) -> Vec<Statement> {
+        let mut statements = Vec::new();
+        while let Some((i, text)) = self.lines.next() {
+            let line = i + 1;
+            let tokens = match parser::tokenize(self.file, line, text) {
+                Ok(tokens) if tokens.is_empty() => continue,
+                Ok(tokens) => tokens,
+                Err(e) => {
+                    statements.push(Statement::Error(e));
+                    continue;
+                }
+            };
+
+            match tokens[0].text.to_ascii_uppercase().as_str() {
+                "}" if tokens.len() > 1 => statements.push(self.error(line, text, Some(&tokens[1]), "unexpected argument".to_string())),
+                "}" if opener.is_none() => statements.push(self.error(line, text, Some(&tokens[0]), "unexpected '}'".to_string())),
+                "}" => return statements,
+                "LET" => statements.push(self.parse_let(line, text, tokens)),
+                "REPEAT" => statements.push(self.parse_repeat(line, text, tokens)),
+                "INCLUDE" => statements.extend(self.parse_include(line, text, tokens)),
+                "EXPECT" => statements.push(self.parse_expect(line, text, tokens)),
+                _ => {
+                    let statement = match parser::parse_command(self.file, line, text, tokens, None, self.arena_size) {
+                        Ok(_) => Statement::Command { file: self.file.to_string(), line, text: text.to_string(), skip: 0, capture: None },
+                        Err(e) => Statement::Error(e),
+                    };
+                    statements.push(statement);
+                }
+            }
+        }
+        if let Some(line) = opener {
+            statements.push(self.error(line, "", None, "missing '}' to close REPEAT".to_string()));
+        }
+        statements
+    }
+
+    fn parse_let(&self, line: usize, text: &str, tokens: Vec<Token>) -> Statement {
+        let name = match tokens.get(1) {
+            None => return self.error(line, text, None, "missing <name> after LET".to_string()),
+            Some(token) if !is_identifier(token.text) => return self.error(line, text, Some(token), "invalid variable name".to_string()),
+            Some(token) => token.text.to_string(),
+        };
+        match tokens.get(2) {
+            None => return self.error(line, text, None, "missing '=' after LET".to_string()),
+            Some(token) if token.text != "=" => return self.error(line, text, Some(token), "expected '='".to_string()),
+            Some(_) => {}
+        }
+        let Some(value) = tokens.get(3).copied() else {
+            return self.error(line, text, None, "missing value after LET".to_string());
+        };
+
+        let keyword = value.text.to_ascii_uppercase();
+        if COMMAND_NAMES.contains(&keyword.as_str()) {
+            if keyword != "INSERT" && keyword != "UPDATE" {
+                return self.error(line, text, Some(&value), "only INSERT and UPDATE return an ID".to_string());
+            }
+            return match parser::parse_command(self.file, line, text, tokens[3..].to_vec(), None, self.arena_size) {
+                Ok(_) => Statement::Command { file: self.file.to_string(), line, text: text.to_string(), skip: 3, capture: Some(name) },
+                Err(e) => Statement::Error(e),
+            };
+        }
+
+        let expression: String = tokens[3..].iter().map(|token| token.text).collect();
+        match Expr::parse(&expression) {
+            Ok(expr) => Statement::Let { file: self.file.to_string(), line, name, value: Argument { expr, column: value.column } },
+            Err(message) => self.error(line, text, Some(&Token { text: &expression, column: value.column }), message),
+        }
+    }
+
+    fn parse_repeat(&mut self, line: usize, text: &str, tokens: Vec<Token>) -> Statement {
+        // The body is consumed even when the header is bad, so that its `}` is not reported too
+        let opens_block = tokens.len() > 1 && tokens[tokens.len() - 1].text == "{";
+        let header = if opens_block { &tokens[1..tokens.len() - 1] } else { &tokens[1..] };
+        let body = if opens_block { self.parse_block(Some(line)) } else { Vec::new() };
+
+        // The count may contain spaces; it runs up to an `AS`
+        let split = header.iter().position(|token| token.text.eq_ignore_ascii_case("AS")).unwrap_or(header.len());
+        let (count, rest) = header.split_at(split);
+        let count = match count.first() {
+            None => return self.error(line, text, None, "missing <count> after REPEAT".to_string()),
+            Some(first) => {
+                let expression: String = count.iter().map(|token| token.text).collect();
+                match Expr::parse(&expression) {
+                    Ok(count) => count,
+                    Err(_) => return self.error(line, text, Some(&Token { text: &expression, column: first.column }), "invalid <count>".to_string()),
+                }
+            }
+        };
+        let counter = match rest {
+            [] => None,
+            [_, name] if is_identifier(name.text) => Some(name.text.to_string()),
+            [_, name] => return self.error(line, text, Some(name), "invalid variable name".to_string()),
+            [_] => return self.error(line, text, None, "missing <name> after AS".to_string()),
+            [_, _, extra, ..] => return self.error(line, text, Some(extra), "unexpected argument".to_string()),
+        };
+        if !opens_block {
+            return self.error(line, text, None, "expected '{' at the end of REPEAT".to_string());
+        }
+        let count = Argument { expr: count, column: header[0].column };
+        Statement::Repeat { file: self.file.to_string(), line, count, counter, body }
+    }
+
+    fn argument(&self, line: usize, text: &str, token: Option<&Token>, what: &str) -> Result<Argument, ParseError> {
+        let Some(token) = token else {
+            return Err(self.parse_error(line, text, None, format!("missing {} after EXPECT", what)));
+        };
+        match Expr::parse(token.text) {
+            Ok(expr) => Ok(Argument { expr, column: token.column }),
+            Err(_) => Err(self.parse_error(line, text, Some(token), format!("invalid {}", what))),
+        }
+    }
+
+    fn parse_expect(&self, line: usize, text: &str, tokens: Vec<Token>) -> Statement {
+        match self.parse_check(line, text, &tokens) {
+            Ok(check) => Statement::Expect { file: self.file.to_string(), line, text: text.to_string(), check },
+            Err(e) => Statement::Error(e),
+        }
+    }
+
+    fn parse_check(&self, line: usize, text: &str, tokens: &[Token]) -> Result<Check, ParseError> {
+        let Some(kind) = tokens.get(1) else {
+            return Err(self.parse_error(line, text, None, "missing <check> after EXPECT".to_string()));
+        };
+        let comparison = |token: Option<&Token>, allowed: &[Comparison]| match token {
+            None => Err(self.parse_error(line, text, None, "missing comparison after EXPECT".to_string())),
+            Some(token) => match Comparison::parse(token.text) {
+                Some(comparison) if allowed.contains(&comparison) => Ok(comparison),
+                _ => {
+                    let allowed: Vec<String> = allowed.iter().map(|comparison| format!("'{}'", comparison)).collect();
+                    Err(self.parse_error(line, text, Some(token), format!("expected {}", allowed.join(" or "))))
+                }
+            },
+        };
+        let finish = |check: Check, used: usize| match tokens.get(used) {
+            Some(extra) => Err(self.parse_error(line, text, Some(extra), "unexpected argument".to_string())),
+            None => Ok(check),
+        };
+
+        let name = kind.text.to_ascii_uppercase();
+        match name.as_str() {
+            "READ" => {
+                let id = self.argument(line, text, tokens.get(2), "<id>")?;
+                let comparison = comparison(tokens.get(3), &[Comparison::Eq, Comparison::Ne])?;
+                let Some(literal) = tokens.get(4) else {
+                    return Err(self.parse_error(line, text, None, "missing <data> after EXPECT".to_string()));
+                };
+                let base_dir = Path::new(self.file).parent().unwrap_or(Path::new(""));
+                let data = parser::parse_data(literal.text, base_dir, self.arena_size).map_err(|message| self.parse_error(line, text, Some(literal), message))?;
+                finish(Check::Read { id, comparison, data }, 5)
+            }
+            "ERROR" => match parser::parse_command(self.file, line, text, tokens[2..].to_vec(), None, self.arena_size) {
+                Ok(Some(_)) => Ok(Check::Error { skip: 2 }),
+                Ok(None) => Err(self.parse_error(line, text, None, "missing command after EXPECT ERROR".to_string())),
+                Err(e) => Err(e),
+            },
+            "BLOCK" => {
+                let id = self.argument(line, text, tokens.get(2), "<id>")?;
+                match tokens.get(3) {
+                    Some(token) if token.text.eq_ignore_ascii_case("AT") => {}
+                    Some(token) => return Err(self.parse_error(line, text, Some(token), "expected 'AT'".to_string())),
+                    None => return Err(self.parse_error(line, text, None, "missing 'AT' after EXPECT BLOCK".to_string())),
+                }
+                let address = self.argument(line, text, tokens.get(4), "<address>")?;
+                finish(Check::BlockAt { id, address }, 5)
+            }
+            _ => {
+                let Some(&(stat, _)) = Stat::ALL.iter().find(|(_, stat_name)| *stat_name == name) else {
+                    return Err(self.parse_error(line, text, Some(kind), "unknown check".to_string()));
+                };
+                let all = [Comparison::Eq, Comparison::Ne, Comparison::Lt, Comparison::Le, Comparison::Gt, Comparison::Ge];
+                let comparison = comparison(tokens.get(2), &all)?;
+                let Some(first) = tokens.get(3) else {
+                    return Err(self.parse_error(line, text, None, "missing <value> after EXPECT".to_string()));
+                };
+                // Like LET, the value may contain spaces
+                let expression: String = tokens[3..].iter().map(|token| token.text).collect();
+                match Expr::parse(&expression) {
+                    Ok(expr) => Ok(Check::Stat { stat, comparison, value: Argument { expr, column: first.column } }),
+                    Err(_) => Err(self.parse_error(line, text, Some(&Token { text: &expression, column: first.column }), "invalid <value>".to_string())),
+                }
+            }
+        }
+    }
+
+    fn parse_include(&mut self, line: usize, text: &str, tokens: Vec<Token>) -> Vec<Statement> {
+        let path = match tokens.as_slice() {
+            [_] => return vec![self.error(line, text, None, "missing <path> after INCLUDE".to_string())],
+            [_, path] => path,
+            [_, _, extra, ..] => return vec![self.error(line, text, Some(extra), "unexpected argument".to_string())],
+            [] => unreachable!(),
+        };
+        let resolved = Path::new(self.file).parent().unwrap_or(Path::new("")).join(path.text);
+        let source = match fs::read_to_string(&resolved) {
+            Ok(source) => source,
+            Err(e) => return vec![self.error(line, text, Some(path), format!("cannot read {}: {}", resolved.display(), e))],
+        };
+        let canonical = fs::canonicalize(&resolved).unwrap_or(resolved.clone());
+        if self.includes.contains(&canonical) {
+            return vec![self.error(line, text, Some(path), "INCLUDE cycle".to_string())];
+        }
+
+        let file = resolved.to_string_lossy().into_owned();
+        self.includes.push(canonical);
+        let statements = ScriptParser::new(&file, &source, self.includes, self.arena_size).parse_block(None);
+        self.includes.pop();
+        statements
+    }
+}
+
+/// Something that happened while running a script, in order
+#[derive(Debug, Clone, Copy)]
+pub enum Event<'a> {
+    /// A command or EXPECT is about to run; `text` is its source line
+    Command { file: &'a str, line: usize, text: &'a str },
+    /// It succeeded; `name` is the command (or EXPECT). `explanation` is what the allocator did,
+    /// when explain mode is on.
+    Finished { file: &'a str, line: usize, name: &'a str, outcome: &'a Outcome, elapsed: Duration, explanation: &'a [String] },
+    /// A parse, evaluation or execution error; `message` is already prefixed with `file:line:col:`
+    Error { file: &'a str, line: usize, name: Option<&'a str>, message: &'a str, explanation: &'a [String] },
+    /// The blocks after a command or EXPECT, whether or not it succeeded, and the manager they
+    /// were taken from; only reported after `Interpreter::set_capture_maps(true)`
+    State { file: &'a str, line: usize, map: &'a MemoryMap, memory_manager: &'a MemoryManager },
+}
+
+impl Event<'_> {
+    /// The JSON object reported for a finished or failed statement; `None` for `Command` and `State`
+    pub fn to_json(&self) -> Option<Json> {
+        match *self {
+            Event::Command { .. } | Event::State { .. } => None,
+            Event::Finished { file, line, name, outcome, elapsed, explanation } => {
+                let mut json = Json::object([("file", file.into()), ("line", line.into()), ("command", name.into()), ("status", "ok".into())]);
+                if let Some(id) = outcome.id {
+                    json.push("id", id);
+                }
+                if let Some(message) = &outcome.message {
+                    json.push("message", message.as_str());
+                }
+                for (key, value) in &outcome.details {
+                    json.push(key, value.clone());
+                }
+                json.push("time_us", elapsed.as_nanos() as f64 / 1000.0);
+                if !explanation.is_empty() {
+                    json.push("explain", explanation.to_vec());
+                }
+                Some(json)
+            }
+            Event::Error { file, line, name, message, explanation } => {
+                let mut json = Json::object([
+                    ("file", file.into()),
+                    ("line", line.into()),
+                    ("command", name.into()),
+                    ("status", "error".into()),
+                    ("error", message.into()),
+                ]);
+                if !explanation.is_empty() {
+                    json.push("explain", explanation.to_vec());
+                }
+                Some(json)
+            }
+        }
+    }
+}
+
+/// Runs scripts against a `MemoryManager`, keeping variables between statements
+#[derive(Debug, Clone, Default)]
+pub struct Interpreter {
+    variables: Variables,
+    keep_going: bool, // Run the rest of the script after an error
+    failed: bool,
+    expectations_passed: usize,
+    expectations_failed: usize,
+    stopped: bool,
+    until: Option<Breakpoint>,
+    stopped_at: Option<(String, usize)>,
+    capture_maps: bool,
+}
+
+impl Interpreter {
+    pub fn new(keep_going: bool) -> Self {
+        Interpreter { keep_going, ..Interpreter::default() }
+    }
+
+    pub fn variables(&self) -> &Variables {
+        &self.variables
+    }
+
+    /// Whether any statement has failed
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    pub fn expectations_passed(&self) -> usize {
+        self.expectations_passed
+    }
+
+    pub fn expectations_failed(&self) -> usize {
+        self.expectations_failed
+    }
+
+    /// Stops before the first statement at `breakpoint`, leaving the manager as it was after
+    /// the statement before. A stop is not a failure.
+    pub fn set_until(&mut self, breakpoint: Option<Breakpoint>) {
+        self.until = breakpoint;
+    }
+
+    /// The file and line the breakpoint stopped before, if it was reached
+    pub fn stopped_at(&self) -> Option<(&str, usize)> {
+        self.stopped_at.as_ref().map(|(file, line)| (file.as_str(), *line))
+    }
+
+    /// Reports an `Event::State` with the memory map after every command and EXPECT
+    pub fn set_capture_maps(&mut self, capture_maps: bool) {
+        self.capture_maps = capture_maps;
+    }
+
+    fn report_state(&self, memory_manager: &MemoryManager, report: &mut dyn FnMut(Event), file: &str, line: usize) {
+        if self.capture_maps {
+            report(Event::State { file, line, map: &MemoryMap::capture(memory_manager), memory_manager });
+        }
+    }
+
+    fn fail(&mut self, report: &mut dyn FnMut(Event), file: &str, line: usize, name: Option<&str>, message: String) {
+        self.fail_explained(report, file, line, name, message, &[]);
+    }
+
+    fn fail_explained(
+        &mut self,
+        report: &mut dyn FnMut(Event),
+        file: &str,
+        line: usize,
+        name: Option<&str>,
+        message: String,
+        explanation: &[String],
+    ) {
+        self.failed = true;
+        report(Event::Error { file, line, name, message: &message, explanation });
+        if !self.keep_going {
+            self.stopped = true;
+        }
+    }
+
+    fn eval(&self, file: &str, line: usize, argument: &Argument) -> Result<i64, String> {
+        argument.expr.eval(&self.variables).map_err(|message| format!("{}:{}:{}: {}", file, line, argument.column, message))
+    }
+
+    fn eval_usize(&self, file: &str, line: usize, argument: &Argument, what: &str) -> Result<usize, String> {
+        let value = self.eval(file, line, argument)?;
+        usize::try_from(value).map_err(|_| format!("{}:{}:{}: {} is negative ({})", file, line, argument.column, what, value))
+    }
+
+    // Runs a check, returning the expected and actual values when it does not hold. An `Err`
+    // is a problem with the check itself, such as an unknown variable.
+    fn check(
+        &self,
+        memory_manager: &mut MemoryManager,
+        file: &str,
+        line: usize,
+        text: &str,
+        check: &Check,
+    ) -> Result<Option<(String, String)>, String> {
+        match check {
+            Check::Read { id, comparison, data } => {
+                let id = self.eval_usize(file, line, id, "<id>")?;
+                let expected = match comparison {
+                    Comparison::Ne => format!("anything but {}", show_bytes(data)),
+                    _ => show_bytes(data),
+                };
+                let actual = match memory_manager.find(id) {
+                    Ok(block) => memory_manager.get_data(block).to_vec(),
+                    Err(e) => return Ok(Some((expected, e))),
+                };
+                if comparison.holds(&actual, data) {
+                    return Ok(None);
+                }
+                let mut actual_text = show_bytes(&actual);
+                if let Some(offset) = actual.iter().zip(data).position(|(a, b)| a != b) {
+                    actual_text.push_str(&format!(", first difference at byte {}", offset));
+                }
+                Ok(Some((expected, actual_text)))
+            }
+            Check::Error { skip } => {
+                let tokens = parser::tokenize(file, line, text).map_err(|e| e.to_string())?;
+                let command = parser::parse_command(file, line, text, tokens[*skip..].to_vec(), Some(&self.variables), memory_manager.config().arena_size)
+                    .map_err(|e| e.to_string())?
+                    .ok_or_else(|| format!("{}:{}:1: missing command", file, line))?;
+                match command.execute(memory_manager) {
+                    Err(_) => Ok(None),
+                    Ok(message) => Ok(Some((
+                        format!("{} to fail", command.name()),
+                        message.unwrap_or_else(|| "it succeeded".to_string()),
+                    ))),
+                }
+            }
+            Check::Stat { stat, comparison, value } => {
+                let expected = self.eval(file, line, value)?;
+                let actual = stat.value(memory_manager);
+                if comparison.holds(actual as i128, expected as i128) {
+                    return Ok(None);
+                }
+                Ok(Some((format!("{} {} {}", stat.name(), comparison, expected), format!("{} = {}", stat.name(), actual))))
+            }
+            Check::BlockAt { id, address } => {
+                let id = self.eval_usize(file, line, id, "<id>")?;
+                let address = self.eval_usize(file, line, address, "<address>")?;
+                let actual = match memory_manager.find(id) {
+                    Ok(block) if block.start == address => return Ok(None),
+                    Ok(block) => format!("block {} at 0x{:04X}", id, block.start),
+                    Err(e) => e,
+                };
+                Ok(Some((format!("block {} at 0x{:04X}", id, address), actual)))
+            }
+        }
+    }
+
+    /// Runs statements in order until the end, or the first error unless `keep_going` is set
+    pub fn run(&mut self, memory_manager: &mut MemoryManager, statements: &[Statement], report: &mut dyn FnMut(Event)) {
+        for statement in statements {
+            if self.stopped {
+                return;
+            }
+            let (file, line) = statement.location();
+            if self.until.as_ref().is_some_and(|breakpoint| breakpoint.matches(file, line)) {
+                self.stopped = true;
+                self.stopped_at = Some((file.to_string(), line));
+                return;
+            }
+            match statement {
+                Statement::Error(e) => self.fail(report, &e.file, e.line, None, e.to_string()),
+                Statement::Command { file, line, text, skip, capture } => {
+                    report(Event::Command { file, line: *line, text: text.trim() });
+                    let command = parser::tokenize(file, *line, text)
+                        .and_then(|tokens| parser::parse_command(file, *line, text, tokens[*skip..].to_vec(), Some(&self.variables), memory_manager.config().arena_size));
+                    let command = match command {
+                        Ok(Some(command)) => command,
+                        Ok(None) => continue,
+                        Err(e) => {
+                            self.fail(report, file, *line, None, e.to_string());
+                            continue;
+                        }
+                    };
+                    let started = Instant::now();
+                    let result = command.run(memory_manager);
+                    let elapsed = started.elapsed();
+                    let explanation = memory_manager.take_explanation();
+                    match result {
+                        Ok(outcome) => {
+                            if let (Some(name), Some(id)) = (capture, outcome.id) {
+                                self.variables.insert(name.clone(), id as i64);
+                            }
+                            let name = command.name();
+                            report(Event::Finished { file, line: *line, name, outcome: &outcome, elapsed, explanation: &explanation });
+                        }
+                        Err(e) => {
+                            let message = format!("{}:{}:1: {} failed: {}", file, line, command.name(), e);
+                            self.fail_explained(report, file, *line, Some(command.name()), message, &explanation);
+                        }
+                    }
+                    self.report_state(memory_manager, report, file, *line);
+                }
+                Statement::Expect { file, line, text, check } => {
+                    report(Event::Command { file, line: *line, text: text.trim() });
+                    let started = Instant::now();
+                    let result = self.check(memory_manager, file, *line, text, check);
+                    let explanation = memory_manager.take_explanation();
+                    match result {
+                        Ok(None) => {
+                            self.expectations_passed += 1;
+                            let elapsed = started.elapsed();
+                            let outcome = Outcome::default();
+                            report(Event::Finished { file, line: *line, name: "EXPECT", outcome: &outcome, elapsed, explanation: &explanation });
+                        }
+                        Ok(Some((expected, actual))) => {
+                            self.expectations_failed += 1;
+                            let message = format!(
+                                "{}:{}:1: expectation failed: {}\n  - expected: {}\n  + actual:   {}",
+                                file,
+                                line,
+                                text.trim(),
+                                expected,
+                                actual
+                            );
+                            self.fail_explained(report, file, *line, Some("EXPECT"), message, &explanation);
+                        }
+                        Err(message) => self.fail_explained(report, file, *line, Some("EXPECT"), message, &explanation),
+                    }
+                    self.report_state(memory_manager, report, file, *line);
+                }
+                Statement::Let { file, line, name, value } => match self.eval(file, *line, value) {
+                    Ok(value) => {
+                        self.variables.insert(name.clone(), value);
+                    }
+                    Err(message) => self.fail(report, file, *line, None, message),
+                },
+                Statement::Repeat { file, line, count: argument, counter, body } => {
+                    let count = match self.eval(file, *line, argument) {
+                        Ok(count) if count < 0 => {
+                            let message = format!("{}:{}:{}: <count> is negative ({})", file, line, argument.column, count);
+                            self.fail(report, file, *line, None, message);
+                            continue;
+                        }
+                        Ok(count) => count,
+                        Err(message) => {
+                            self.fail(report, file, *line, None, message);
+                            continue;
+                        }
+                    };
+                    for i in 0..count {
+                        if self.stopped {
+                            break;
+                        }
+                        if let Some(counter) = counter {
+                            self.variables.insert(counter.clone(), i);
+                        }
+                        self.run(memory_manager, body, report);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::fs;
+
+use crate::buddy_allocator::PlacementStrategy;
+use crate::memory_manager::{ManagerConfig, MemoryManager};
+
+const TRACE_HEADER: &str = "mmtrace 1";
+
+/// One `MemoryManager` operation. Only data lengths are kept; replay fills blocks with zeros.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceOp {
+    Insert { size: usize, data_len: usize },
+    Delete { id: usize },
+    Update { id: usize, data_len: usize },
+    Resize { id: usize, new_size: usize },
+    Reset, // Everything freed, IDs start again from 0
+}
+
+/// Where an operation left its block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub id: usize,
+    pub offset: usize,
+}
+
+/// An operation and what it returned: the resulting block for insert/update/resize,
+/// nothing for delete, or the error message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub op: TraceOp,
+    pub result: Result<Option<Placement>, String>,
+}
+
+/// A recorded sequence of operations, stored one per line:
+///
+/// ```text
+/// mmtrace 1 first-fit
+/// i 24 5 = 0@0x0000
+/// d 0 = ok
+/// u 1 5 = 1@0x0020
+/// r 1 64 = 1@0x0040
+/// i 70000 1 = ! Insufficient memory
+/// reset = ok
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub strategy: PlacementStrategy,
+    pub entries: Vec<TraceEntry>,
+}
+
+impl Trace {
+    pub fn new(strategy: PlacementStrategy) -> Self {
+        Trace { strategy, entries: Vec::new() }
+    }
+
+    pub fn push(&mut self, op: TraceOp, result: Result<Option<Placement>, String>) {
+        self.entries.push(TraceEntry { op, result });
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_string()).map_err(|e| format!("Failed to write trace {}: {}", path, e))
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Failed to read trace {}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("{}:{}", path, e))
+    }
+
+    /// Parses the text format; errors are prefixed with the 1-based line number
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate();
+        let strategy = match lines.next() {
+            Some((_, header)) => match header.strip_prefix(TRACE_HEADER) {
+                Some(rest) => rest.trim().parse::<PlacementStrategy>().map_err(|e| format!("1: {}", e))?,
+                None => return Err(format!("1: expected '{} <strategy>' header", TRACE_HEADER)),
+            },
+            None => return Err("1: empty trace".to_string()),
+        };
+
+        let mut trace = Trace::new(strategy);
+        for (i, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = Self::parse_entry(line).map_err(|e| format!("{}: {}", i + 1, e))?;
+            trace.entries.push(entry);
+        }
+        Ok(trace)
+    }
+
+    fn parse_entry(line: &str) -> Result<TraceEntry, String> {
+        let (op, result) = line.split_once(" = ").ok_or("missing ' = ' before the result")?;
+
+        let fields: Vec<&str> = op.split_whitespace().collect();
+        let number = |i: usize| -> Result<usize, String> {
+            let field = fields.get(i).ok_or(format!("missing operand in '{}'", op))?;
+            field.parse::<usize>().map_err(|_| format!("invalid number '{}'", field))
+        };
+        let op = match fields.first().copied() {
+            Some("i") if fields.len() == 3 => TraceOp::Insert { size: number(1)?, data_len: number(2)? },
+            Some("d") if fields.len() == 2 => TraceOp::Delete { id: number(1)? },
+            Some("u") if fields.len() == 3 => TraceOp::Update { id: number(1)?, data_len: number(2)? },
+            Some("r") if fields.len() == 3 => TraceOp::Resize { id: number(1)?, new_size: number(2)? },
+            Some("reset") if fields.len() == 1 => TraceOp::Reset,
+            _ => return Err(format!("invalid operation '{}'", op)),
+        };
+
+        let result = if let Some(message) = result.strip_prefix("! ") {
+            Err(message.to_string())
+        } else if result == "ok" {
+            Ok(None)
+        } else {
+            let (id, offset) = result.split_once("@0x").ok_or(format!("invalid result '{}'", result))?;
+            let id = id.parse::<usize>().map_err(|_| format!("invalid block ID '{}'", id))?;
+            let offset = usize::from_str_radix(offset, 16).map_err(|_| format!("invalid offset '0x{}'", offset))?;
+            Ok(Some(Placement { id, offset }))
+        };
+
+        Ok(TraceEntry { op, result })
+    }
+}
+
+impl std::fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.op {
+            TraceOp::Insert { size, data_len } => write!(f, "i {} {}", size, data_len)?,
+            TraceOp::Delete { id } => write!(f, "d {}", id)?,
+            TraceOp::Update { id, data_len } => write!(f, "u {} {}", id, data_len)?,
+            TraceOp::Resize { id, new_size } => write!(f, "r {} {}", id, new_size)?,
+            TraceOp::Reset => write!(f, "reset")?,
+        }
+        match &self.result {
+            Ok(Some(placement)) => write!(f, " = {}@0x{:04X}", placement.id, placement.offset),
+            Ok(None) => write!(f, " = ok"),
+            Err(message) => write!(f, " = ! {}", message),
+        }
+    }
+}
+
+impl std::fmt::Display for Trace {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{} {}", TRACE_HEADER, self.strategy)?;
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+/// An operation whose replayed result differs from the recorded one
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub index: usize, // 0-based position in the trace
+    pub recorded: TraceEntry,
+    pub replayed: Result<Option<Placement>, String>,
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let describe = |result: &Result<Option<Placement>, String>| match result {
+            Ok(Some(placement)) => format!("block at 0x{:04X}", placement.offset),
+            Ok(None) => "ok".to_string(),
+            Err(message) => format!("error '{}'", message),
+        };
+        write!(
+            f,
+            "operation {} ({}): recorded {}, replayed {}",
+            self.index,
+            self.recorded,
+            describe(&self.recorded.result),
+            describe(&self.replayed)
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayReport {
+    pub strategy: PlacementStrategy,
+    pub operations: usize,
+    pub divergences: Vec<Divergence>,
+}
+
+impl ReplayReport {
+    pub fn is_exact(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+impl std::fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Replayed {} operations with {}: {} divergence(s)",
+            self.operations,
+            self.strategy,
+            self.divergences.len()
+        )?;
+        for divergence in &self.divergences {
+            writeln!(f, "  {}", divergence)?;
+        }
+        Ok(())
+    }
+}
+
+/// Re-executes `trace` against a fresh, quiet manager using `strategy`.
+///
+/// Recorded block IDs are mapped to the IDs the replay hands out, so an operation only
+/// diverges when its offset or error differs, not because an earlier ID shifted.
+pub fn replay(trace: &Trace, strategy: PlacementStrategy) -> ReplayReport {
+    replay_with_config(trace, ManagerConfig { strategy, ..ManagerConfig::default() })
+}
+
+/// Like `replay`, with a manager built from `config`. Panics if `config` is invalid.
+pub fn replay_with_config(trace: &Trace, config: ManagerConfig) -> ReplayReport {
+    let strategy = config.strategy;
+    let mut memory_manager = MemoryManager::with_config(config).expect("invalid manager configuration");
+    memory_manager.set_verbose(false);
+
+    let mut ids: HashMap<usize, usize> = HashMap::new();
+    let mut divergences = Vec::new();
+    // IDs that never existed in the replay map to one that cannot exist either
+    let lookup = |ids: &HashMap<usize, usize>, id: usize| ids.get(&id).copied().unwrap_or(usize::MAX);
+    let placement = |memory_manager: &MemoryManager, id: usize| {
+        memory_manager.find(id).map(|block| Some(Placement { id, offset: block.start }))
+    };
+
+    for (index, entry) in trace.entries.iter().enumerate() {
+        let replayed = match &entry.op {
+            TraceOp::Insert { size, data_len } => memory_manager
+                .insert(*size, vec![0; *data_len])
+                .and_then(|id| placement(&memory_manager, id)),
+            TraceOp::Delete { id } => memory_manager.delete(lookup(&ids, *id)).map(|_| None),
+            TraceOp::Update { id, data_len } => memory_manager
+                .update(lookup(&ids, *id), vec![0; *data_len])
+                .and_then(|id| placement(&memory_manager, id)),
+            TraceOp::Resize { id, new_size } => {
+                let id = lookup(&ids, *id);
+                memory_manager.resize(id, *new_size).and_then(|_| placement(&memory_manager, id))
+            }
+            TraceOp::Reset => {
+                ids.clear();
+                memory_manager.reset().map(|_| None)
+            }
+        };
+
+        if let (Ok(Some(recorded)), Ok(Some(actual))) = (&entry.result, &replayed) {
+            ids.insert(recorded.id, actual.id);
+        }
+
+        let same = match (&entry.result, &replayed) {
+            (Ok(Some(recorded)), Ok(Some(actual))) => recorded.offset == actual.offset,
+            (recorded, actual) => recorded == actual,
+        };
+        if !same {
+            divergences.push(Divergence { index, recorded: entry.clone(), replayed });
+        }
+    }
+
+    ReplayReport { strategy, operations: trace.entries.len(), divergences }
+}
@@ -0,0 +1,188 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Small deterministic PRNG (xorshift64*), so a seed reproduces a workload on every platform
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Scramble the seed (splitmix64) so that small seeds still give well-mixed states; zero is not a valid state
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Rng { state: if z == 0 { 1 } else { z } }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in [min, max]
+    pub fn range(&mut self, min: usize, max: usize) -> usize {
+        if max <= min {
+            return min;
+        }
+        min + (self.next_u64() % (max - min + 1) as u64) as usize
+    }
+
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+}
+
+fn parse_fields<T: std::str::FromStr>(spec: &str, fields: &[&str], expected: &str) -> Result<Vec<T>, String> {
+    fields
+        .iter()
+        .map(|field| field.parse::<T>().map_err(|_| format!("Invalid value '{}' in '{}' (expected {})", field, spec, expected)))
+        .collect()
+}
+
+/// Distribution of requested block sizes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeDistribution {
+    Uniform { min: usize, max: usize },
+    PowerLaw { alpha: f64, min: usize, max: usize }, // Many small blocks, a long tail of large ones
+    Bimodal { small: usize, large: usize, large_fraction: f64 },
+}
+
+impl SizeDistribution {
+    pub fn sample(&self, rng: &mut Rng) -> usize {
+        match *self {
+            SizeDistribution::Uniform { min, max } => rng.range(min, max),
+            SizeDistribution::PowerLaw { alpha, min, max } => {
+                // Inverse transform sampling of a Pareto distribution, truncated to [min, max]
+                let u = rng.next_f64();
+                let size = min as f64 / (1.0 - u).powf(1.0 / alpha);
+                (size as usize).clamp(min, max)
+            }
+            SizeDistribution::Bimodal { small, large, large_fraction } => {
+                if rng.chance(large_fraction) { large } else { small }
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for SizeDistribution {
+    type Err = String;
+
+    /// `uniform:<min>:<max>`, `power-law:<alpha>:<min>:<max>` or `bimodal:<small>:<large>:<large fraction>`
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = spec.split(':').collect();
+        match fields.as_slice() {
+            ["uniform", rest @ ..] if rest.len() == 2 => {
+                let v = parse_fields::<usize>(spec, rest, "sizes")?;
+                Ok(SizeDistribution::Uniform { min: v[0], max: v[1] })
+            }
+            ["power-law", alpha, rest @ ..] if rest.len() == 2 => {
+                let alpha = parse_fields::<f64>(spec, &[alpha], "an exponent")?[0];
+                let v = parse_fields::<usize>(spec, rest, "sizes")?;
+                Ok(SizeDistribution::PowerLaw { alpha, min: v[0].max(1), max: v[1] })
+            }
+            ["bimodal", small, large, fraction] => {
+                let v = parse_fields::<usize>(spec, &[small, large], "sizes")?;
+                let large_fraction = parse_fields::<f64>(spec, &[fraction], "a fraction")?[0];
+                Ok(SizeDistribution::Bimodal { small: v[0], large: v[1], large_fraction })
+            }
+            _ => Err(format!(
+                "Invalid size distribution '{}' (expected uniform:<min>:<max>, power-law:<alpha>:<min>:<max> or bimodal:<small>:<large>:<fraction>)",
+                spec
+            )),
+        }
+    }
+}
+
+/// How many operations a block stays live; decides which block a free releases
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LifetimeDistribution {
+    Uniform { min: usize, max: usize },
+    Exponential { mean: f64 },
+}
+
+impl LifetimeDistribution {
+    pub fn sample(&self, rng: &mut Rng) -> usize {
+        match *self {
+            LifetimeDistribution::Uniform { min, max } => rng.range(min, max),
+            LifetimeDistribution::Exponential { mean } => (-(1.0 - rng.next_f64()).ln() * mean) as usize,
+        }
+    }
+}
+
+impl std::str::FromStr for LifetimeDistribution {
+    type Err = String;
+
+    /// `uniform:<min>:<max>` or `exp:<mean>`
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = spec.split(':').collect();
+        match fields.as_slice() {
+            ["uniform", rest @ ..] if rest.len() == 2 => {
+                let v = parse_fields::<usize>(spec, rest, "operation counts")?;
+                Ok(LifetimeDistribution::Uniform { min: v[0], max: v[1] })
+            }
+            ["exp", mean] => Ok(LifetimeDistribution::Exponential { mean: parse_fields::<f64>(spec, &[mean], "a mean")?[0] }),
+            _ => Err(format!("Invalid lifetime distribution '{}' (expected uniform:<min>:<max> or exp:<mean>)", spec)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkloadConfig {
+    pub seed: u64,
+    pub operations: usize,
+    pub sizes: SizeDistribution,
+    pub lifetimes: LifetimeDistribution,
+    pub alloc_ratio: f64, // Probability that an operation allocates while blocks are live
+}
+
+impl Default for WorkloadConfig {
+    fn default() -> Self {
+        WorkloadConfig {
+            seed: 1,
+            operations: 10_000,
+            sizes: SizeDistribution::PowerLaw { alpha: 1.2, min: 16, max: 4096 },
+            lifetimes: LifetimeDistribution::Exponential { mean: 200.0 },
+            alloc_ratio: 0.5,
+        }
+    }
+}
+
+/// A generated operation. `Free` names the allocation by its position among the workload's
+/// `Alloc`s, so the same workload can be run against any strategy even when allocations fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkloadOp {
+    Alloc { size: usize },
+    Free { alloc_index: usize },
+}
+
+/// Generates `config.operations` operations. Each allocation draws a lifetime, and every free
+/// releases the live allocation whose lifetime ends first.
+pub fn generate(config: &WorkloadConfig) -> Vec<WorkloadOp> {
+    let mut rng = Rng::new(config.seed);
+    let mut ops = Vec::with_capacity(config.operations);
+    let mut live: BinaryHeap<Reverse<(usize, usize)>> = BinaryHeap::new(); // (death time, alloc index)
+    let mut allocations = 0;
+
+    for now in 0..config.operations {
+        if live.is_empty() || rng.chance(config.alloc_ratio) {
+            let size = config.sizes.sample(&mut rng);
+            let death = now + config.lifetimes.sample(&mut rng);
+            live.push(Reverse((death, allocations)));
+            ops.push(WorkloadOp::Alloc { size });
+            allocations += 1;
+        } else if let Some(Reverse((_, alloc_index))) = live.pop() {
+            ops.push(WorkloadOp::Free { alloc_index });
+        }
+    }
+    ops
+}
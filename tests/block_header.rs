@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod tests {
+    use memory_manager::block_header::{walk, HEADER_SIZE};
+    use memory_manager::{BlockHeader, ManagerConfig, MemoryManager, PlacementStrategy};
+
+    fn manager() -> MemoryManager {
+        let config = ManagerConfig { arena_size: 256, min_block: 1, strategy: PlacementStrategy::FirstFit, block_headers: true };
+        let mut memory_manager = MemoryManager::with_config(config).unwrap();
+        memory_manager.set_verbose(false);
+        memory_manager
+    }
+
+    #[test]
+    fn test_header_round_trips() {
+        let header = BlockHeader { order: 6, allocated: true, id: 42, data_len: 17 };
+        assert_eq!(BlockHeader::decode(&header.encode()), Ok(header));
+        assert_eq!(BlockHeader::decode(&[0; HEADER_SIZE]), Err("bad header magic 0x0000".to_string()));
+        assert!(BlockHeader::decode(&header.encode()[..8]).is_err());
+    }
+
+    #[test]
+    fn test_blocks_make_room_for_their_header() {
+        let mut memory_manager = manager();
+        let id = memory_manager.insert(16, b"payload".to_vec()).unwrap();
+        let block = memory_manager.find(id).unwrap();
+        assert_eq!((block.start, block.end), (0, 32), "16 bytes plus the header need a 32-byte block");
+        assert_eq!(&memory_manager.get_buffer()[HEADER_SIZE..HEADER_SIZE + 7], b"payload");
+        assert_eq!(memory_manager.get_data(memory_manager.find(id).unwrap()), b"payload");
+
+        // Data that would overwrite the next block's header moves the block instead
+        assert_eq!(memory_manager.update(id, vec![7; 16]), Ok(id));
+        assert_eq!(memory_manager.update(id, vec![7; 17]), Ok(1));
+    }
+
+    #[test]
+    fn test_heap_is_walkable_after_every_operation() {
+        let mut memory_manager = manager();
+        assert_eq!(walk(memory_manager.get_buffer()), Ok(vec![(0, BlockHeader::free(8))]));
+
+        memory_manager.insert(10, b"a".to_vec()).unwrap();
+        memory_manager.insert(40, b"bb".to_vec()).unwrap();
+        memory_manager.insert(10, b"ccc".to_vec()).unwrap();
+        memory_manager.resize(2, 20).unwrap();
+        memory_manager.update(1, b"updated".to_vec()).unwrap();
+        memory_manager.delete(0).unwrap();
+
+        let blocks = walk(memory_manager.get_buffer()).unwrap();
+        let summary: Vec<(usize, usize, bool, usize, usize)> =
+            blocks.iter().map(|(start, header)| (*start, header.size(), header.allocated, header.id, header.data_len)).collect();
+        assert_eq!(summary, [(0, 64, false, 0, 0), (64, 64, true, 1, 7), (128, 64, true, 2, 3), (192, 64, false, 0, 0)]);
+        let allocator_blocks: Vec<(usize, usize)> = blocks.iter().map(|(start, header)| (*start, start + header.size())).collect();
+        let mut expected = memory_manager.allocator().free_blocks();
+        expected.extend(memory_manager.allocator().allocated_blocks());
+        expected.sort_unstable();
+        assert_eq!(allocator_blocks, expected);
+    }
+
+    #[test]
+    fn test_sizes_near_the_limit_fail_with_headers() {
+        let mut memory_manager = manager();
+        let id = memory_manager.insert(10, b"first".to_vec()).unwrap();
+        for size in [isize::MAX as usize, usize::MAX - HEADER_SIZE + 1, usize::MAX] {
+            assert_eq!(memory_manager.insert(size, b"x".to_vec()), Err("Insufficient memory".to_string()), "INSERT {}", size);
+            assert_eq!(memory_manager.resize(id, size), Err("Insufficient memory".to_string()), "resize to {}", size);
+        }
+        // Data that fits the arena only without its header
+        assert_eq!(memory_manager.update(id, vec![0; 256 - HEADER_SIZE + 1]), Err("Insufficient memory".to_string()));
+        assert_eq!(memory_manager.get_data(memory_manager.find(id).unwrap()), b"first", "Failed calls leave the block alone");
+    }
+
+    #[test]
+    fn test_recover_from_buffer() {
+        let mut memory_manager = manager();
+        memory_manager.insert(10, b"first".to_vec()).unwrap();
+        memory_manager.insert(40, b"second".to_vec()).unwrap();
+        memory_manager.insert(10, b"third".to_vec()).unwrap();
+        memory_manager.delete(0).unwrap();
+
+        let mut recovered = MemoryManager::recover_from_buffer(memory_manager.get_buffer()).unwrap();
+        recovered.set_verbose(false);
+        let blocks = |memory_manager: &MemoryManager| memory_manager.iter_all().map(|block| (block.id, block.start, block.end, block.data_len)).collect::<Vec<_>>();
+        assert_eq!(blocks(&recovered), blocks(&memory_manager));
+        assert_eq!(recovered.get_data(recovered.find(2).unwrap()), b"third");
+        assert_eq!(recovered.insert(4, b"next".to_vec()), Ok(3), "IDs carry on after the highest one found");
+
+        let mut corrupt = memory_manager.get_buffer().to_vec();
+        corrupt[64] = 0;
+        let error = MemoryManager::recover_from_buffer(&corrupt).err().unwrap_or_default();
+        assert!(error.starts_with("0x0040: bad header magic"), "{}", error);
+        assert!(MemoryManager::recover_from_buffer(&[0; 256]).is_err());
+
+        // The largest ID has no next one
+        let mut last_id = memory_manager.get_buffer().to_vec();
+        last_id[64 + 8..64 + 16].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            MemoryManager::recover_from_buffer(&last_id).err(),
+            Some(format!("0x0040: block ID {} leaves no ID to carry on from", usize::MAX))
+        );
+    }
+}
@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod tests {
+    use memory_manager::{BuddyAllocator, PlacementStrategy};
+
+    #[test]
+    fn test_splits_and_merges_buddies() {
+        let mut allocator = BuddyAllocator::with_arena(128, PlacementStrategy::FirstFit);
+        assert_eq!(allocator.allocate(32), Ok(0));
+        assert_eq!(allocator.allocate(16), Ok(32));
+        assert_eq!(allocator.free_blocks(), vec![(48, 64), (64, 128)]);
+
+        assert!(allocator.deallocate(0, 16).is_err(), "The size must match the allocation");
+        allocator.deallocate(0, 32).unwrap();
+        allocator.deallocate(32, 16).unwrap();
+        assert_eq!(allocator.free_blocks(), vec![(0, 128)]);
+    }
+
+    #[test]
+    fn test_explain_narrates_splits_and_merges() {
+        let mut allocator = BuddyAllocator::with_arena(128, PlacementStrategy::FirstFit);
+        allocator.allocate(32).unwrap();
+        assert!(allocator.take_explanation().is_empty(), "Nothing is recorded until explain mode is on");
+
+        allocator.set_explain(true);
+        allocator.allocate(16).unwrap();
+        let explanation = allocator.take_explanation();
+        assert_eq!(
+            explanation[..4],
+            [
+                "allocate 16 bytes: order 4 (16 bytes)",
+                "  chose free block 0x0020 of order 5 (32 bytes) by first-fit",
+                "  split into 0x0020 and 0x0030 (order 4); 0x0030 stays free",
+                "  allocated 0x0020 - 0x0030",
+            ]
+        );
+        assert_eq!(explanation.last().map(String::as_str), Some("    └── 0x0040 - 0x0080 order 6 free"));
+        assert!(allocator.take_explanation().is_empty(), "Taking the explanation clears it");
+
+        allocator.deallocate(0, 32).unwrap();
+        allocator.deallocate(32, 16).unwrap();
+        let explanation = allocator.take_explanation();
+        assert!(explanation.contains(&"  buddy 0x0020 of order 5 is split into smaller blocks: stop".to_string()));
+        assert!(explanation.contains(&"  buddy 0x0040 of order 6 is free: merge into 0x0000 (order 7)".to_string()));
+        assert_eq!(allocator.render_tree(), "0x0000 - 0x0080 order 7 free\n");
+
+        assert_eq!(allocator.allocate(256), Err("Insufficient memory".to_string()));
+        assert_eq!(allocator.take_explanation()[1], "  no free block of order 8 or higher: insufficient memory");
+    }
+
+    #[test]
+    fn test_to_dot_groups_buddies() {
+        let mut allocator = BuddyAllocator::with_arena(128, PlacementStrategy::FirstFit);
+        assert_eq!(
+            allocator.to_dot(),
+            "digraph buddy {\n    node [shape=box, style=filled, fontname=\"monospace\"];\n    b0_7 [label=\"0x0000 - 0x0080\\norder 7\\nfree\", fillcolor=palegreen];\n}\n"
+        );
+
+        allocator.allocate(64).unwrap();
+        let dot = allocator.to_dot();
+        assert!(dot.contains("    subgraph cluster_0_6 { label=\"buddies\"; style=dashed; b0_6; b64_6; }\n"), "{}", dot);
+        assert!(dot.contains("    b0_7 -> b0_6;\n    b0_7 -> b64_6;\n"), "{}", dot);
+        assert!(dot.contains("    b0_6 [label=\"0x0000 - 0x0040\\norder 6\\nallocated\", fillcolor=lightblue];\n"), "{}", dot);
+        assert!(dot.contains("b64_6 [label=\"0x0040 - 0x0080\\norder 6\\nfree\""), "{}", dot);
+    }
+}
@@ -0,0 +1,45 @@
+#[cfg(test)]
+mod tests {
+    use memory_manager::json::Json;
+    use memory_manager::{MemoryManager, PlacementStrategy};
+
+    fn events<'a>(trace: &'a Json, phase: &str) -> Vec<&'a Json> {
+        match trace.get("traceEvents") {
+            Some(Json::Array(events)) => events.iter().filter(|event| event.get("ph") == Some(&Json::from(phase))).collect(),
+            _ => panic!("No traceEvents array"),
+        }
+    }
+
+    #[test]
+    fn test_records_block_lifetimes_and_counters() {
+        let mut memory_manager = MemoryManager::with_strategy(PlacementStrategy::FirstFit);
+        memory_manager.set_verbose(false);
+        memory_manager.enable_chrome_trace();
+        memory_manager.insert(20, b"a".to_vec()).unwrap();
+        memory_manager.insert(16, b"b".to_vec()).unwrap();
+        memory_manager.delete(0).unwrap();
+        assert!(memory_manager.insert(70000, Vec::new()).is_err());
+        memory_manager.insert(8, b"c".to_vec()).unwrap();
+        let trace = memory_manager.take_chrome_trace().expect("Tracing was enabled").to_json();
+
+        // Block 0 ends when it is deleted; blocks 1 and 2 are still open at the end
+        let blocks = events(&trace, "X");
+        let names: Vec<&Json> = blocks.iter().filter_map(|event| event.get("name")).collect();
+        assert_eq!(names, [&Json::from("block 0"), &Json::from("block 1"), &Json::from("block 2")]);
+        let args = blocks[1].get("args").unwrap();
+        assert_eq!((args.get("id"), args.get("address"), args.get("size")), (Some(&Json::Int(1)), Some(&Json::from("0x0020")), Some(&Json::Int(16))));
+
+        // Overlapping blocks get separate lanes, and a freed lane is reused
+        let lanes: Vec<&Json> = blocks.iter().filter_map(|event| event.get("tid")).collect();
+        assert_eq!(lanes, [&Json::Int(1), &Json::Int(2), &Json::Int(1)]);
+
+        let counters = events(&trace, "C");
+        assert_eq!(counters.len(), 10, "Two counters per operation");
+        let used: Vec<&Json> = counters.iter().filter_map(|event| event.get("args").and_then(|args| args.get("used bytes"))).collect();
+        assert_eq!(used, [&Json::Int(32), &Json::Int(48), &Json::Int(16), &Json::Int(16), &Json::Int(24)]);
+
+        let failures = events(&trace, "i");
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].get("name"), Some(&Json::from("insert failed")));
+    }
+}
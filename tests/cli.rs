@@ -0,0 +1,307 @@
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::process::{Command, Output, Stdio};
+
+    use memory_manager::cli::{parse_size, Cli, Format, RunOptions, Subcommand, Verbosity};
+    use memory_manager::PlacementStrategy;
+
+    fn parse(args: &[&str]) -> Result<Cli, String> {
+        Cli::parse(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+    }
+
+    fn run_script(name: &str, script: &str, extra_args: &[&str]) -> Output {
+        let path = std::env::temp_dir().join(format!("mm-cli-{}-{}.cmmd", name, std::process::id()));
+        std::fs::write(&path, script).expect("Failed to write script");
+        let output = Command::new(env!("CARGO_BIN_EXE_memory_manager"))
+            .arg(&path)
+            .args(extra_args)
+            .output()
+            .expect("Failed to run binary");
+        let _ = std::fs::remove_file(&path);
+        output
+    }
+
+    #[test]
+    fn test_successful_script_exits_zero() {
+        let output = run_script("ok", "# setup\nINSERT 24 data1\n\nINSERT 48 data2 \nDELETE 0\nDUMP\n", &[]);
+        assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    #[test]
+    fn test_stops_at_first_error_unless_keep_going() {
+        let script = "INSERT 24 a\nDELETE\nINSERT 24 b\nDELETE 7\nINSERT 24 c\n";
+
+        let output = run_script("stop", script, &[]);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(stderr.contains(":2:7: missing <id> after DELETE"), "{}", stderr);
+        assert!(!stderr.contains("Allocated block with ID 1"), "Should stop after the first error: {}", stderr);
+
+        let output = run_script("keep", script, &["--keep-going"]);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(1), "Any failure should give a non-zero status");
+        assert!(stderr.contains(":4:1: DELETE failed: Block not found"), "{}", stderr);
+        assert!(stderr.contains("Allocated block with ID 2"), "{}", stderr);
+    }
+
+    #[test]
+    fn test_without_a_file_reads_commands_from_stdin() {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_memory_manager"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("Failed to run binary");
+        child.stdin.take().unwrap().write_all(b"INSERT 8 \"a b\"\nREAD 0\nDELETE 3\n:quit\nDUMP\n").unwrap();
+        let output = child.wait_with_output().unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        assert!(stdout.contains("Allocated block with ID 0"), "{}", stdout);
+        assert!(stdout.contains("Block 0 contains"), "{}", stdout);
+        assert!(!stdout.contains("Memory Manager Dump"), "Should stop at :quit: {}", stdout);
+        assert!(stderr.contains("DELETE failed: Block not found"), "{}", stderr);
+        assert_eq!(output.status.code(), Some(1));
+    }
+
+    #[test]
+    fn test_test_mode_runs_a_directory() {
+        let dir = std::env::temp_dir().join(format!("mm-cli-tests-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a_pass.cmmd"), "LET a = INSERT 24 data3\nEXPECT READ $a == data3\nEXPECT ERROR INSERT 70000 x\n").unwrap();
+        std::fs::write(dir.join("b_fail.cmmd"), "INSERT 8 x\nEXPECT ALLOCATED_BLOCKS == 2\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a script").unwrap();
+
+        let output = Command::new(env!("CARGO_BIN_EXE_memory_manager")).arg("--test").arg(&dir).output().unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(output.status.code(), Some(1), "{}", stdout);
+        assert!(stdout.contains("a_pass.cmmd (2 expectations)"), "{}", stdout);
+        assert!(stdout.contains("FAIL "), "{}", stdout);
+        assert!(stdout.contains("+ actual:   ALLOCATED_BLOCKS = 1"), "{}", stdout);
+        assert!(stdout.contains("1 passed, 1 failed"), "{}", stdout);
+    }
+
+    #[test]
+    fn test_json_format_prints_one_object_per_command() {
+        let output = run_script("json", "INSERT 24 \"a b\"\nDUMP\nDELETE 4\n", &["--format", "json", "--keep-going"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(lines.len(), 3, "Nothing but JSON should reach stdout: {}", stdout);
+        assert!(lines.iter().all(|line| line.starts_with('{') && line.ends_with('}')), "{}", stdout);
+        assert!(lines[0].contains(r#""command":"INSERT","status":"ok","id":0"#), "{}", lines[0]);
+        assert!(lines[1].contains(r#""blocks":[{"type":"allocated","id":0,"start":0,"end":32,"size":32,"data":"a b""#), "{}", lines[1]);
+        assert!(lines[2].contains(r#""status":"error","error":""#), "{}", lines[2]);
+
+        let output = run_script("bad-format", "DUMP\n", &["--format", "xml"]);
+        assert_eq!(output.status.code(), Some(2));
+    }
+
+    #[test]
+    fn test_parse_subcommands_and_legacy_forms() {
+        let run = parse(&["run", "a.cmmd", "-", "--keep-going"]).unwrap();
+        let files = vec!["a.cmmd".to_string(), "-".to_string()];
+        assert_eq!(run.subcommand, Subcommand::Run(RunOptions { files, keep_going: true, ..RunOptions::default() }));
+        let files = vec!["a.cmmd".to_string()];
+        assert_eq!(parse(&["a.cmmd", "-i"]).unwrap().subcommand, Subcommand::Run(RunOptions { files, interactive: true, ..RunOptions::default() }));
+        assert_eq!(parse(&[]).unwrap().subcommand, Subcommand::Repl { record: None });
+        assert_eq!(parse(&["--test", "dir"]).unwrap().subcommand, Subcommand::Test { dir: "dir".into(), keep_going: false });
+        assert_eq!(parse(&["inspect", "heap.img"]).unwrap().subcommand, Subcommand::Inspect { image: "heap.img".into() });
+        assert_eq!(parse(&["-h"]).unwrap().subcommand, Subcommand::Help);
+        assert_eq!(
+            parse(&["report", "--html", "out.html", "a.cmmd"]).unwrap().subcommand,
+            Subcommand::Report { file: "a.cmmd".into(), html: "out.html".into(), keep_going: false }
+        );
+        assert!(parse(&["report", "a.cmmd"]).is_err(), "--html is required");
+        assert!(parse(&["run", "a.cmmd", "--html", "out.html"]).is_err());
+        let run = parse(&["run", "a.cmmd", "--chrome-trace", "run.json"]).unwrap();
+        let files = vec!["a.cmmd".to_string()];
+        assert_eq!(run.subcommand, Subcommand::Run(RunOptions { files, chrome_trace: Some("run.json".into()), ..RunOptions::default() }));
+        assert!(parse(&["repl", "--chrome-trace", "run.json"]).is_err());
+        let run = parse(&["run", "a.cmmd", "--journal", "state"]).unwrap();
+        let files = vec!["a.cmmd".to_string()];
+        assert_eq!(run.subcommand, Subcommand::Run(RunOptions { files, journal: Some("state".into()), ..RunOptions::default() }));
+        assert!(parse(&["test", "dir", "--journal", "state"]).is_err());
+
+        // The old positional strategy still works, but not together with --strategy
+        let replay = parse(&["replay", "run.trace", "best-fit"]).unwrap();
+        assert_eq!(replay.subcommand, Subcommand::Replay { trace: "run.trace".into() });
+        assert_eq!(replay.options.strategy, Some(PlacementStrategy::BestFit));
+        assert!(parse(&["--strategy", "worst-fit", "replay", "run.trace", "best-fit"]).is_err());
+
+        // Options after `bench` belong to the benchmark
+        let bench = parse(&["-q", "bench", "--ops", "10", "-q"]).unwrap();
+        assert_eq!(bench.subcommand, Subcommand::Bench { args: vec!["--ops".into(), "10".into(), "-q".into()] });
+        assert_eq!(bench.options.verbosity, Verbosity::Quiet);
+    }
+
+    #[test]
+    fn test_parse_global_options() {
+        let cli = parse(&["repl", "--arena-size", "4K", "--min-block", "16", "--strategy", "best-fit", "--format", "json", "-v"]).unwrap();
+        let config = cli.options.config(PlacementStrategy::FirstFit).unwrap();
+        assert_eq!((config.arena_size, config.min_block, config.strategy), (4096, 16, PlacementStrategy::BestFit));
+        assert_eq!((cli.options.format, cli.options.verbosity), (Format::Json, Verbosity::Verbose));
+        assert!(!config.block_headers);
+        assert!(parse(&["--block-headers", "repl"]).unwrap().options.config(PlacementStrategy::FirstFit).unwrap().block_headers);
+
+        assert_eq!(parse_size("1M"), Ok(1 << 20));
+        assert!(parse_size("12Q").is_err());
+        assert!(parse(&["--arena-size", "3000", "repl"]).unwrap().options.config(PlacementStrategy::FirstFit).is_err());
+        assert!(parse(&["--format", "xml"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+        assert!(parse(&["run"]).is_err());
+        assert!(parse(&["replay", "a", "b", "c"]).is_err());
+        assert!(parse(&["replay", "run.trace", "--record", "x"]).is_err());
+        assert!(parse(&["--test", "dir", "a.cmmd"]).is_err());
+        assert!(parse(&["repl", "--step"]).is_err());
+        assert!(parse(&["run", "a.cmmd", "--until", "x:y"]).is_err());
+        assert!(parse(&["run", "a.cmmd", "--snapshot-every", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_explain_narrates_each_command() {
+        let output = run_script("explain", "INSERT 20 a\nDELETE 0\n", &["--arena-size", "128", "--min-block", "16", "--explain", "-q"]);
+        assert_eq!(output.status.code(), Some(0));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.starts_with("allocate 32 bytes: order 5 (32 bytes)\n  chose free block 0x0000 of order 7 (128 bytes) by first-fit\n"), "{}", stdout);
+        assert!(stdout.contains("  buddy 0x0040 of order 6 is free: merge into 0x0000 (order 7)\n"), "{}", stdout);
+        assert!(stdout.ends_with("  buddy tree:\n    0x0000 - 0x0080 order 7 free\n"), "{}", stdout);
+    }
+
+    #[test]
+    fn test_run_subcommand_shares_one_manager_across_files_and_stdin() {
+        let first = std::env::temp_dir().join(format!("mm-cli-first-{}.cmmd", std::process::id()));
+        std::fs::write(&first, "LET a = INSERT 100 one\n").unwrap();
+        let mut child = Command::new(env!("CARGO_BIN_EXE_memory_manager"))
+            .args(["--arena-size", "1K", "--min-block", "64", "-q", "run"])
+            .arg(&first)
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("Failed to run binary");
+        child.stdin.take().unwrap().write_all(b"INSERT 3 x\nEXPECT READ $a == one\nDUMP\n").unwrap();
+        let output = child.wait_with_output().unwrap();
+        let _ = std::fs::remove_file(&first);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        assert_eq!(output.status.code(), Some(0), "{}", stderr);
+        assert!(stderr.is_empty(), "Quiet should only print errors: {}", stderr);
+        assert!(stdout.starts_with("Memory Manager Dump:"), "{}", stdout);
+        assert!(stdout.contains("End: 0x0080, Size: 128"), "{}", stdout);
+        assert!(stdout.contains("End: 0x00C0, Size: 64"), "Small requests should be rounded up to --min-block: {}", stdout);
+        assert!(stdout.contains("End: 0x0400"), "{}", stdout);
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let binary = env!("CARGO_BIN_EXE_memory_manager");
+        let status = |args: &[&str]| Command::new(binary).args(args).output().unwrap().status.code();
+        assert_eq!(status(&["--help"]), Some(0));
+        assert_eq!(status(&["run", "/nonexistent/file.cmmd"]), Some(2));
+        assert_eq!(status(&["--min-block", "3", "repl"]), Some(2));
+        assert_eq!(status(&["frobnicate", "--now"]), Some(2));
+        assert_eq!(status(&["bench", "--ops", "50", "--strategy", "best-fit"]), Some(0));
+        assert_eq!(status(&["bench", "--arena-size", "1000"]), Some(2));
+    }
+
+    #[test]
+    fn test_report_writes_html_even_when_a_command_fails() {
+        let script = std::env::temp_dir().join(format!("mm-cli-report-{}.cmmd", std::process::id()));
+        let html = script.with_extension("html");
+        std::fs::write(&script, "INSERT 20 a\nDELETE 3\n").unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_memory_manager")).arg("report").arg("--html").arg(&html).arg(&script).output().unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let page = std::fs::read_to_string(&html).expect("The report should be written");
+        let _ = std::fs::remove_file(&script);
+        let _ = std::fs::remove_file(&html);
+        assert_eq!(page.matches("<section class=\"step\"").count(), 3);
+        assert!(page.contains("DELETE failed: Block not found"));
+    }
+
+    #[test]
+    fn test_step_until_and_snapshots() {
+        let script = "INSERT 10 hi\nINSERT 20 there\nDELETE 0\nDELETE 1\n";
+
+        // Without a terminal, --step shows each change without pausing
+        let output = run_script("step", script, &["--step", "-q"]);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(0), "{}", stderr);
+        assert!(stderr.contains("split free 0x0000 - 0x10000 into 2 blocks\n  + block 0 at 0x0000 - 0x0010 (16 bytes)"), "{}", stderr);
+        assert!(stderr.contains("merged 3 blocks into free 0x0000 - 0x10000"), "{}", stderr);
+
+        // --until hands the manager to the prompt, which reads stdin here
+        let path = std::env::temp_dir().join(format!("mm-cli-until-{}.cmmd", std::process::id()));
+        std::fs::write(&path, script).unwrap();
+        let mut child = Command::new(env!("CARGO_BIN_EXE_memory_manager"))
+            .arg(&path)
+            .args(["--until", "3", "-q"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("Failed to run binary");
+        child.stdin.take().unwrap().write_all(b"STATS\n").unwrap();
+        let output = child.wait_with_output().unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Stopped before"), "{:?}", output);
+        assert!(stdout.contains("Allocated blocks: 2"), "Both INSERTs should have run, neither DELETE: {}", stdout);
+
+        let output = run_script("until-missing", script, &["--until", "99"]);
+        assert_eq!(output.status.code(), Some(1));
+
+        let dir = std::env::temp_dir().join(format!("mm-cli-snapshots-{}", std::process::id()));
+        let output = run_script("snapshots", script, &["--snapshot-every", "2", "--snapshot-dir", dir.to_str().unwrap()]);
+        assert_eq!(output.status.code(), Some(0));
+        let second = std::fs::read_to_string(dir.join("snapshot-000002.txt")).unwrap_or_default();
+        let fourth = std::fs::read_to_string(dir.join("snapshot-000004.txt")).unwrap_or_default();
+        let image = dir.join("snapshot-000002.img");
+        let inspected = Command::new(env!("CARGO_BIN_EXE_memory_manager")).args(["inspect", image.to_str().unwrap()]).output().expect("Failed to run binary");
+        let loaded = run_script("snapshot-load", &format!("LOAD {}\nREAD 1\n", image.display()), &[]);
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_file(&path);
+        assert!(second.starts_with("# After 2 commands, at ") && second.contains("Allocated Block ID: 1"), "{}", second);
+        assert!(fourth.contains("Block 0: Free Block, Start: 0x0000, End: 0x10000"), "{}", fourth);
+
+        // The image beside each map holds the whole state, block data included
+        let stdout = String::from_utf8_lossy(&inspected.stdout);
+        assert_eq!(inspected.status.code(), Some(0), "{:?}", inspected);
+        assert!(stdout.contains("Allocated blocks: 2"), "{}", stdout);
+        let stderr = String::from_utf8_lossy(&loaded.stderr);
+        assert_eq!(loaded.status.code(), Some(0), "{}", stderr);
+        assert!(stderr.contains("Loaded 2 blocks") && stderr.contains("Block 1 contains AllocatedBlock { id: 1, start: 32, end: 64"), "{}", stderr);
+    }
+
+    #[test]
+    fn test_save_load_and_inspect_an_image() {
+        let image = std::env::temp_dir().join(format!("mm-cli-image-{}.img", std::process::id()));
+        let output = run_script("save", &format!("INSERT 24 data1\nINSERT 100 data2\nSAVE {}\n", image.display()), &[]);
+        assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Saved 2 blocks to "));
+
+        // A later run picks up where the first one stopped
+        let output = run_script("load", &format!("LOAD {}\nREAD 1\nINSERT 8 x\n", image.display()), &[]);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(0), "{}", stderr);
+        assert!(stderr.contains("Block 1 contains") && stderr.contains("Allocated block with ID 2"), "{}", stderr);
+
+        let output = Command::new(env!("CARGO_BIN_EXE_memory_manager")).args(["inspect", image.to_str().unwrap()]).output().expect("Failed to run binary");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(output.status.code(), Some(0));
+        assert!(stdout.contains("Arena of 65536 bytes, minimum block size 1, first-fit; next ID 2"), "{}", stdout);
+        assert!(stdout.contains("Allocated Block ID: 1") && stdout.contains("Allocated blocks: 2"), "{}", stdout);
+
+        let mut bytes = std::fs::read(&image).unwrap();
+        bytes[100] ^= 0xFF;
+        std::fs::write(&image, bytes).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_memory_manager")).args(["inspect", image.to_str().unwrap()]).output().expect("Failed to run binary");
+        let _ = std::fs::remove_file(&image);
+        assert_eq!(output.status.code(), Some(2));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("checksum mismatch"));
+    }
+}
@@ -0,0 +1,95 @@
+#[cfg(test)]
+mod tests {
+    use memory_manager::differential::{run_ops, run_random, shrink, shrink_by, DiffOp, Failure};
+    use memory_manager::parser::parse_script;
+    use memory_manager::Command;
+
+    // MM_DIFF_SEEDS and MM_DIFF_STEPS widen the search locally, e.g. MM_DIFF_SEEDS=1000
+    fn env_or(name: &str, default: u64) -> u64 {
+        std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+    }
+
+    #[test]
+    fn test_manager_agrees_with_reference_model() {
+        let seeds = env_or("MM_DIFF_SEEDS", 20);
+        let steps = env_or("MM_DIFF_STEPS", 300) as usize;
+
+        for seed in 0..seeds {
+            if let Err(failure) = run_random(seed, steps) {
+                let path = std::env::temp_dir().join(format!("differential-seed-{}.cmmd", seed));
+                std::fs::write(&path, failure.to_command_file()).expect("Failed to write reproduction");
+                panic!("Seed {} diverged from the model; reproduction written to {}\n{}", seed, path.display(), failure);
+            }
+        }
+    }
+
+    #[test]
+    fn test_errors_for_unknown_ids_are_expected() {
+        let ops = vec![
+            DiffOp::Insert { size: 16, data: b"abc".to_vec() },
+            DiffOp::Delete { id: 0 },
+            DiffOp::Delete { id: 0 },
+            DiffOp::Update { id: 7, data: b"x".to_vec() },
+        ];
+        assert_eq!(run_ops(&ops), Ok(()), "Errors for unknown IDs are expected results");
+    }
+
+    #[test]
+    fn test_shrink_keeps_passing_sequences() {
+        let ops = vec![
+            DiffOp::Insert { size: 100, data: b"hello".to_vec() },
+            DiffOp::Update { id: 0, data: vec![b'y'; 200] },
+        ];
+        assert_eq!(shrink(ops.clone()), ops);
+    }
+
+    #[test]
+    fn test_shrink_minimizes_an_injected_divergence() {
+        // Pretend the manager diverges on an update of more than 4 bytes once something was inserted
+        let fails_at = |ops: &[DiffOp]| {
+            ops.iter().position(|op| matches!(op, DiffOp::Update { data, .. } if data.len() > 4))
+                .filter(|&i| ops[..i].iter().any(|op| matches!(op, DiffOp::Insert { .. })))
+        };
+        let ops = vec![
+            DiffOp::Insert { size: 16, data: b"abcdefgh".to_vec() },
+            DiffOp::Insert { size: 8, data: b"zz".to_vec() },
+            DiffOp::Delete { id: 1 },
+            DiffOp::Update { id: 0, data: b"0123456789abcdefghijklmnopqrstuvwxyz0123".to_vec() },
+            DiffOp::Delete { id: 0 },
+        ];
+
+        assert_eq!(
+            shrink_by(ops, fails_at),
+            vec![DiffOp::Insert { size: 8, data: b"z".to_vec() }, DiffOp::Update { id: 0, data: b"01234".to_vec() }],
+            "Keeps one insert and the update, with the update's data halved from 40 to 5 bytes"
+        );
+    }
+
+    #[test]
+    fn test_command_file_replays_the_exact_bytes() {
+        // Data that reads as hex when bare, bytes that are not UTF-8, quotes, spaces and nothing at all
+        let ops = vec![
+            DiffOp::Insert { size: 8, data: b"0x12".to_vec() },
+            DiffOp::Insert { size: 8, data: vec![0xff, 0x00, b'"', b' ', b'#'] },
+            DiffOp::Update { id: 1, data: Vec::new() },
+            DiffOp::Delete { id: 0 },
+        ];
+        let failure = Failure { ops: ops.clone(), message: String::new() };
+
+        let commands: Vec<Command> = parse_script("repro.cmmd", &failure.to_command_file())
+            .into_iter()
+            .map(|result| result.expect("Every line should parse").1)
+            .collect();
+        let replayed: Vec<DiffOp> = commands[..commands.len() - 1]
+            .iter()
+            .map(|command| match command {
+                Command::Insert { size, data } => DiffOp::Insert { size: *size, data: data.clone() },
+                Command::Update { id, data } => DiffOp::Update { id: *id, data: data.clone() },
+                Command::Delete { id } => DiffOp::Delete { id: *id },
+                other => panic!("Unexpected command {:?}", other),
+            })
+            .collect();
+        assert_eq!(replayed, ops);
+        assert_eq!(commands.last(), Some(&Command::Dump));
+    }
+}
@@ -0,0 +1,66 @@
+#[cfg(test)]
+mod tests {
+    use memory_manager::MemoryManager;
+
+    #[test]
+    fn test_profiler_tracks_sites_size_classes_and_peak() {
+        let mut memory_manager = MemoryManager::new();
+        memory_manager.enable_profiling();
+
+        let a = memory_manager.insert(100, vec![1; 100]).expect("Insertion should succeed");
+        let b = memory_manager.insert(20, vec![2; 20]).expect("Insertion should succeed");
+        memory_manager.delete(a).expect("Deletion should succeed");
+        memory_manager.update(b, vec![3; 40]).expect("Update should succeed");
+        assert!(memory_manager.insert(100_000, vec![]).is_err());
+
+        let profiler = memory_manager.profiler().expect("Profiling should be enabled");
+        assert_eq!(profiler.peak_bytes(), 128 + 32);
+        assert_eq!(profiler.live_bytes(), 64, "Only the reallocated block should be live");
+
+        let classes = profiler.size_classes();
+        assert_eq!(classes[&128].allocations, 1);
+        assert_eq!(classes[&128].frees, 1);
+        assert_eq!(classes[&32].live_blocks, 0, "Update reallocated the 32-byte block");
+        assert_eq!(classes[&64].live_bytes, 64);
+
+        let sites = profiler.sites();
+        assert_eq!(sites.len(), 3, "Two inserts and the update are separate call sites");
+        assert!(sites.keys().all(|site| site.starts_with("tests/heap_profiler.rs:")));
+
+        let timeline: Vec<(&str, usize)> = profiler.timeline().iter()
+            .map(|sample| (sample.operation, sample.live_bytes))
+            .collect();
+        assert_eq!(timeline, vec![
+            ("insert", 128),
+            ("insert", 160),
+            ("delete", 32),
+            ("update", 64),
+            ("insert", 64),
+        ]);
+    }
+
+    #[test]
+    fn test_profiler_reports() {
+        let mut memory_manager = MemoryManager::new();
+        memory_manager.enable_profiling();
+        for size in [8, 8, 16] {
+            memory_manager.insert(size, vec![0; size]).expect("Insertion should succeed");
+        }
+
+        let profiler = memory_manager.take_profiler().expect("Profiling should be enabled");
+        assert!(memory_manager.profiler().is_none());
+
+        let report = profiler.report();
+        assert!(report.contains("3 operations, 3 live blocks, 32 live bytes, peak 32 bytes"), "{}", report);
+
+        let stacks = profiler.collapsed_stacks();
+        let lines: Vec<&str> = stacks.lines().collect();
+        assert_eq!(lines.len(), 2, "One line per (site, size class): {}", stacks);
+        for line in lines {
+            let (stack, weight) = line.rsplit_once(' ').expect("Each line should end with a weight");
+            assert!(stack.starts_with("tests/heap_profiler.rs:"));
+            assert!(stack.ends_with(";8B") || stack.ends_with(";16B"));
+            assert!(weight == "16");
+        }
+    }
+}
@@ -0,0 +1,138 @@
+#[cfg(test)]
+mod tests {
+    use memory_manager::{ImageBlock, ManagerConfig, MemoryImage, MemoryManager, PlacementStrategy};
+
+    fn manager() -> MemoryManager {
+        let config = ManagerConfig { arena_size: 1024, min_block: 16, strategy: PlacementStrategy::BestFit, block_headers: false };
+        let mut memory_manager = MemoryManager::with_config(config).unwrap();
+        memory_manager.set_verbose(false);
+        memory_manager.insert(20, b"first".to_vec()).unwrap();
+        memory_manager.insert(100, b"second".to_vec()).unwrap();
+        memory_manager.insert(8, b"third".to_vec()).unwrap();
+        memory_manager.delete(1).unwrap();
+        memory_manager.resize(2, 40).unwrap();
+        memory_manager
+    }
+
+    #[test]
+    fn test_image_round_trips_the_whole_manager() {
+        let mut original = manager();
+        let bytes = original.to_image().encode();
+        let mut loaded = MemoryManager::from_image(MemoryImage::decode(&bytes).unwrap()).unwrap();
+        loaded.set_verbose(false);
+
+        assert_eq!(loaded.config(), original.config());
+        assert_eq!(loaded.iter_all().collect::<Vec<_>>(), original.iter_all().collect::<Vec<_>>());
+        assert_eq!(loaded.allocator().free_blocks(), original.allocator().free_blocks());
+        assert_eq!(loaded.get_buffer(), original.get_buffer());
+        assert_eq!(loaded.to_image().encode(), bytes, "Saving a loaded image gives the same bytes");
+
+        // Both go on to make the same decisions, starting from the same next ID
+        for memory_manager in [&mut original, &mut loaded] {
+            assert_eq!(memory_manager.insert(64, b"fourth".to_vec()), Ok(3));
+            memory_manager.delete(0).unwrap();
+        }
+        assert_eq!(loaded.iter_all().collect::<Vec<_>>(), original.iter_all().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_save_and_load_files() {
+        let path = std::env::temp_dir().join(format!("mm-image-{}.img", std::process::id()));
+        let path = path.to_str().unwrap();
+        let original = manager();
+        original.save(path).unwrap();
+
+        let mut other = MemoryManager::with_strategy(PlacementStrategy::FirstFit);
+        other.set_verbose(false);
+        other.insert(8, b"gone".to_vec()).unwrap();
+        other.load_from(path).unwrap();
+        let _ = std::fs::remove_file(path);
+        assert_eq!(other.config(), original.config());
+        assert_eq!(other.iter_all().collect::<Vec<_>>(), original.iter_all().collect::<Vec<_>>());
+        assert_eq!(other.get_data(other.find(2).unwrap()), b"third");
+
+        let error = MemoryManager::load("/nonexistent/heap.img").err().unwrap();
+        assert!(error.starts_with("Failed to read image /nonexistent/heap.img"), "{}", error);
+    }
+
+    #[test]
+    fn test_rejects_corrupt_images() {
+        let bytes = manager().to_image().encode();
+        assert_eq!(MemoryImage::decode(b"not an image"), Err("not a memory image".to_string()));
+
+        let mut version = bytes.clone();
+        version[8] = 9;
+        assert_eq!(MemoryImage::decode(&version), Err("unsupported image version 9 (expected at most 2)".to_string()));
+
+        // Every single-byte change and every truncation past the header is caught
+        for i in 12..bytes.len() {
+            let mut flipped = bytes.clone();
+            flipped[i] ^= 0x01;
+            assert!(MemoryImage::decode(&flipped).is_err(), "Flipped byte {} went unnoticed", i);
+            assert!(MemoryImage::decode(&bytes[..i]).is_err(), "Truncation to {} bytes went unnoticed", i);
+        }
+    }
+
+    #[test]
+    fn test_rejects_inconsistent_state() {
+        let image = manager().to_image();
+
+        let mut overlapping = image.clone();
+        overlapping.blocks[1].start -= 16;
+        assert!(MemoryManager::from_image(overlapping).err().unwrap_or_default().contains("gap or overlap"));
+
+        let mut repeated = image.clone();
+        let first_id = repeated.blocks[0].id;
+        repeated.blocks.iter_mut().filter(|block| block.id.is_some()).for_each(|block| block.id = first_id);
+        assert!(MemoryManager::from_image(repeated).err().unwrap_or_default().contains("is repeated"));
+
+        let mut unmerged = image.clone();
+        let (start, end) = unmerged.free.pop().unwrap();
+        let half = start + (end - start) / 2;
+        unmerged.free.extend([(start, half), (half, end)]);
+        assert!(MemoryManager::from_image(unmerged).err().unwrap_or_default().contains("were not merged"));
+
+        let mut stale = image.clone();
+        stale.arena[0] ^= 0xFF;
+        assert_eq!(MemoryManager::from_image(stale).err(), Some("Data of block 0 does not match the arena".to_string()));
+    }
+
+    #[test]
+    fn test_rejects_blocks_past_the_arena() {
+        // A block that claims more room than the arena has, with data to match
+        let config = ManagerConfig { arena_size: 64, min_block: 16, strategy: PlacementStrategy::FirstFit, block_headers: false };
+        let image = MemoryImage {
+            config,
+            next_id: 1,
+            free: Vec::new(),
+            allocated: vec![(0, 64)],
+            blocks: vec![ImageBlock { id: Some(0), start: 0, end: 1000, requested: 200, data: vec![7; 200] }],
+            arena: vec![0; 64],
+        };
+        assert_eq!(
+            MemoryManager::from_image(image).err(),
+            Some("Block at 0x0000 ends at 0x03E8, past the 64-byte arena".to_string())
+        );
+    }
+
+    #[test]
+    fn test_load_keeps_leak_sites_and_refuses_while_recording() {
+        let path = std::env::temp_dir().join(format!("mm-image-sites-{}.img", std::process::id()));
+        let path = path.to_str().unwrap();
+        manager().save(path).unwrap();
+
+        let mut memory_manager = MemoryManager::with_strategy(PlacementStrategy::FirstFit);
+        memory_manager.set_verbose(false);
+        memory_manager.load_from(path).unwrap();
+        let report = memory_manager.leak_report();
+        assert_eq!(report.entries.len(), 1, "Loaded blocks share the site of the load");
+        assert!(report.entries[0].location.starts_with(file!()), "{}", report.entries[0].location);
+        assert_eq!(report.entries[0].ids, vec![0, 2]);
+
+        memory_manager.start_recording();
+        let error = memory_manager.load_from(path).err();
+        let _ = std::fs::remove_file(path);
+        assert_eq!(error, Some("Cannot load an image while recording, profiling or tracing".to_string()));
+        assert!(memory_manager.take_trace().unwrap().entries.is_empty(), "Nothing was recorded");
+    }
+}
@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod tests {
-    use memory_manager::{MemoryManager, BuddyAllocator};
+    use memory_manager::memory_manager::MemoryBlock;
+    use memory_manager::{MemoryManager, BlockInfo, BuddyAllocator, ManagerConfig, PlacementStrategy, SearchMatch};
 
     #[test]
     fn test_memory_manager_initialization() {
@@ -57,6 +58,35 @@ mod tests {
         assert!(memory_manager.find(id).is_err(), "Block should not be findable after deletion");
     }
 
+    #[test]
+    fn test_update_returns_the_block_id_after_a_move() {
+        let mut memory_manager = MemoryManager::new();
+        let id = memory_manager.insert(8, vec![1; 8]).expect("Insertion should succeed");
+        memory_manager.insert(8, vec![9; 8]).expect("Insertion should succeed");
+
+        // Data that fits is written in place and keeps the ID
+        assert_eq!(memory_manager.update(id, vec![2; 4]), Ok(id));
+
+        // Data that outgrows the block moves it, and only the returned ID finds it again
+        let moved = memory_manager.update(id, vec![3; 32]).expect("Update should succeed");
+        assert_ne!(moved, id);
+        assert!(memory_manager.find(id).is_err(), "The old ID is released by the move");
+        let block = memory_manager.find(moved).expect("Block should be found under its new ID");
+        assert_eq!(memory_manager.get_data(block), &[3; 32][..]);
+    }
+
+    #[test]
+    fn test_block_data_is_kept_as_written() {
+        let mut memory_manager = MemoryManager::new();
+        memory_manager.set_verbose(false);
+        let id = memory_manager.insert(5, vec![1, 2, 3, 4, 5]).expect("Insertion should succeed");
+
+        // The block is rounded up to 8 bytes, but its data stays the 5 bytes written
+        let block = memory_manager.find(id).expect("Block should be found");
+        assert_eq!(memory_manager.get_data(block), &[1, 2, 3, 4, 5][..]);
+        assert_eq!(memory_manager.get_buffer_slice(block.start, block.end), &[1, 2, 3, 4, 5, 0, 0, 0][..]);
+    }
+
     #[test]
     fn test_memory_allocation_strategy() {
         let mut memory_manager = MemoryManager::new();
@@ -77,6 +107,18 @@ mod tests {
         
         // Verify free block merging
         memory_manager.dump(); // This will help visualize the memory state
+
+        // Blocks are rounded to 128, 256, 512, 512 and 512 bytes and sit at 0, 256, 512, 1024 and 1536
+        let stats = memory_manager.stats();
+        assert_eq!(stats.used_bytes, 128 + 512 + 512, "Only the remaining blocks should count as used");
+        assert_eq!(stats.allocated_blocks, 3);
+        // 128 at 128, 256 at 256, 512 at 1024, and the 2K to 32K halves left over from splitting
+        assert_eq!(stats.free_blocks, 8, "Freed blocks cannot merge while their buddies are in use");
+        assert_eq!(stats.largest_free, 32768);
+
+        // Freed space should be reused
+        let id = memory_manager.insert(200, vec![0; 200]).expect("Insertion should succeed");
+        assert_eq!(memory_manager.find(id).expect("Block should be found").start, 256);
     }
 
     #[test]
@@ -95,4 +137,178 @@ mod tests {
         
         assert!(block3 <= block1, "Reallocation should use previously freed space");
     }
+
+    #[test]
+    fn test_manager_config() {
+        let config = ManagerConfig { arena_size: 1024, min_block: 64, strategy: PlacementStrategy::BestFit, block_headers: false };
+        let mut memory_manager = MemoryManager::with_config(config).expect("Config should be valid");
+        assert_eq!(memory_manager.get_buffer().len(), 1024);
+        assert_eq!(memory_manager.config(), config);
+
+        // Small requests are rounded up to the minimum block, and the arena size bounds allocation
+        let id = memory_manager.insert(3, b"abc".to_vec()).expect("Insertion should succeed");
+        assert_eq!(memory_manager.find(id).expect("Block should be found").size(), 64);
+        assert!(memory_manager.insert(1024, Vec::new()).is_err(), "Only 960 bytes are left");
+
+        assert!(MemoryManager::with_config(ManagerConfig { arena_size: 1000, ..config }).is_err());
+        assert!(MemoryManager::with_config(ManagerConfig { min_block: 24, ..config }).is_err());
+        assert!(MemoryManager::with_config(ManagerConfig { min_block: 2048, ..config }).is_err());
+    }
+
+    // A quiet first-fit manager with a 128-byte arena and 16-byte minimum blocks
+    fn small_arena() -> MemoryManager {
+        let config = ManagerConfig { arena_size: 128, min_block: 16, strategy: PlacementStrategy::FirstFit, block_headers: false };
+        let mut memory_manager = MemoryManager::with_config(config).expect("Config should be valid");
+        memory_manager.set_verbose(false);
+        memory_manager
+    }
+
+    // `small_arena` with block 0 ("a", 32 bytes) at 0x00 and block 1 ("b", 16 bytes) at 0x20,
+    // leaving 0x30 - 0x40 and 0x40 - 0x80 free
+    fn two_blocks() -> (MemoryManager, usize, usize) {
+        let mut memory_manager = small_arena();
+        let first = memory_manager.insert(32, b"a".to_vec()).expect("Insertion should succeed");
+        let second = memory_manager.insert(16, b"b".to_vec()).expect("Insertion should succeed");
+        (memory_manager, first, second)
+    }
+
+    #[test]
+    fn test_render_map() {
+        assert!(small_arena().render_map(8).starts_with("........\n"), "An untouched arena is all unsplit free");
+
+        let (mut memory_manager, first, _) = two_blocks();
+        let map = memory_manager.render_map(8);
+        let lines: Vec<&str> = map.lines().collect();
+        assert_eq!(lines[0], "0#1-----", "IDs are written over the blocks they fit in");
+        assert_eq!(lines[1], "|       |");
+        assert_eq!(lines[2], "0x0000  0x0080");
+        assert!(lines[3].ends_with("each cell is 16 bytes"));
+
+        // Cells holding both allocated and free bytes are marked as such
+        assert!(memory_manager.render_map(2).starts_with("%-\n"));
+
+        // A freed block cannot merge while its buddy is split, so it shows as split free space
+        memory_manager.delete(first).expect("Deletion should succeed");
+        assert!(memory_manager.render_map(8).starts_with("--1-----\n"));
+    }
+
+    #[test]
+    fn test_to_dot_labels_blocks_by_id() {
+        let (mut memory_manager, first, _) = two_blocks();
+        let dot = memory_manager.to_dot();
+        assert!(dot.contains("b0_5 [label=\"0x0000 - 0x0020\\norder 5\\nallocated\\nblock 0\""), "{}", dot);
+        assert!(dot.contains("b32_4 [label=\"0x0020 - 0x0030\\norder 4\\nallocated\\nblock 1\""), "{}", dot);
+        assert!(dot.contains("subgraph cluster_32_4 { label=\"buddies\"; style=dashed; b32_4; b48_4; }"), "{}", dot);
+        assert!(dot.contains("b48_4 [label=\"0x0030 - 0x0040\\norder 4\\nfree\", fillcolor=palegreen];"), "{}", dot);
+
+        // Freeing block 0 leaves its parent split, as the other half is still split too
+        memory_manager.delete(first).expect("Deletion should succeed");
+        let dot = memory_manager.to_dot();
+        assert!(dot.contains("b0_5 [label=\"0x0000 - 0x0020\\norder 5\\nfree\""), "{}", dot);
+        assert!(dot.contains("b0_6 [label=\"0x0000 - 0x0040\\norder 6\\nsplit\""), "{}", dot);
+    }
+
+    #[test]
+    fn test_hexdump_and_search() {
+        let (mut memory_manager, first, second) = two_blocks();
+        assert_eq!(memory_manager.update(first, b"hello, world".to_vec()), Ok(first));
+        assert_eq!(memory_manager.update(second, vec![0xde, 0xad, 0xbe, 0xef]), Ok(second));
+
+        assert_eq!(
+            memory_manager.hexdump_block(second),
+            Ok("-- block 1 at 0x0020 - 0x0030 (16 bytes)\n00000020: dead beef 0000 0000 0000 0000 0000 0000  ................\n".to_string())
+        );
+        let dump = memory_manager.hexdump(0, 0x80).expect("Range should be valid");
+        assert!(dump.starts_with("-- block 0 at 0x0000 - 0x0020 (32 bytes)\n00000000: 6865 6c6c 6f2c 2077 6f72 6c64 0000 0000  hello, world....\n"));
+        assert!(dump.ends_with("-- free at 0x0030 - 0x0080 (80 bytes)\n00000030: 0000 0000 0000 0000 0000 0000 0000 0000  ................\n*\n00000070: 0000 0000 0000 0000 0000 0000 0000 0000  ................\n"));
+        assert!(memory_manager.hexdump(0x40, 0x20).is_err());
+        assert!(memory_manager.hexdump(0, 0x100).is_err());
+
+        // A range cut part-way through rows starts a new heading at each block it crosses
+        assert_eq!(
+            memory_manager.hexdump(0x18, 0x38),
+            Ok(concat!(
+                "-- block 0 at 0x0000 - 0x0020 (32 bytes)\n",
+                "00000018: 0000 0000 0000 0000                      ........\n",
+                "-- block 1 at 0x0020 - 0x0030 (16 bytes)\n",
+                "00000020: dead beef 0000 0000 0000 0000 0000 0000  ................\n",
+                "-- free at 0x0030 - 0x0080 (80 bytes)\n",
+                "00000030: 0000 0000 0000 0000                      ........\n",
+            )
+            .to_string())
+        );
+
+        // A match that runs from one block into the next is not inside any live block
+        assert_eq!(memory_manager.search(&[0x00, 0xde], false), vec![SearchMatch { address: 0x1f, id: Some(first), offset: 31 }]);
+        assert!(memory_manager.search(&[0x00, 0xde], true).is_empty());
+
+        // A freed block's bytes stay in the arena, so only a search of the whole arena finds them
+        memory_manager.delete(first).expect("Deletion should succeed");
+        assert_eq!(memory_manager.search(b"world", false), vec![SearchMatch { address: 7, id: None, offset: 7 }]);
+        assert!(memory_manager.search(b"world", true).is_empty());
+        assert_eq!(memory_manager.search(&[0xbe, 0xef], true), vec![SearchMatch { address: 0x22, id: Some(second), offset: 2 }]);
+    }
+
+    #[test]
+    fn test_block_at_and_blocks_in_range() {
+        let (memory_manager, first, second) = two_blocks();
+
+        let (block, offset) = memory_manager.block_at(0x24).expect("Address is in the arena");
+        assert_eq!((block.start(), block.end(), offset), (0x20, 0x30, 4));
+        assert!(matches!(block, MemoryBlock::Allocated(allocated) if allocated.id == second));
+
+        // Either side of a boundary belongs to a different block
+        let (block, offset) = memory_manager.block_at(0x1f).expect("Address is in the arena");
+        assert!(matches!(block, MemoryBlock::Allocated(allocated) if allocated.id == first));
+        assert_eq!(offset, 31);
+        let (block, offset) = memory_manager.block_at(0x20).expect("Address is in the arena");
+        assert!(matches!(block, MemoryBlock::Allocated(allocated) if allocated.id == second));
+        assert_eq!(offset, 0);
+        let (block, offset) = memory_manager.block_at(0x30).expect("Address is in the arena");
+        assert!(matches!(block, MemoryBlock::Free(_)));
+        assert_eq!(offset, 0);
+        assert_eq!(memory_manager.block_at(0x7f).map(|(block, offset)| (block.start(), offset)), Some((0x30, 0x4f)));
+        assert!(memory_manager.block_at(0x80).is_none());
+
+        let starts: Vec<usize> = memory_manager.blocks_in_range(0x10, 0x31).iter().map(|block| block.start()).collect();
+        assert_eq!(starts, vec![0, 0x20, 0x30]);
+        let starts: Vec<usize> = memory_manager.blocks_in_range(0x10, 0x30).iter().map(|block| block.start()).collect();
+        assert_eq!(starts, vec![0, 0x20], "A range ending on a block's end stops at that block");
+        assert_eq!(memory_manager.blocks_in_range(0x20, 0x30).len(), 1, "The end is exclusive");
+        assert!(memory_manager.blocks_in_range(0x30, 0x30).is_empty());
+        assert!(matches!(memory_manager.blocks_in_range(0, 1), [MemoryBlock::Allocated(allocated)] if allocated.id == first));
+    }
+
+    #[test]
+    fn test_block_iterators() {
+        let (mut memory_manager, first, second) = two_blocks();
+        let third = memory_manager.insert(3, b"c".to_vec()).expect("Insertion should succeed");
+        memory_manager.resize(first, 40).expect("Resize should succeed");
+        // With block 1 gone, first fit puts the new block in the space block 0 moved out of
+        memory_manager.delete(second).expect("Deletion should succeed");
+        let fourth = memory_manager.insert(16, b"dd".to_vec()).expect("Insertion should succeed");
+
+        let allocated: Vec<BlockInfo> = memory_manager.iter_allocated().collect();
+        assert_eq!(
+            allocated,
+            vec![
+                BlockInfo { id: Some(fourth), start: 0x00, end: 0x10, requested: 16, data_len: 2 },
+                BlockInfo { id: Some(third), start: 0x30, end: 0x40, requested: 3, data_len: 1 },
+                BlockInfo { id: Some(first), start: 0x40, end: 0x80, requested: 40, data_len: 1 },
+            ]
+        );
+        assert!(memory_manager.iter_free().all(|block| block.is_free() && block.requested == 0));
+        assert_eq!(memory_manager.iter_free().map(|block| block.size()).sum::<usize>(), 128 - 16 - 16 - 64);
+        assert_eq!(memory_manager.iter_all().count(), memory_manager.blocks().len());
+        assert!(memory_manager.iter_all().zip(memory_manager.iter_all().skip(1)).all(|(a, b)| a.end == b.start), "Blocks tile the arena");
+
+        // Blocks of the same size stay in address order, whatever their IDs
+        let by_size: Vec<Option<usize>> = memory_manager.iter_by_size().map(|block| block.id).collect();
+        assert_eq!(by_size, vec![Some(first), Some(fourth), Some(third)]);
+
+        // The allocator's free lists hold what is left: two 16-byte blocks that are not buddies
+        let free_lists = memory_manager.allocator().free_lists();
+        assert_eq!(free_lists[4].iter().copied().collect::<Vec<usize>>(), vec![0x10, 0x20]);
+        assert_eq!(free_lists.iter().map(|starts| starts.len()).sum::<usize>(), memory_manager.allocator().free_blocks().len());
+    }
 }
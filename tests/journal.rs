@@ -0,0 +1,126 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use memory_manager::{Journal, JournalOptions, JournalRecord, ManagerConfig, MemoryImage, MemoryManager, PlacementStrategy, Repl, SyncPolicy};
+
+    fn config() -> ManagerConfig {
+        ManagerConfig { arena_size: 256, min_block: 8, strategy: PlacementStrategy::BestFit, block_headers: false }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mm-journal-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn open(dir: &Path, options: JournalOptions) -> MemoryManager {
+        let mut memory_manager = MemoryManager::open_durable(dir.to_str().unwrap(), config(), options).unwrap();
+        memory_manager.set_verbose(false);
+        memory_manager
+    }
+
+    // A mix of operations, including failing ones, that splits, merges and moves blocks
+    fn operations() -> Vec<fn(&mut MemoryManager)> {
+        vec![
+            |mm| drop(mm.insert(20, b"alpha".to_vec())),
+            |mm| drop(mm.insert(60, b"beta".to_vec())),
+            |mm| drop(mm.insert(8, b"gamma".to_vec())),
+            |mm| drop(mm.update(0, b"a value that no longer fits the block".to_vec())),
+            |mm| drop(mm.delete(1)),
+            |mm| drop(mm.insert(500, Vec::new())),
+            |mm| drop(mm.resize(2, 30)),
+            |mm| drop(mm.delete(9)),
+            |mm| drop(mm.insert(16, b"delta".to_vec())),
+        ]
+    }
+
+    #[test]
+    fn test_reopening_replays_the_journal() {
+        let dir = temp_dir("reopen");
+        let mut memory_manager = open(&dir, JournalOptions { checkpoint_every: 0, ..JournalOptions::default() });
+        for operation in operations() {
+            operation(&mut memory_manager);
+        }
+        let expected = memory_manager.to_image();
+        drop(memory_manager);
+
+        let mut reopened = open(&dir, JournalOptions::default());
+        assert_eq!(reopened.to_image(), expected);
+        assert_eq!(reopened.insert(8, b"more".to_vec()), Ok(5), "IDs carry on where they stopped");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_checkpoints_truncate_the_journal() {
+        let dir = temp_dir("checkpoint");
+        let mut memory_manager = open(&dir, JournalOptions { sync: SyncPolicy::Never, checkpoint_every: 4 });
+        let journal = dir.join("journal.wal");
+        let empty = fs::metadata(&journal).unwrap().len();
+        for operation in operations() {
+            operation(&mut memory_manager);
+        }
+        // 9 operations: checkpoints after the 4th and the 8th leave one record
+        assert!(fs::metadata(&journal).unwrap().len() > empty);
+        let (_, records, _) = Journal::open(&dir, JournalOptions::default()).unwrap();
+        assert_eq!(records, vec![JournalRecord::Insert { size: 16, data: b"delta".to_vec() }]);
+        let checkpoint = MemoryImage::decode(&fs::read(dir.join("checkpoint.img")).unwrap()).unwrap();
+        assert_eq!(checkpoint.next_id, 4);
+
+        // A crash between replacing the checkpoint and the log leaves the log of the older
+        // checkpoint, whose records must not be applied twice
+        let old_log = fs::read(&journal).unwrap();
+        memory_manager.checkpoint().unwrap();
+        let expected = memory_manager.to_image();
+        drop(memory_manager);
+        fs::write(&journal, old_log).unwrap();
+        assert_eq!(open(&dir, JournalOptions::default()).to_image(), expected);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_reset_is_journaled() {
+        let dir = temp_dir("reset");
+        let mut repl = Repl::new(open(&dir, JournalOptions::default()));
+        repl.eval("INSERT 20 alpha").unwrap();
+        repl.eval("INSERT 60 beta").unwrap();
+        repl.eval(":reset").unwrap();
+        assert_eq!(repl.eval("INSERT 8 gamma"), Ok(Some("Allocated block with ID 0".to_string())));
+        let expected = repl.memory_manager().to_image();
+        drop(repl);
+
+        assert_eq!(open(&dir, JournalOptions::default()).to_image(), expected, "The reset and what followed survive reopening");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_recovers_a_consistent_prefix_from_any_truncation() {
+        let dir = temp_dir("truncate");
+        let mut memory_manager = open(&dir, JournalOptions { sync: SyncPolicy::Never, checkpoint_every: 0 });
+        let journal = dir.join("journal.wal");
+
+        // The state after each operation, and where its record ends in the journal
+        let mut states = vec![(fs::metadata(&journal).unwrap().len(), memory_manager.to_image())];
+        for operation in operations() {
+            operation(&mut memory_manager);
+            states.push((fs::metadata(&journal).unwrap().len(), memory_manager.to_image()));
+        }
+        drop(memory_manager);
+        let checkpoint = fs::read(dir.join("checkpoint.img")).unwrap();
+        let log = fs::read(&journal).unwrap();
+
+        let crashed = temp_dir("truncate-crashed");
+        for offset in 0..=log.len() {
+            fs::create_dir_all(&crashed).unwrap();
+            fs::write(crashed.join("checkpoint.img"), &checkpoint).unwrap();
+            fs::write(crashed.join("journal.wal"), &log[..offset]).unwrap();
+
+            let recovered = open(&crashed, JournalOptions::default()).to_image();
+            let (_, expected) = states.iter().rev().find(|(end, _)| *end as usize <= offset).unwrap_or(&states[0]);
+            assert_eq!(&recovered, expected, "Journal cut at byte {}", offset);
+            fs::remove_dir_all(&crashed).unwrap();
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
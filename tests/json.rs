@@ -0,0 +1,37 @@
+#[cfg(test)]
+mod tests {
+    use memory_manager::json::{bytes_fields, Json};
+    use memory_manager::{MemoryManager, PlacementStrategy};
+
+    #[test]
+    fn test_json_is_written_compactly_and_escaped() {
+        let mut json = Json::object([("name", "a \"quoted\"\nline\u{1}".into()), ("n", 3usize.into()), ("none", Json::Null)]);
+        json.push("ratio", 0.5);
+        json.push("nan", f64::NAN);
+        json.push("list", vec![true, false]);
+        assert_eq!(
+            json.to_string(),
+            r#"{"name":"a \"quoted\"\nline\u0001","n":3,"none":null,"ratio":0.5,"nan":null,"list":[true,false]}"#
+        );
+        assert_eq!(json.get("n"), Some(&Json::Int(3)));
+        assert_eq!(Json::from(None::<usize>), Json::Null);
+    }
+
+    #[test]
+    fn test_bytes_and_blocks_as_json() {
+        let [data, hex] = bytes_fields(b"a\x00\xff");
+        assert_eq!((data.1.to_string(), hex.1.to_string()), (r#""a\\x00\\xff""#.to_string(), r#""6100ff""#.to_string()));
+
+        let mut memory_manager = MemoryManager::with_strategy(PlacementStrategy::FirstFit);
+        memory_manager.set_verbose(false);
+        memory_manager.insert(10, b"hi".to_vec()).unwrap();
+        assert_eq!(
+            memory_manager.blocks_json().to_string(),
+            r#"[{"type":"allocated","id":0,"start":0,"end":16,"size":16,"data":"hi","data_hex":"6869"},{"type":"free","start":16,"end":65536,"size":65520}]"#
+        );
+        let stats = memory_manager.stats().to_json();
+        assert_eq!(stats.get("used_bytes"), Some(&Json::Int(16)));
+        assert_eq!(stats.get("largest_free"), Some(&Json::Int(32768)), "Splitting leaves free halves of 16 bytes up to 32K");
+        assert_eq!(stats.get("fragmentation"), Some(&Json::Float(1.0 - 32768.0 / 65520.0)));
+    }
+}
@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod tests {
+    use memory_manager::{LeakCheck, MemoryManager};
+
+    #[test]
+    fn test_leak_report_groups_by_call_site() {
+        let mut memory_manager = MemoryManager::new();
+
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            ids.push(memory_manager.insert(16, vec![1; 16]).expect("Insertion should succeed"));
+        }
+        let other = memory_manager.insert(100, vec![2; 100]).expect("Insertion should succeed");
+        memory_manager.delete(ids[0]).expect("Deletion should succeed");
+
+        let report = memory_manager.leak_report();
+        assert_eq!(report.entries.len(), 2, "Blocks should be grouped into two call sites");
+        assert_eq!(report.block_count(), 3);
+        assert_eq!(report.total_bytes(), 16 * 2 + 128);
+
+        let loop_site = report.entries.iter().find(|entry| entry.ids == vec![ids[1], ids[2]])
+            .expect("Blocks inserted in the loop should share a call site");
+        assert_eq!(loop_site.bytes, 32);
+        assert!(loop_site.location.starts_with("tests/leak_report.rs:"), "Location should be the caller's: {}", loop_site.location);
+
+        let other_site = report.entries.iter().find(|entry| entry.ids == vec![other]).unwrap();
+        assert_ne!(other_site.location, loop_site.location);
+    }
+
+    #[test]
+    fn test_resize_moves_block_to_new_call_site() {
+        let mut memory_manager = MemoryManager::new();
+        memory_manager.set_capture_backtraces(true);
+
+        let id = memory_manager.insert(8, vec![7; 8]).expect("Insertion should succeed");
+        let before = memory_manager.leak_report().entries[0].location.clone();
+
+        memory_manager.resize(id, 64).expect("Resize should succeed");
+        let report = memory_manager.leak_report();
+        assert_eq!(report.entries.len(), 1);
+        assert_ne!(report.entries[0].location, before, "Resize should become the block's call site");
+        assert_eq!(report.entries[0].bytes, 64);
+        assert!(report.entries[0].backtrace.is_some(), "Backtrace should be captured when enabled");
+
+        let block = memory_manager.find(id).expect("Block should keep its ID");
+        assert_eq!(&memory_manager.get_buffer_slice(block.start, block.end)[..8], &[7; 8]);
+    }
+
+    #[test]
+    #[should_panic(expected = "dropped with leaks")]
+    fn test_leak_check_panics_on_drop() {
+        let mut memory_manager = MemoryManager::new();
+        memory_manager.set_leak_check(LeakCheck::Panic);
+        memory_manager.insert(24, b"never deleted".to_vec()).expect("Insertion should succeed");
+    }
+
+    #[test]
+    fn test_leak_check_passes_when_all_blocks_deleted() {
+        let mut memory_manager = MemoryManager::new();
+        memory_manager.set_leak_check(LeakCheck::Panic);
+        let id = memory_manager.insert(24, b"deleted".to_vec()).expect("Insertion should succeed");
+        memory_manager.delete(id).expect("Deletion should succeed");
+        assert!(memory_manager.leak_report().is_empty());
+    }
+}
@@ -0,0 +1,85 @@
+#[cfg(test)]
+mod tests {
+    use memory_manager::malloc_trace::MallocEvent;
+    use memory_manager::{replay_malloc_trace, MallocTrace, PlacementStrategy};
+
+    const TRACE: &str = "\
+# ptr size
+a 0x10 100
+a 0x20 5000
+
+r 0x10 0x30 300
+f 0x20
+f 0x99
+f (nil)
+a 0x40 65536
+r 0x30 0x30 0
+";
+
+    #[test]
+    fn test_parse_malloc_trace() {
+        let trace = MallocTrace::parse(TRACE).expect("Trace should parse");
+        assert_eq!(trace.events.len(), 8);
+        assert_eq!(trace.events[2], (5, MallocEvent::Realloc { old: "0x10".into(), new: "0x30".into(), size: 300 }));
+
+        let error = MallocTrace::parse("a 0x10 100\nm 0x10\n").unwrap_err();
+        assert!(error.starts_with("2: "), "{}", error);
+        assert_eq!(MallocTrace::parse("a 0x10 70000").err(), Some("1: size 70000 is larger than the 65536-byte arena".to_string()));
+        assert!(MallocTrace::parse_for("a 0x10 70000", 1 << 17).is_ok());
+    }
+
+    #[test]
+    fn test_sizes_past_the_arena_fail_instead_of_hanging() {
+        let line = format!("a 0x1 {}", usize::MAX);
+        assert_eq!(MallocTrace::parse(&line).err(), Some(format!("1: size {} is larger than the 65536-byte arena", usize::MAX)));
+
+        // A trace built in code skips the parser's check, and the manager must still refuse
+        let trace = MallocTrace { events: vec![(1, MallocEvent::Alloc { ptr: "0x1".into(), size: usize::MAX })] };
+        let report = replay_malloc_trace(&trace, PlacementStrategy::FirstFit);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].error, "Insufficient memory");
+    }
+
+    #[test]
+    fn test_impossible_events_are_reported_and_skipped() {
+        let trace = MallocTrace::parse("a 0x10 100\na 0x10 200\nr 0x99 0x20 50\na 0x20 16\nr 0x20 0x10 64\nr 0 0x30 8\n").unwrap();
+        let report = replay_malloc_trace(&trace, PlacementStrategy::FirstFit);
+        assert_eq!(
+            report.malformed,
+            vec![
+                (2, "allocation returned 0x10, which is still live".to_string()),
+                (3, "realloc of 0x99, which is not live".to_string()),
+                (5, "realloc returned 0x10, which is still live".to_string()),
+            ]
+        );
+        assert!(report.failures.is_empty());
+        let last = report.samples.last().unwrap();
+        assert_eq!((last.live_blocks, last.requested_bytes), (3, 100 + 16 + 8), "Only the well-formed lines were replayed");
+        assert!(report.to_string().contains("  malformed lines: 3\n  line 2: allocation returned 0x10, which is still live\n"));
+    }
+
+    #[test]
+    fn test_replay_malloc_trace_reports_failures_and_usage() {
+        let trace = MallocTrace::parse(TRACE).unwrap();
+        let report = replay_malloc_trace(&trace, PlacementStrategy::FirstFit);
+
+        assert_eq!(report.events, 8);
+        assert_eq!(report.samples.len(), 8, "One sample per event");
+        assert_eq!(report.unknown_frees, 1);
+        assert!(report.malformed.is_empty());
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].line, 9);
+        assert_eq!(report.failures[0].size, 65536, "The whole arena is asked for while a block is live");
+
+        assert_eq!(report.peak_used_bytes, 512 + 8192, "Realloc to 300 bytes keeps the 5000-byte block live");
+        assert_eq!(report.samples[2].requested_bytes, 5300);
+        assert!(report.peak_fragmentation > 0.0);
+
+        let last = report.samples.last().unwrap();
+        assert_eq!((last.line, last.live_blocks, last.used_bytes), (10, 0, 0), "realloc(p, 0) frees p");
+
+        let csv = report.to_csv();
+        assert_eq!(csv.lines().count(), 9);
+        assert!(csv.starts_with("line,live_blocks,used_bytes"));
+    }
+}
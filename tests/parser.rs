@@ -0,0 +1,137 @@
+#[cfg(test)]
+mod tests {
+    use memory_manager::parser::{parse_data, parse_line, parse_script, tokenize};
+    use std::path::Path;
+    use memory_manager::{Command, COMMAND_NAMES};
+
+    #[test]
+    fn test_tokenize_tracks_columns_and_comments() {
+        let tokens = tokenize("f", 1, "  INSERT\t24  data1 # trailing comment").unwrap();
+        let words: Vec<(&str, usize)> = tokens.iter().map(|token| (token.text, token.column)).collect();
+        assert_eq!(words, vec![("INSERT", 3), ("24", 10), ("data1", 14)]);
+        assert!(tokenize("f", 1, "# whole line").unwrap().is_empty());
+        assert!(tokenize("f", 1, "   ").unwrap().is_empty());
+
+        let tokens = tokenize("f", 1, r#"INSERT 24 "a # b\" c"*2 x"de ad""#).unwrap();
+        let words: Vec<&str> = tokens.iter().map(|token| token.text).collect();
+        assert_eq!(words, vec!["INSERT", "24", r#""a # b\" c"*2"#, r#"x"de ad""#]);
+
+        let error = tokenize("f", 3, r#"INSERT 8 "open"#).unwrap_err();
+        assert_eq!(error.to_string(), "f:3:10: unterminated string '\"open'");
+    }
+
+    #[test]
+    fn test_parse_data_literals() {
+        let here = Path::new("");
+        assert_eq!(parse_data("plain", here, 65536), Ok(b"plain".to_vec()));
+        assert_eq!(parse_data(r#""a b\n\t\"\\\x41\0""#, here, 65536), Ok(b"a b\n\t\"\\A\0".to_vec()));
+        assert_eq!(parse_data("0xDEADBEEF", here, 65536), Ok(vec![0xde, 0xad, 0xbe, 0xef]));
+        assert_eq!(parse_data(r#"x"de ad be ef""#, here, 65536), Ok(vec![0xde, 0xad, 0xbe, 0xef]));
+        assert_eq!(parse_data("0x00*128", here, 65536), Ok(vec![0; 128]));
+        assert_eq!(parse_data(r#""ab"*3"#, here, 65536), Ok(b"ababab".to_vec()));
+        assert_eq!(parse_data(r#""a*2""#, here, 65536), Ok(b"a*2".to_vec()));
+
+        assert!(parse_data("0xABC", here, 65536).is_err());
+        assert!(parse_data("0xZZ", here, 65536).is_err());
+        assert!(parse_data(r#""\q""#, here, 65536).is_err());
+        assert!(parse_data("0x00*many", here, 65536).is_err());
+        assert!(parse_data(r#"ab"c""#, here, 65536).is_err());
+    }
+
+    #[test]
+    fn test_repeated_data_is_capped_at_the_arena_size() {
+        let here = Path::new("");
+        assert_eq!(parse_data("0x00*64", here, 64), Ok(vec![0; 64]));
+        assert_eq!(
+            parse_data("0x00*65", here, 64),
+            Err("data of 1 bytes repeated 65 times does not fit the 64-byte arena".to_string())
+        );
+        assert!(parse_data("abcd*4611686018427387904", here, 64).is_err(), "The length overflows usize");
+
+        let error = parse_line("f", 1, "INSERT 8 x*99999999999999").unwrap_err();
+        assert_eq!((error.column, error.token.as_str()), (10, "x*99999999999999"));
+    }
+
+    #[test]
+    fn test_data_from_file_is_relative_to_script() {
+        let dir = std::env::temp_dir().join(format!("mm-parser-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("payload.bin"), [1u8, 2, 3]).unwrap();
+        let script = dir.join("script.cmmd");
+        let script = script.to_str().unwrap();
+
+        assert_eq!(
+            parse_line(script, 1, "UPDATE 2 @payload.bin"),
+            Ok(Some(Command::Update { id: 2, data: vec![1, 2, 3] }))
+        );
+        let error = parse_line(script, 2, "INSERT 8 @missing.bin").unwrap_err();
+        assert_eq!((error.column, error.token.as_str()), (10, "@missing.bin"));
+        assert!(error.message.starts_with("cannot read"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(
+            parse_line("f", 1, "INSERT 24 data1 "),
+            Ok(Some(Command::Insert { size: 24, data: b"data1".to_vec() }))
+        );
+        assert_eq!(parse_line("f", 1, "delete 3"), Ok(Some(Command::Delete { id: 3 })));
+        assert_eq!(parse_line("f", 1, "UPDATE 1 x"), Ok(Some(Command::Update { id: 1, data: b"x".to_vec() })));
+        assert_eq!(parse_line("f", 1, "READ 0"), Ok(Some(Command::Read { id: 0 })));
+        assert_eq!(parse_line("f", 1, "DUMP"), Ok(Some(Command::Dump)));
+        assert_eq!(parse_line("f", 1, "stats"), Ok(Some(Command::Stats)));
+        assert_eq!(parse_line("f", 1, "MAP"), Ok(Some(Command::Map { width: 64 })));
+        assert_eq!(parse_line("f", 1, "dot"), Ok(Some(Command::Dot)));
+        assert_eq!(parse_line("f", 1, "HEXDUMP 3"), Ok(Some(Command::Hexdump { id: 3 })));
+        assert_eq!(parse_line("f", 1, "WHICH 0x24"), Ok(Some(Command::Which { address: 0x24 })));
+        assert_eq!(parse_line("f", 1, "HEXDUMP 0x20 0x40"), Ok(Some(Command::HexdumpRange { start: 0x20, end: 0x40 })));
+        assert_eq!(parse_line("f", 1, "FIND 0xBEEF"), Ok(Some(Command::Find { pattern: vec![0xbe, 0xef], live_only: false })));
+        assert_eq!(parse_line("f", 1, "find \"a b\" live"), Ok(Some(Command::Find { pattern: b"a b".to_vec(), live_only: true })));
+        assert_eq!(parse_line("f", 1, "MAP 32"), Ok(Some(Command::Map { width: 32 })));
+        assert_eq!(parse_line("dir/f", 1, "SAVE heap.img"), Ok(Some(Command::Save { path: "dir/heap.img".into() })));
+        assert_eq!(parse_line("f", 1, "load \"my heap.img\""), Ok(Some(Command::Load { path: "my heap.img".into() })));
+        assert_eq!(parse_line("f", 1, ""), Ok(None));
+        assert_eq!(parse_line("f", 1, "   # nothing"), Ok(None));
+    }
+
+    #[test]
+    fn test_command_names_are_all_parsed() {
+        for name in COMMAND_NAMES {
+            let error = parse_line("f", 1, name).err().map(|error| error.message);
+            assert!(!error.is_some_and(|message| message.starts_with("unknown command")), "{} is not parsed", name);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors_point_at_token() {
+        let error = parse_line("cmds.cmmd", 4, "INSERT 24 hello world").unwrap_err();
+        assert_eq!(error.to_string(), "cmds.cmmd:4:17: unexpected argument 'world'");
+        assert_eq!(
+            parse_line("cmds.cmmd", 4, r#"INSERT 24 "hello world""#),
+            Ok(Some(Command::Insert { size: 24, data: b"hello world".to_vec() }))
+        );
+
+        let error = parse_line("cmds.cmmd", 5, "UPDATE 1 0x0G").unwrap_err();
+        assert_eq!(error.to_string(), "cmds.cmmd:5:10: invalid hex byte '0G' '0x0G'");
+
+        let error = parse_line("cmds.cmmd", 2, "DELETE abc").unwrap_err();
+        assert_eq!(error.to_string(), "cmds.cmmd:2:8: invalid <id> 'abc'");
+
+        let error = parse_line("cmds.cmmd", 9, "INSERT 24  ").unwrap_err();
+        assert_eq!((error.column, error.token.as_str()), (10, ""));
+        assert_eq!(error.to_string(), "cmds.cmmd:9:10: missing <data> after INSERT");
+
+        let error = parse_line("cmds.cmmd", 1, "  ALLOC 1").unwrap_err();
+        assert_eq!(error.to_string(), "cmds.cmmd:1:3: unknown command 'ALLOC'");
+    }
+
+    #[test]
+    fn test_parse_script_reports_line_numbers() {
+        let results = parse_script("s", "INSERT 8 a\n\n# c\nBOGUS\nDUMP\n");
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().0, 1);
+        assert_eq!(results[1].as_ref().unwrap_err().line, 4);
+        assert_eq!(results[2], Ok((5, Command::Dump)));
+    }
+}
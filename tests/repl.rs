@@ -0,0 +1,103 @@
+#[cfg(test)]
+mod tests {
+    use memory_manager::line_editor::LineEditor;
+    use memory_manager::{replay, LeakCheck, MemoryManager, PlacementStrategy, Repl};
+
+    fn repl() -> Repl {
+        let mut memory_manager = MemoryManager::with_strategy(PlacementStrategy::FirstFit);
+        memory_manager.set_verbose(false);
+        Repl::new(memory_manager)
+    }
+
+    #[test]
+    fn test_eval_runs_commands_and_reports_errors() {
+        let mut repl = repl();
+        assert_eq!(repl.eval(r#"INSERT 16 "hi there""#), Ok(Some("Allocated block with ID 0".to_string())));
+        assert_eq!(repl.eval("  # just a comment"), Ok(None));
+        assert_eq!(repl.eval("DELETE 5"), Err("DELETE failed: Block not found".to_string()));
+        assert_eq!(repl.eval("FROB"), Err("<stdin>:4:1: unknown command 'FROB'".to_string()));
+        assert!(repl.eval("help").unwrap().unwrap().contains(":reset"));
+        assert_eq!(repl.memory_manager().stats().allocated_blocks, 1);
+    }
+
+    #[test]
+    fn test_reset_and_quit() {
+        let mut repl = repl();
+        repl.eval("INSERT 16 a").unwrap();
+        repl.eval(":reset").unwrap();
+        assert_eq!(repl.memory_manager().stats().allocated_blocks, 0);
+        assert_eq!(repl.eval("INSERT 16 a"), Ok(Some("Allocated block with ID 0".to_string())));
+
+        assert!(!repl.is_done());
+        assert_eq!(repl.eval(":quit"), Ok(None));
+        assert!(repl.is_done());
+    }
+
+    #[test]
+    fn test_reset_keeps_recording_profiling_and_leak_settings() {
+        let mut memory_manager = MemoryManager::with_strategy(PlacementStrategy::FirstFit);
+        memory_manager.set_verbose(false);
+        memory_manager.start_recording();
+        memory_manager.enable_profiling();
+        memory_manager.set_leak_check(LeakCheck::Print);
+        memory_manager.set_capture_backtraces(true);
+        let mut repl = Repl::new(memory_manager);
+        repl.eval("INSERT 16 a").unwrap();
+        repl.eval("INSERT 32 b").unwrap();
+        repl.eval(":reset").unwrap();
+        repl.eval("INSERT 8 c").unwrap();
+
+        let mut memory_manager = repl.into_memory_manager();
+        assert_eq!(memory_manager.leak_check(), LeakCheck::Print);
+        assert!(memory_manager.captures_backtraces());
+        let profiler = memory_manager.take_profiler().expect("Profiling survives a reset");
+        assert_eq!(profiler.live_bytes(), 8, "The reset freed the first two blocks");
+
+        let trace = memory_manager.take_trace().expect("Recording survives a reset");
+        assert_eq!(trace.to_string().lines().nth(3), Some("reset = ok"));
+        assert!(replay(&trace, PlacementStrategy::FirstFit).is_exact(), "A replay resets at the same point");
+        memory_manager.set_leak_check(LeakCheck::Ignore);
+    }
+
+    #[test]
+    fn test_explain_toggles_narration() {
+        let mut repl = repl();
+        assert_eq!(repl.eval(":explain"), Ok(Some("Explain mode on".to_string())));
+        let message = repl.eval("INSERT 16 a").unwrap().unwrap();
+        assert!(message.starts_with("allocate 16 bytes: order 4 (16 bytes)\n"), "{}", message);
+        assert!(message.ends_with("\nAllocated block with ID 0"), "{}", message);
+
+        repl.eval(":reset").unwrap();
+        assert!(repl.memory_manager().is_explaining(), "Explain mode survives a reset");
+        assert_eq!(repl.eval(":explain"), Ok(Some("Explain mode off".to_string())));
+        assert_eq!(repl.eval("INSERT 16 a"), Ok(Some("Allocated block with ID 0".to_string())));
+    }
+
+    #[test]
+    fn test_completes_commands_and_live_ids() {
+        let mut repl = repl();
+        assert_eq!(repl.complete("IN"), vec!["INSERT"]);
+        assert_eq!(repl.complete("d"), vec!["delete", "dump", "dot"]);
+        assert_eq!(repl.complete(":r"), vec![":reset"]);
+        assert_eq!(repl.complete("").len(), 17);
+
+        for _ in 0..12 {
+            repl.eval("INSERT 8 x").unwrap();
+        }
+        repl.eval("DELETE 10").unwrap();
+        assert_eq!(repl.complete("READ 1"), vec!["1", "11"]);
+        assert_eq!(repl.complete("update 1"), vec!["1", "11"]);
+        assert_eq!(repl.complete("DELETE ").len(), 11);
+        assert!(repl.complete("INSERT 8").is_empty());
+        assert!(repl.complete("DELETE 1 ").is_empty());
+    }
+
+    #[test]
+    fn test_history_skips_blanks_and_repeats() {
+        let mut editor = LineEditor::new();
+        for line in ["DUMP", "DUMP", "  ", "READ 0", "DUMP"] {
+            editor.add_history(line);
+        }
+        assert_eq!(editor.history(), ["DUMP", "READ 0", "DUMP"]);
+    }
+}
@@ -0,0 +1,37 @@
+#[cfg(test)]
+mod tests {
+    use memory_manager::{MemoryManager, PlacementStrategy, Report, Script};
+
+    fn report(source: &str, keep_going: bool) -> Report {
+        let mut memory_manager = MemoryManager::with_strategy(PlacementStrategy::FirstFit);
+        memory_manager.set_verbose(false);
+        Report::run("test.cmmd", &mut memory_manager, &Script::parse("test.cmmd", source).statements, keep_going)
+    }
+
+    #[test]
+    fn test_records_a_step_per_statement() {
+        let report = report("INSERT 20 a\nDELETE 5\nINSERT 16 b\nDELETE 0\n", true);
+        assert_eq!(report.steps.len(), 5, "The start plus one step per statement");
+        assert_eq!((report.steps[0].line, report.steps[0].map.stats().used_bytes), (0, 0));
+        assert_eq!((report.steps[1].line, report.steps[1].text.as_str()), (1, "INSERT 20 a"));
+        assert_eq!(report.steps[1].map.stats().used_bytes, 32);
+        assert_eq!(report.steps[2].error.as_deref(), Some("test.cmmd:2:1: DELETE failed: Block not found"));
+        assert_eq!(report.steps[4].map.free_list, vec![(0, 32), (48, 64), (64, 128)].into_iter().chain((7..16).map(|order| (1 << order, 2 << order))).collect::<Vec<_>>());
+
+        assert_eq!(self::report("INSERT 20 a\nDELETE 5\nINSERT 16 b\n", false).steps.len(), 3, "Stops at the first failure");
+    }
+
+    #[test]
+    fn test_html_has_a_map_and_table_per_step() {
+        let html = report("INSERT 20 \"<a>\"\nINSERT 16 b\n", false).to_html();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert_eq!(html.matches("<svg class=\"map\"").count(), 3);
+        assert_eq!(html.matches("<table>").count(), 3);
+        assert!(html.contains("<polyline class=\"used\""));
+        assert!(html.contains("<input type=\"range\" id=\"scrubber\" min=\"0\" max=\"2\" value=\"2\">"));
+        assert!(html.contains("<code>INSERT 20 &quot;&lt;a&gt;&quot;</code>"), "Source text is escaped");
+        assert!(html.contains("<tr><td>0</td><td>0x0000</td><td>0x0020</td><td>32</td><td>3</td><td>29</td></tr>"));
+        assert!(html.contains("<tr class=\"total\"><td>Allocated</td><td></td><td></td><td>48</td><td>4</td><td>44</td></tr>"));
+        assert!(!html.contains("src=") && !html.contains("href="), "The page is self-contained");
+    }
+}
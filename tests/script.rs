@@ -0,0 +1,239 @@
+#[cfg(test)]
+mod tests {
+    use memory_manager::json::Json;
+    use memory_manager::parser::{Expr, Variables};
+    use memory_manager::script::Event;
+    use memory_manager::memory_map::MapEntry;
+    use memory_manager::{Breakpoint, Interpreter, MapChange, MemoryManager, MemoryMap, PlacementStrategy, Script};
+
+    // Runs a script on a fresh manager, returning the interpreter and the outputs and errors it reported
+    fn run(source: &str, keep_going: bool) -> (Interpreter, MemoryManager, Vec<String>, Vec<String>) {
+        let mut memory_manager = MemoryManager::with_strategy(PlacementStrategy::FirstFit);
+        memory_manager.set_verbose(false);
+        let script = Script::parse("test.cmmd", source);
+        let mut interpreter = Interpreter::new(keep_going);
+        let (mut outputs, mut errors) = (Vec::new(), Vec::new());
+        interpreter.run(&mut memory_manager, &script.statements, &mut |event| match event {
+            Event::Finished { outcome, .. } => outputs.extend(outcome.message.clone()),
+            Event::Error { message, .. } => errors.push(message.to_string()),
+            Event::Command { .. } | Event::State { .. } => {}
+        });
+        (interpreter, memory_manager, outputs, errors)
+    }
+
+    #[test]
+    fn test_expressions() {
+        let mut variables = Variables::new();
+        variables.insert("i".to_string(), 3);
+        let eval = |text: &str| Expr::parse(text).and_then(|expr| expr.eval(&variables));
+        assert_eq!(eval("$i*8+16"), Ok(40));
+        assert_eq!(eval("(1+$i)*(10-2)/4%3"), Ok(2));
+        assert_eq!(eval("$j"), Err("unknown variable $j".to_string()));
+        assert_eq!(eval("1/0"), Err("division by zero".to_string()));
+        assert!(eval("2*").is_err());
+        assert!(eval("(1").is_err());
+        assert!(eval("x").is_err());
+    }
+
+    #[test]
+    fn test_let_captures_ids() {
+        let (interpreter, memory_manager, _, errors) = run(
+            "INSERT 8 pad\nLET a = INSERT 24 data1\nLET size = 16 * 2\nLET b = insert $size \"two\"\nDELETE 0\nDELETE $a\nREAD $b\n",
+            false,
+        );
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(interpreter.variables()["a"], 1);
+        assert_eq!(interpreter.variables()["size"], 32);
+        assert_eq!(memory_manager.find(2).unwrap().size(), 32);
+        assert!(memory_manager.find(1).is_err());
+    }
+
+    #[test]
+    fn test_repeat_with_counter() {
+        let source = "REPEAT 2 AS i {\n  REPEAT 3 AS j {\n    INSERT ($i*3+$j+1)*8 x\n  }\n}\nREPEAT $i + 4 {\n  DELETE 0\n}\n";
+        let (interpreter, memory_manager, outputs, errors) = run(source, true);
+        assert_eq!(interpreter.variables()["j"], 2);
+        assert_eq!(outputs.iter().filter(|message| message.starts_with("Allocated")).count(), 6);
+        assert_eq!(memory_manager.find(5).unwrap().size(), 64);
+        // `$i + 4` is 5 deletes of block 0; the first succeeds and the rest fail
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        assert_eq!(errors[0], "test.cmmd:7:1: DELETE failed: Block not found");
+    }
+
+    #[test]
+    fn test_runtime_errors_stop_the_script() {
+        let (interpreter, memory_manager, _, errors) = run("LET a = INSERT 8 x\nDELETE $b\nINSERT 8 y\n", false);
+        assert!(interpreter.failed());
+        assert_eq!(errors, vec!["test.cmmd:2:8: unknown variable $b '$b'"]);
+        assert_eq!(memory_manager.stats().allocated_blocks, 1);
+
+        let (_, _, _, errors) = run("REPEAT 0-1 {\n}\nINSERT 1-2 x\n", true);
+        assert_eq!(errors, vec!["test.cmmd:1:8: <count> is negative (-1)", "test.cmmd:3:8: <size> is negative (-1) '1-2'"]);
+    }
+
+    #[test]
+    fn test_structure_errors() {
+        let script = Script::parse(
+            "s.cmmd",
+            "LET 1x = 4\nLET a = DELETE 0\nREPEAT 3\n}\nREPEAT 2 AS {\nDUMP\n}\nLET b 4\nREPEAT 2 {\nDUMP\n",
+        );
+        let errors: Vec<String> = script.errors().iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "s.cmmd:1:5: invalid variable name '1x'",
+                "s.cmmd:2:9: only INSERT and UPDATE return an ID 'DELETE'",
+                "s.cmmd:3:9: expected '{' at the end of REPEAT",
+                "s.cmmd:4:1: unexpected '}' '}'",
+                "s.cmmd:5:14: missing <name> after AS",
+                "s.cmmd:8:7: expected '=' '4'",
+                "s.cmmd:9:1: missing '}' to close REPEAT",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_include_is_relative_and_shares_variables() {
+        let dir = std::env::temp_dir().join(format!("mm-script-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        std::fs::write(dir.join("lib/setup.cmmd"), "LET base = INSERT $size setup\nINCLUDE ../main.cmmd\n").unwrap();
+        let main = dir.join("main.cmmd");
+        std::fs::write(&main, "LET size = 64\nINCLUDE lib/setup.cmmd\nREAD $base\nINCLUDE missing.cmmd\n").unwrap();
+
+        let main = main.to_str().unwrap();
+        let script = Script::parse(main, &std::fs::read_to_string(main).unwrap());
+        let errors: Vec<String> = script.errors().iter().map(|e| e.message.clone()).collect();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert_eq!(errors[0], "INCLUDE cycle");
+        assert!(errors[1].starts_with("cannot read"));
+
+        let mut memory_manager = MemoryManager::with_strategy(PlacementStrategy::FirstFit);
+        memory_manager.set_verbose(false);
+        let mut interpreter = Interpreter::new(true);
+        interpreter.run(&mut memory_manager, &script.statements, &mut |_| {});
+        assert_eq!(interpreter.variables()["base"], 0);
+        assert_eq!(memory_manager.find(0).unwrap().size(), 64);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_expectations_pass() {
+        let source = "LET a = INSERT 24 \"data3\"\n\
+                      EXPECT READ $a == \"data3\"\n\
+                      EXPECT READ $a != data\n\
+                      EXPECT ERROR INSERT 70000 x\n\
+                      EXPECT FREE_BYTES >= 1024\n\
+                      EXPECT USED_BYTES == 16 * 2\n\
+                      EXPECT ALLOCATED_BLOCKS < 2\n\
+                      EXPECT BLOCK $a AT 0x0000\n";
+        let (interpreter, _, _, errors) = run(source, false);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!((interpreter.expectations_passed(), interpreter.expectations_failed()), (7, 0));
+    }
+
+    #[test]
+    fn test_failed_expectations_show_expected_and_actual() {
+        let source = "LET a = INSERT 24 data1\nEXPECT READ $a == data3\nEXPECT USED_BYTES == 16\nEXPECT BLOCK $a AT 0x40\nEXPECT ERROR DELETE $a\nEXPECT READ $a == x\n";
+        let (interpreter, _, _, errors) = run(source, true);
+        assert_eq!(interpreter.expectations_failed(), 5);
+        assert!(interpreter.failed());
+        assert_eq!(
+            errors,
+            vec![
+                "test.cmmd:2:1: expectation failed: EXPECT READ $a == data3\n  - expected: \"data3\" (5 bytes)\n  + actual:   \"data1\" (5 bytes), first difference at byte 4",
+                "test.cmmd:3:1: expectation failed: EXPECT USED_BYTES == 16\n  - expected: USED_BYTES == 16\n  + actual:   USED_BYTES = 32",
+                "test.cmmd:4:1: expectation failed: EXPECT BLOCK $a AT 0x40\n  - expected: block 0 at 0x0040\n  + actual:   block 0 at 0x0000",
+                "test.cmmd:5:1: expectation failed: EXPECT ERROR DELETE $a\n  - expected: DELETE to fail\n  + actual:   Deleted block with ID 0",
+                "test.cmmd:6:1: expectation failed: EXPECT READ $a == x\n  - expected: \"x\" (1 bytes)\n  + actual:   Block not found",
+            ]
+        );
+
+        // Without --keep-going the first failed expectation stops the script
+        let (interpreter, _, _, errors) = run(source, false);
+        assert_eq!((interpreter.expectations_failed(), errors.len()), (1, 1));
+    }
+
+    #[test]
+    fn test_expect_syntax_errors() {
+        let script = Script::parse("e.cmmd", "EXPECT\nEXPECT READ 0 < x\nEXPECT WIBBLE == 1\nEXPECT BLOCK 0 IN 4\nEXPECT ERROR\nEXPECT FREE_BYTES =< 1\n");
+        let errors: Vec<String> = script.errors().iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "e.cmmd:1:7: missing <check> after EXPECT",
+                "e.cmmd:2:15: expected '==' or '!=' '<'",
+                "e.cmmd:3:8: unknown check 'WIBBLE'",
+                "e.cmmd:4:16: expected 'AT' 'IN'",
+                "e.cmmd:5:13: missing command after EXPECT ERROR",
+                "e.cmmd:6:19: expected '==' or '!=' or '<' or '<=' or '>' or '>=' '=<'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_events_as_json() {
+        let mut memory_manager = MemoryManager::with_strategy(PlacementStrategy::FirstFit);
+        memory_manager.set_verbose(false);
+        let script = Script::parse("j.cmmd", "LET a = INSERT 8 x\nSTATS\nDELETE 5\n");
+        let mut lines = Vec::new();
+        Interpreter::new(true).run(&mut memory_manager, &script.statements, &mut |event| {
+            lines.extend(event.to_json().map(|mut json| {
+                if let Json::Object(fields) = &mut json {
+                    fields.retain(|(key, _)| key != "time_us");
+                }
+                json.to_string()
+            }))
+        });
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], r#"{"file":"j.cmmd","line":1,"command":"INSERT","status":"ok","id":0,"message":"Allocated block with ID 0"}"#);
+        assert!(lines[1].starts_with(r#"{"file":"j.cmmd","line":2,"command":"STATS","status":"ok","stats":{"total_bytes":65536,"used_bytes":8,"#), "{}", lines[1]);
+        assert_eq!(
+            lines[2],
+            r#"{"file":"j.cmmd","line":3,"command":"DELETE","status":"error","error":"j.cmmd:3:1: DELETE failed: Block not found"}"#
+        );
+    }
+
+    #[test]
+    fn test_until_stops_before_a_line() {
+        let mut memory_manager = MemoryManager::with_strategy(PlacementStrategy::FirstFit);
+        memory_manager.set_verbose(false);
+        let script = Script::parse("dir/test.cmmd", "REPEAT 3 AS i {\n    INSERT 8 x\n    DELETE $i\n}\n");
+        let mut interpreter = Interpreter::new(false);
+        interpreter.set_until(Some("test.cmmd:3".parse().unwrap()));
+        interpreter.run(&mut memory_manager, &script.statements, &mut |_| {});
+
+        assert_eq!(interpreter.stopped_at(), Some(("dir/test.cmmd", 3)));
+        assert!(!interpreter.failed(), "A breakpoint is not a failure");
+        assert_eq!(memory_manager.stats().allocated_blocks, 1, "Only the first INSERT should have run");
+
+        assert_eq!("12".parse(), Ok(Breakpoint { file: None, line: 12 }));
+        assert!("other.cmmd:3".parse::<Breakpoint>().unwrap().matches("a/other.cmmd", 3));
+        assert!(!"other.cmmd:3".parse::<Breakpoint>().unwrap().matches("a/mother.cmmd", 3));
+        assert!("0".parse::<Breakpoint>().is_err());
+        assert!(":4".parse::<Breakpoint>().is_err());
+    }
+
+    #[test]
+    fn test_state_events_diff_the_memory_map() {
+        let mut memory_manager = MemoryManager::with_strategy(PlacementStrategy::FirstFit);
+        memory_manager.set_verbose(false);
+        let script = Script::parse("test.cmmd", "INSERT 10 a\nINSERT 20 b\nUPDATE 1 c\nDELETE 0\nDELETE 1\nEXPECT FREE_BLOCKS == 1\n");
+        let mut interpreter = Interpreter::new(false);
+        interpreter.set_capture_maps(true);
+        let mut maps = vec![MemoryMap { entries: vec![MapEntry { id: None, start: 0, end: 65536, data: Vec::new() }], free_list: vec![(0, 65536)] }];
+        interpreter.run(&mut memory_manager, &script.statements, &mut |event| {
+            if let Event::State { map, .. } = event {
+                maps.push(map.clone());
+            }
+        });
+        let diffs: Vec<Vec<MapChange>> = maps.windows(2).map(|pair| pair[0].diff(&pair[1])).collect();
+
+        assert_eq!(diffs.len(), 6, "One state per command and EXPECT");
+        assert_eq!(diffs[0], vec![MapChange::Split { start: 0, end: 65536, parts: 2 }, MapChange::Created { id: 0, start: 0, end: 16 }]);
+        assert_eq!(diffs[2], vec![MapChange::Changed { id: 1, start: 32, end: 64 }]);
+        assert_eq!(diffs[3], vec![MapChange::Freed { id: 0, start: 0, end: 16 }, MapChange::Merged { start: 0, end: 32, parts: 2 }]);
+        assert_eq!(diffs[4], vec![MapChange::Freed { id: 1, start: 32, end: 64 }, MapChange::Merged { start: 0, end: 65536, parts: 3 }]);
+        assert!(diffs[5].is_empty());
+        assert_eq!(diffs[4][1].to_string(), "merged 3 blocks into free 0x0000 - 0x10000");
+    }
+}
@@ -0,0 +1,68 @@
+#[cfg(test)]
+mod tests {
+    use memory_manager::trace::{Placement, TraceOp};
+    use memory_manager::{replay, MemoryManager, PlacementStrategy, Trace};
+
+    fn record_scenario(strategy: PlacementStrategy) -> Trace {
+        let mut memory_manager = MemoryManager::with_strategy(strategy);
+        memory_manager.start_recording();
+
+        let a = memory_manager.insert(100, vec![1; 100]).unwrap();
+        let b = memory_manager.insert(10, vec![2; 10]).unwrap();
+        memory_manager.delete(a).unwrap();
+        memory_manager.insert(16, vec![3; 16]).unwrap();
+        memory_manager.update(b, vec![4; 40]).unwrap();
+        memory_manager.resize(a, 200).unwrap_err();
+        assert!(memory_manager.insert(70000, vec![5]).is_err());
+
+        memory_manager.take_trace().expect("Recording should be enabled")
+    }
+
+    #[test]
+    fn test_recording_captures_results() {
+        let trace = record_scenario(PlacementStrategy::FirstFit);
+
+        assert_eq!(trace.entries.len(), 7);
+        assert_eq!(trace.entries[0].op, TraceOp::Insert { size: 100, data_len: 100 });
+        assert_eq!(trace.entries[0].result, Ok(Some(Placement { id: 0, offset: 0 })));
+        assert_eq!(trace.entries[2].result, Ok(None));
+        assert_eq!(trace.entries[3].result, Ok(Some(Placement { id: 2, offset: 0 })), "First fit reuses the freed block");
+        assert_eq!(trace.entries[4].result.as_ref().unwrap().unwrap().id, 3, "Update reallocates under a new ID");
+        assert_eq!(trace.entries[5].result, Err("Block not found".to_string()));
+        assert_eq!(trace.entries[6].result, Err("Insufficient memory".to_string()));
+    }
+
+    #[test]
+    fn test_trace_text_round_trip() {
+        let trace = record_scenario(PlacementStrategy::BestFit);
+        let text = trace.to_string();
+        assert!(text.starts_with("mmtrace 1 best-fit\n"));
+
+        let parsed = Trace::parse(&text).expect("Trace should parse");
+        assert_eq!(parsed, trace);
+
+        let error = Trace::parse("mmtrace 1 first-fit\ni 24 5 = 0@0x0000\nx 1 = ok\n").unwrap_err();
+        assert!(error.starts_with("3: "), "Error should name the line: {}", error);
+    }
+
+    #[test]
+    fn test_replay_is_exact_with_recorded_strategy() {
+        for strategy in PlacementStrategy::ALL {
+            let trace = record_scenario(strategy);
+            let report = replay(&trace, trace.strategy);
+            assert!(report.is_exact(), "{}", report);
+            assert_eq!(report.operations, 7);
+        }
+    }
+
+    #[test]
+    fn test_replay_flags_divergence_with_other_strategy() {
+        let trace = record_scenario(PlacementStrategy::FirstFit);
+        let report = replay(&trace, PlacementStrategy::WorstFit);
+
+        assert!(!report.is_exact());
+        let first = &report.divergences[0];
+        assert_eq!(first.index, 1, "Worst fit takes the 16-byte block from the largest free block");
+        assert!(first.to_string().contains("recorded block at 0x0080"), "{}", first);
+    }
+}
@@ -0,0 +1,86 @@
+#[cfg(test)]
+mod tests {
+    use memory_manager::bench::{format_table, run_workload, summary_csv, BenchOptions};
+    use memory_manager::workload::{generate, LifetimeDistribution, SizeDistribution, WorkloadConfig, WorkloadOp};
+    use memory_manager::PlacementStrategy;
+
+    fn small_workload(seed: u64) -> WorkloadConfig {
+        WorkloadConfig {
+            seed,
+            operations: 500,
+            sizes: "bimodal:32:2048:0.2".parse().unwrap(),
+            lifetimes: "uniform:10:50".parse().unwrap(),
+            alloc_ratio: 0.5,
+        }
+    }
+
+    #[test]
+    fn test_distribution_specs() {
+        assert_eq!("uniform:8:64".parse::<SizeDistribution>(), Ok(SizeDistribution::Uniform { min: 8, max: 64 }));
+        assert_eq!(
+            "power-law:1.5:16:4096".parse::<SizeDistribution>(),
+            Ok(SizeDistribution::PowerLaw { alpha: 1.5, min: 16, max: 4096 })
+        );
+        assert_eq!("exp:100".parse::<LifetimeDistribution>(), Ok(LifetimeDistribution::Exponential { mean: 100.0 }));
+        assert!("uniform:8".parse::<SizeDistribution>().is_err());
+        assert!("bimodal:8:64:x".parse::<SizeDistribution>().is_err());
+    }
+
+    #[test]
+    fn test_generated_workload_is_seeded_and_consistent() {
+        let ops = generate(&small_workload(7));
+        assert_eq!(ops, generate(&small_workload(7)), "Same seed should give the same workload");
+        assert_ne!(ops, generate(&small_workload(8)));
+        assert_eq!(ops.len(), 500);
+
+        // Every free names an earlier allocation, and each allocation is freed at most once
+        let mut allocations = 0;
+        let mut freed = std::collections::HashSet::new();
+        for op in &ops {
+            match *op {
+                WorkloadOp::Alloc { size } => {
+                    assert!(size == 32 || size == 2048);
+                    allocations += 1;
+                }
+                WorkloadOp::Free { alloc_index } => {
+                    assert!(alloc_index < allocations);
+                    assert!(freed.insert(alloc_index), "Allocation {} freed twice", alloc_index);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_run_workload_reports_metrics() {
+        let ops = generate(&small_workload(3));
+        let results: Vec<_> = PlacementStrategy::ALL.iter()
+            .map(|&strategy| run_workload(&ops, strategy, 50))
+            .collect();
+
+        for result in &results {
+            assert_eq!(result.allocations + result.frees, 500);
+            assert_eq!(result.latencies_ns.len(), 500);
+            assert_eq!(result.samples.len(), 10);
+            assert!(result.percentile(50.0) <= result.percentile(99.0));
+            assert!(result.failure_rate() <= 1.0);
+        }
+
+        let table = format_table(&results);
+        assert!(table.contains("first-fit") && table.contains("best-fit") && table.contains("worst-fit"));
+        assert!(table.contains("Fragmentation over time:"));
+        assert_eq!(summary_csv(&results).lines().count(), 4);
+    }
+
+    #[test]
+    fn test_bench_options() {
+        let args: Vec<String> = ["--seed", "9", "--ops", "100", "--strategy", "best-fit", "--sizes", "uniform:1:10"]
+            .iter().map(|arg| arg.to_string()).collect();
+        let options = BenchOptions::parse(&args).expect("Options should parse");
+        assert_eq!(options.workload.seed, 9);
+        assert_eq!(options.workload.operations, 100);
+        assert_eq!(options.strategies, vec![PlacementStrategy::BestFit]);
+
+        assert!(BenchOptions::parse(&["--ops".to_string()]).is_err());
+        assert!(BenchOptions::parse(&["--bogus".to_string()]).is_err());
+    }
+}